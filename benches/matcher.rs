@@ -0,0 +1,84 @@
+//! Đo tốc độ dò mũi tên trên các ảnh chụp lưu sẵn trong `benches/fixtures` (thêm ảnh chụp game thật vào đó để đo).
+//! So với quét vét cạn 1 tỉ lệ để thấy lợi của dò lại quanh chỗ cũ (ROI) và kim tự tháp 2 tầng.
+//! Chạy: cargo bench --bench matcher
+#![allow(dead_code)]
+
+#[path = "../src/config.rs"]
+mod config;
+#[path = "../src/matcher.rs"]
+mod matcher;
+
+use config::MatchSettings;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use image::RgbaImage;
+use matcher::TemplateMatcher;
+use std::path::Path;
+
+const ARROW: &[u8] = include_bytes!("../src/arrow.png");
+
+fn screenshots() -> Vec<(String, RgbaImage)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("benches").join("fixtures");
+    let mut files: Vec<_> = std::fs::read_dir(&dir).expect("benches/fixtures")
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().and_then(|e| e.to_str()).map_or(false, |e| e.eq_ignore_ascii_case("png")))
+        .collect();
+    files.sort();
+    files.into_iter().map(|p| {
+        let name = p.file_stem().and_then(|s| s.to_str()).unwrap_or("").to_string();
+        (name, image::open(&p).expect("ảnh chụp hỏng").to_rgba8())
+    }).collect()
+}
+
+/// Cách làm cũ: ZNCC ở mọi vị trí, tỉ lệ gốc, không tầng thô
+fn exhaustive(template: &RgbaImage, frame: &RgbaImage) -> f32 {
+    let gray = |img: &RgbaImage| img.pixels().map(|p| (p[0] as f64 * 299.0 + p[1] as f64 * 587.0 + p[2] as f64 * 114.0) / 1000.0).collect::<Vec<_>>();
+    let (tw, th) = (template.width() as usize, template.height() as usize);
+    let (fw, fh) = (frame.width() as usize, frame.height() as usize);
+    let (tpl, img) = (gray(template), gray(frame));
+    let mean = tpl.iter().sum::<f64>() / tpl.len() as f64;
+    let tpl: Vec<f64> = tpl.iter().map(|v| v - mean).collect();
+    let norm = tpl.iter().map(|v| v * v).sum::<f64>().sqrt();
+    let mut best = 0.0f64;
+    for y in 0..=fh.saturating_sub(th) {
+        for x in 0..=fw.saturating_sub(tw) {
+            let (mut sum, mut sq, mut dot) = (0.0, 0.0, 0.0);
+            for row in 0..th {
+                for col in 0..tw {
+                    let v = img[(y + row) * fw + x + col];
+                    sum += v;
+                    sq += v * v;
+                    dot += v * tpl[row * tw + col];
+                }
+            }
+            let variance = sq - sum * sum / (tw * th) as f64;
+            if variance > 1e-6 { best = best.max(dot / (variance.sqrt() * norm)); }
+        }
+    }
+    best as f32
+}
+
+fn bench_matcher(c: &mut Criterion) {
+    let settings = MatchSettings::default();
+    let template = image::load_from_memory(ARROW).unwrap().to_rgba8();
+    for (name, frame) in screenshots() {
+        let mut group = c.benchmark_group(name);
+
+        // Mũi tên đứng yên qua nhiều khung hình: đa số lần dò chỉ xét ô quanh chỗ cũ
+        let mut tracking = TemplateMatcher::new(&template, &settings);
+        tracking.find(&frame);
+        group.bench_function("tracking", |b| b.iter(|| tracking.find(&frame)));
+
+        // Lần đầu / vừa mất dấu: quét cả ảnh mọi tỉ lệ qua tầng thô
+        group.bench_function("full_scan", |b| b.iter_batched(
+            || TemplateMatcher::new(&template, &settings),
+            |mut m| m.find(&frame),
+            BatchSize::SmallInput,
+        ));
+
+        group.bench_function("exhaustive_1x", |b| b.iter(|| exhaustive(&template, &frame)));
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_matcher);
+criterion_main!(benches);
@@ -0,0 +1,227 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use image::DynamicImage;
+use image::imageops::FilterType;
+use crate::config::Config;
+use crate::context::{self, DialogueLine};
+use crate::translation::TranslationResult;
+
+// dHash 32x16: đủ chi tiết để 2 câu thoại khác nhau trong cùng 1 khung cho hash khác nhau,
+// nhưng vẫn bỏ qua nhiễu nén / viền nhấp nháy nhỏ
+const HASH_WIDTH: u32 = 32;
+const HASH_HEIGHT: u32 = 16;
+const HASH_WORDS: usize = (HASH_WIDTH * HASH_HEIGHT / 64) as usize;
+// Số bit khác nhau tối đa (trên 512) vẫn coi là cùng 1 khung hình
+const MAX_DISTANCE: u32 = 6;
+// Gộp các lần thêm trong khoảng này thành 1 lần ghi file
+const SAVE_DELAY_SECS: u64 = 5;
+
+pub type ImageHash = [u64; HASH_WORDS];
+
+static CACHE: OnceLock<Mutex<TranslationCache>> = OnceLock::new();
+static HITS: AtomicU64 = AtomicU64::new(0);
+static MISSES: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Serialize, Deserialize, Clone)]
+struct CacheEntry {
+    image_hash: ImageHash,
+    scope: u64,
+    translated_text: String,
+    #[serde(default)]
+    original_text: Option<String>,
+    #[serde(default)]
+    speaker: Option<String>,
+    #[serde(default)]
+    detected_language: Option<String>,
+}
+
+/// LRU nhỏ: phần tử mới dùng nằm ở đầu hàng đợi
+pub struct TranslationCache {
+    entries: VecDeque<CacheEntry>,
+    capacity: usize,
+    loaded_from_disk: bool,
+    // Đã hẹn ghi file, các lần thêm sau đó chỉ sửa trong RAM
+    save_pending: bool,
+}
+
+/// Perceptual hash (dHash) của ảnh đã nén (PNG/JPEG/WebP)
+pub fn image_hash(png_bytes: &[u8]) -> Option<ImageHash> {
+    Some(hash_image(&image::load_from_memory(png_bytes).ok()?))
+}
+
+/// dHash: so sánh độ sáng các ô kề nhau theo hàng ngang
+pub fn hash_image(img: &DynamicImage) -> ImageHash {
+    let img = img.to_luma8();
+    let small = image::imageops::resize(&img, HASH_WIDTH + 1, HASH_HEIGHT, FilterType::Triangle);
+    let mut hash = [0u64; HASH_WORDS];
+    let mut bit = 0usize;
+    for y in 0..HASH_HEIGHT {
+        for x in 0..HASH_WIDTH {
+            if small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0] {
+                hash[bit / 64] |= 1 << (bit % 64);
+            }
+            bit += 1;
+        }
+    }
+    hash
+}
+
+/// Số bit khác nhau giữa 2 hash
+pub fn distance(a: &ImageHash, b: &ImageHash) -> u32 {
+    a.iter().zip(b.iter()).map(|(x, y)| (x ^ y).count_ones()).sum()
+}
+
+/// Gộp provider + model + prompt + các câu thoại trước gửi kèm thành 1 số (FNV-1a, ổn định giữa các lần chạy để lưu ra đĩa).
+/// Cùng khung hình nhưng ngữ cảnh khác thì bản dịch có thể khác nên không dùng lại.
+pub fn scope_key(config: &Config, provider_id: &str, prompt: &str, history: &[DialogueLine]) -> u64 {
+    let model = match provider_id {
+        "openai" => config.openai_model.as_str(),
+        "ollama" => config.ollama_model.as_str(),
+        _ => config.model_override.as_str(),
+    };
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for part in [provider_id, model, prompt, &context::format_lines(history)] {
+        for byte in part.bytes().chain(std::iter::once(0)) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+impl TranslationCache {
+    fn new(capacity: usize) -> Self {
+        Self { entries: VecDeque::new(), capacity: capacity.max(1), loaded_from_disk: false, save_pending: false }
+    }
+
+    fn get_path() -> PathBuf {
+        Config::get_config_dir().join("translation_cache.json")
+    }
+
+    fn load_disk(&mut self) {
+        if self.loaded_from_disk { return; }
+        self.loaded_from_disk = true;
+        if let Ok(content) = fs::read_to_string(Self::get_path()) {
+            if let Ok(entries) = serde_json::from_str::<Vec<CacheEntry>>(&content) {
+                self.entries = entries.into_iter().take(self.capacity).collect();
+            }
+        }
+    }
+
+    fn save_disk(&self) {
+        let path = Self::get_path();
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        if let Ok(content) = serde_json::to_string(&self.entries) {
+            let _ = fs::write(path, content);
+        }
+    }
+
+    fn lookup(&mut self, hash: &ImageHash, scope: u64) -> Option<TranslationResult> {
+        let index = self.entries.iter().position(|e| e.scope == scope && distance(&e.image_hash, hash) <= MAX_DISTANCE)?;
+        let entry = self.entries.remove(index)?;
+        let result = TranslationResult {
+            translated_text: entry.translated_text.clone(),
+            original_text: entry.original_text.clone(),
+            speaker: entry.speaker.clone(),
+            detected_language: entry.detected_language.clone(),
+            remaining_requests: None,
+            from_cache: true,
+        };
+        self.entries.push_front(entry);
+        Some(result)
+    }
+
+    fn insert(&mut self, hash: ImageHash, scope: u64, result: &TranslationResult) {
+        self.entries.retain(|e| !(e.scope == scope && distance(&e.image_hash, &hash) <= MAX_DISTANCE));
+        self.entries.push_front(CacheEntry {
+            image_hash: hash,
+            scope,
+            translated_text: result.translated_text.clone(),
+            original_text: result.original_text.clone(),
+            speaker: result.speaker.clone(),
+            detected_language: result.detected_language.clone(),
+        });
+        self.entries.truncate(self.capacity);
+    }
+}
+
+fn cache(config: &Config) -> &'static Mutex<TranslationCache> {
+    CACHE.get_or_init(|| Mutex::new(TranslationCache::new(config.translation_cache_size)))
+}
+
+pub fn get(config: &Config, hash: &ImageHash, scope: u64) -> Option<TranslationResult> {
+    let mut cache = cache(config).lock().unwrap();
+    cache.capacity = config.translation_cache_size.max(1);
+    if config.translation_cache_on_disk { cache.load_disk(); }
+    match cache.lookup(hash, scope) {
+        Some(result) => {
+            HITS.fetch_add(1, Ordering::Relaxed);
+            Some(result)
+        }
+        None => {
+            MISSES.fetch_add(1, Ordering::Relaxed);
+            None
+        }
+    }
+}
+
+pub fn put(config: &Config, hash: ImageHash, scope: u64, result: &TranslationResult) {
+    let mut cache = cache(config).lock().unwrap();
+    cache.insert(hash, scope, result);
+    if config.translation_cache_on_disk && !cache.save_pending {
+        cache.save_pending = true;
+        std::thread::spawn(|| {
+            std::thread::sleep(std::time::Duration::from_secs(SAVE_DELAY_SECS));
+            flush();
+        });
+    }
+}
+
+/// Ghi ngay phần cache chưa lưu ra đĩa (hẹn giờ ghi, hoặc trước khi thoát app)
+pub fn flush() {
+    if let Some(cache) = CACHE.get() {
+        let mut cache = cache.lock().unwrap();
+        if std::mem::replace(&mut cache.save_pending, false) { cache.save_disk(); }
+    }
+}
+
+/// Xóa cache trong RAM, file trên đĩa và bộ đếm
+pub fn clear() {
+    if let Some(cache) = CACHE.get() {
+        cache.lock().unwrap().entries.clear();
+    }
+    let _ = fs::remove_file(TranslationCache::get_path());
+    HITS.store(0, Ordering::Relaxed);
+    MISSES.store(0, Ordering::Relaxed);
+}
+
+/// (hit, miss) kể từ lần khởi động / lần xóa gần nhất
+pub fn stats() -> (u64, u64) {
+    (HITS.load(Ordering::Relaxed), MISSES.load(Ordering::Relaxed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(original: &str, translated: &str) -> DialogueLine {
+        DialogueLine { original: original.to_string(), translated: translated.to_string(), speaker: None }
+    }
+
+    #[test]
+    fn scope_depends_on_history() {
+        let config = Config::default();
+        let empty = scope_key(&config, "groq", "Translate", &[]);
+        assert_eq!(empty, scope_key(&config, "groq", "Translate", &[]));
+        let with_context = scope_key(&config, "groq", "Translate", &[line("你好", "Xin chào")]);
+        assert_ne!(empty, with_context);
+        assert_ne!(with_context, scope_key(&config, "groq", "Translate", &[line("你好", "Chào ngài")]));
+        assert_ne!(empty, scope_key(&config, "gemini", "Translate", &[]));
+    }
+}
@@ -1,13 +1,27 @@
-use image::{DynamicImage, ImageFormat, imageops::FilterType, RgbaImage};
+use image::DynamicImage;
 use screenshots::Screen;
-use std::sync::Mutex;
-use std::sync::atomic::{AtomicU8, Ordering};
-
-// Biến toàn cục để lưu lại "kích thước mũi tên đúng nhất"
-static CACHED_TEMPLATE: Mutex<Option<RgbaImage>> = Mutex::new(None);
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use crate::config::Region;
+use crate::matcher::{MatchResult, TemplateMatcher};
+
+// Thư mục ảnh PNG thay cho màn hình thật (chạy không cần desktop / CI) và thời gian mỗi khung (ms)
+const FIXTURE_DIR_ENV: &str = "SCREEN_TRANSLATOR_FIXTURES";
+const FIXTURE_FRAME_MS_ENV: &str = "SCREEN_TRANSLATOR_FIXTURE_MS";
+const DEFAULT_FIXTURE_FRAME_MS: u64 = 500;
+
+// Nguồn ảnh đang dùng, None = màn hình thật
+static SOURCE: RwLock<Option<Arc<dyn CaptureSource>>> = RwLock::new(None);
+
+/// Nơi lấy ảnh cho mọi lần chụp vùng (dịch, dò mũi tên, kích hoạt auto)
+pub trait CaptureSource: Send + Sync {
+    /// Ảnh của vùng theo toạ độ màn hình ảo
+    fn capture(&self, region: &Region) -> Result<DynamicImage, anyhow::Error>;
+}
 
-// Bộ đếm để hạn chế tần suất quét kỹ
-static SCAN_COUNTER: AtomicU8 = AtomicU8::new(0);
+/// Chụp màn hình thật
+pub struct ScreenSource;
 
 /// Tìm màn hình chứa điểm (x, y)
 fn find_screen_containing(x: i32, y: i32, screens: &[Screen]) -> Option<&Screen> {
@@ -20,173 +34,134 @@ fn find_screen_containing(x: i32, y: i32, screens: &[Screen]) -> Option<&Screen>
     })
 }
 
-pub fn capture_image(region: &crate::config::Region) -> Result<Vec<u8>, anyhow::Error> {
-    let screens = Screen::all()?;
-    if screens.is_empty() { return Err(anyhow::anyhow!("No screens found")); }
-
-    let screen = find_screen_containing(region.x, region.y, &screens)
-        .unwrap_or(&screens[0]);
-
-    let relative_x = region.x - screen.display_info.x;
-    let relative_y = region.y - screen.display_info.y;
-
-    let image = screen.capture_area(relative_x, relative_y, region.width, region.height)?;
-    let img = DynamicImage::ImageRgba8(image);
-    let mut buffer = Vec::new();
-    img.write_to(&mut std::io::Cursor::new(&mut buffer), ImageFormat::Png)?;
-    Ok(buffer)
+/// Vùng gắn cửa sổ game được tính lại theo vị trí cửa sổ lúc chụp
+#[cfg(windows)]
+fn resolve(region: &Region) -> Region {
+    crate::window::resolve(region)
 }
 
-pub fn is_template_present(region: &crate::config::Region, template_bytes: &[u8]) -> bool {
-    let screens = match Screen::all() {
-        Ok(s) => s,
-        Err(_) => return false,
-    };
-    if screens.is_empty() { return false; }
-
-    let screen = find_screen_containing(region.x, region.y, &screens)
-        .unwrap_or(&screens[0]);
-
-    let relative_x = region.x - screen.display_info.x;
-    let relative_y = region.y - screen.display_info.y;
-
-    let captured_image = match screen.capture_area(relative_x, relative_y, region.width, region.height) {
-        Ok(img) => DynamicImage::ImageRgba8(img),
-        Err(_) => return false,
-    };
+/// Không có cửa sổ để gắn: dùng vị trí lúc lưu
+#[cfg(not(windows))]
+fn resolve(region: &Region) -> Region {
+    Region { anchor: None, ..region.clone() }
+}
 
-    let haystack = captured_image.to_rgba8();
+impl CaptureSource for ScreenSource {
+    fn capture(&self, region: &Region) -> Result<DynamicImage, anyhow::Error> {
+        let region = &resolve(region);
+        let screens = Screen::all()?;
+        if screens.is_empty() { return Err(anyhow::anyhow!("No screens found")); }
 
-    let template_original = match image::load_from_memory(template_bytes) {
-        Ok(img) => img.to_rgba8(),
-        Err(_) => return false,
-    };
+        let screen = find_screen_containing(region.x, region.y, &screens)
+            .unwrap_or(&screens[0]);
 
-    // --- CHIẾN LƯỢC QUÉT ---
+        let relative_x = region.x - screen.display_info.x;
+        let relative_y = region.y - screen.display_info.y;
 
-    // 1. Kiểm tra Cache trước
-    {
-        let cached = CACHED_TEMPLATE.lock().unwrap();
-        if let Some(ref cached_img) = *cached {
-            if check_match_at_scale(&haystack, cached_img) {
-                SCAN_COUNTER.store(0, Ordering::Relaxed);
-                return true;
-            }
-        }
+        let image = screen.capture_area(relative_x, relative_y, region.width, region.height)?;
+        Ok(DynamicImage::ImageRgba8(image))
     }
+}
+
+/// Phát lại các ảnh chụp toàn màn hình (PNG, xếp theo tên file) thay cho màn hình thật.
+/// Góc trên trái của mỗi ảnh là (0, 0) của màn hình ảo, vùng gắn cửa sổ dùng vị trí lúc lưu.
+/// Mỗi khung hiện trong `frame_interval` rồi sang khung sau, dừng ở khung cuối (0 = chỉ dùng khung đầu).
+pub struct FixtureSource {
+    frames: Vec<DynamicImage>,
+    started: Instant,
+    frame_interval: Duration,
+}
 
-    // 2. Kiểm tra ảnh gốc
-    if check_match_at_scale(&haystack, &template_original) {
-        let mut cached = CACHED_TEMPLATE.lock().unwrap();
-        *cached = Some(template_original);
-        SCAN_COUNTER.store(0, Ordering::Relaxed);
-        return true;
+impl FixtureSource {
+    /// `path` là 1 file ảnh hoặc thư mục chứa các file .png
+    pub fn new(path: &Path, frame_interval: Duration) -> Result<Self, anyhow::Error> {
+        let mut files: Vec<PathBuf> = if path.is_dir() {
+            std::fs::read_dir(path)?
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| p.extension().and_then(|e| e.to_str()).map_or(false, |e| e.eq_ignore_ascii_case("png")))
+                .collect()
+        } else {
+            vec![path.to_path_buf()]
+        };
+        files.sort();
+        let frames = files.iter().map(image::open).collect::<Result<Vec<_>, _>>()?;
+        if frames.is_empty() { return Err(anyhow::anyhow!("No PNG frames in {}", path.display())); }
+        Ok(Self { frames, started: Instant::now(), frame_interval })
     }
 
-    // 3. Quét đa tỉ lệ (Chạy mỗi 5 frame)
-    let counter = SCAN_COUNTER.fetch_add(1, Ordering::Relaxed);
-    if counter % 5 != 0 {
-        return false;
+    fn frame_index(&self) -> usize {
+        if self.frame_interval.is_zero() { return 0; }
+        let index = (self.started.elapsed().as_millis() / self.frame_interval.as_millis()) as usize;
+        index.min(self.frames.len() - 1)
     }
+}
 
-    // Mở rộng dải scale một chút để dễ bắt hơn, nhưng vẫn tránh mức 0.5 (quá nhỏ)
-    let scales = [
-        1.0,
-        0.95, 1.05,
-        0.9, 1.1,
-        0.8, 1.2,
-        0.75, 1.25,
-        0.7, 1.3
-    ];
-
-    for scale in scales {
-        let (orig_w, orig_h) = template_original.dimensions();
-        let new_w = (orig_w as f32 * scale) as u32;
-        let new_h = (orig_h as f32 * scale) as u32;
-
-        if new_w < 5 || new_h < 5 { continue; }
-
-        let template_resized = image::imageops::resize(
-            &template_original,
-            new_w,
-            new_h,
-            FilterType::Lanczos3
-        );
-
-        if check_match_at_scale(&haystack, &template_resized) {
-            let mut cached = CACHED_TEMPLATE.lock().unwrap();
-            *cached = Some(template_resized);
-            SCAN_COUNTER.store(0, Ordering::Relaxed);
-            return true;
+impl CaptureSource for FixtureSource {
+    fn capture(&self, region: &Region) -> Result<DynamicImage, anyhow::Error> {
+        let frame = &self.frames[self.frame_index()];
+        let (x, y) = (region.x.max(0) as u32, region.y.max(0) as u32);
+        if region.x < 0 || region.y < 0 || x + region.width > frame.width() || y + region.height > frame.height() {
+            return Err(anyhow::anyhow!("Region {:?} is outside the {}x{} fixture frame", (region.x, region.y, region.width, region.height), frame.width(), frame.height()));
         }
+        Ok(frame.crop_imm(x, y, region.width, region.height))
     }
-
-    false
 }
 
-fn check_match_at_scale(haystack: &RgbaImage, needle: &RgbaImage) -> bool {
-    let (w_h, h_h) = haystack.dimensions();
-    let (w_n, h_n) = needle.dimensions();
-
-    if w_h < w_n || h_h < h_n { return false; }
-
-    // --- CẤU HÌNH ĐỘ DỄ TÍNH ---
-
-    // Color Tolerance: Tăng lên 80 (Rất cao)
-    // Cho phép mũi tên màu trắng bị mờ xuống màu xám đậm vẫn nhận ra.
-    let color_tolerance = 80;
-
-    // Match Threshold: Giảm xuống 0.70 (70%)
-    // Chỉ cần khớp 70% diện tích là được.
-    let match_threshold = 0.70;
+/// Đổi nguồn ảnh (vd. ảnh mẫu khi chạy thử), None = quay lại màn hình thật
+pub fn set_source(source: Option<Arc<dyn CaptureSource>>) {
+    *SOURCE.write().unwrap() = source;
+}
 
-    let limit_x = w_h - w_n;
-    let limit_y = h_h - h_n;
+/// Dùng thư mục ảnh trong biến môi trường `SCREEN_TRANSLATOR_FIXTURES` nếu có
+pub fn init_from_env() -> Result<(), anyhow::Error> {
+    let dir = match std::env::var(FIXTURE_DIR_ENV) {
+        Ok(dir) if !dir.trim().is_empty() => dir,
+        _ => return Ok(()),
+    };
+    let frame_ms = std::env::var(FIXTURE_FRAME_MS_ENV).ok().and_then(|v| v.trim().parse().ok()).unwrap_or(DEFAULT_FIXTURE_FRAME_MS);
+    let source = FixtureSource::new(Path::new(dir.trim()), Duration::from_millis(frame_ms))?;
+    set_source(Some(Arc::new(source)));
+    Ok(())
+}
 
-    // Quét qua haystack (Nhảy bước 2 pixel để tìm nhanh)
-    for y in (0..=limit_y).step_by(2) {
-        for x in (0..=limit_x).step_by(2) {
-            if fuzzy_match(haystack, needle, x, y, color_tolerance, match_threshold) {
-                return true;
-            }
-        }
-    }
-    false
+/// Nguồn ảnh đang dùng
+pub fn source() -> Arc<dyn CaptureSource> {
+    SOURCE.read().unwrap().clone().unwrap_or_else(|| Arc::new(ScreenSource))
 }
 
-fn fuzzy_match(haystack: &RgbaImage, needle: &RgbaImage, sx: u32, sy: u32, tol: i16, threshold: f32) -> bool {
-    let (w, h) = needle.dimensions();
-    let mut total_checked = 0;
-    let mut matched_count = 0;
+/// Ảnh chụp chưa nén, để bước xử lý ảnh (preprocess) tự chọn cách nén
+pub fn capture_raw(region: &Region) -> Result<DynamicImage, anyhow::Error> {
+    source().capture(region)
+}
 
-    // Quét từng pixel của mũi tên mẫu
-    for y in 0..h {
-        for x in 0..w {
-            let p_n = needle.get_pixel(x, y);
+/// Chụp vùng 1 lần và tìm lần lượt các ảnh mẫu (mũi tên), trả về mẫu đầu tiên đạt ngưỡng (vị trí trong vùng)
+pub fn find_template(source: &dyn CaptureSource, region: &Region, matchers: &mut [TemplateMatcher]) -> Option<MatchResult> {
+    let haystack = source.capture(region).ok()?.into_rgba8();
+    matchers.iter_mut().find_map(|m| m.find(&haystack))
+}
 
-            // Chỉ kiểm tra những điểm thực sự là mũi tên (Alpha > 50)
-            if p_n[3] < 50 { continue; }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            total_checked += 1;
-            let p_h = haystack.get_pixel(sx + x, sy + y);
+    fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join("auto")
+    }
 
-            if pixel_diff(p_h, p_n) <= tol {
-                matched_count += 1;
-            }
+    #[test]
+    fn fixture_source_crops_regions() {
+        // Thư mục: khung đầu theo tên file là blank.png
+        let source = FixtureSource::new(&fixtures(), Duration::ZERO).unwrap();
+        assert_eq!(source.frames.len(), 4);
+        let region = Region { x: 140, y: 150, width: 40, height: 30, anchor: None };
+        let img = source.capture(&region).unwrap();
+        assert_eq!((img.width(), img.height()), (40, 30));
+        let blank = image::open(fixtures().join("blank.png")).unwrap();
+        assert_eq!(img.to_rgba8(), blank.crop_imm(140, 150, 40, 30).to_rgba8());
+
+        for outside in [Region { x: 300, ..region.clone() }, Region { y: -1, ..region.clone() }] {
+            assert!(source.capture(&outside).is_err());
         }
+        assert!(FixtureSource::new(&fixtures().join("missing.png"), Duration::ZERO).is_err());
     }
-
-    if total_checked == 0 { return true; }
-
-    // Tính tỉ lệ khớp
-    (matched_count as f32 / total_checked as f32) >= threshold
 }
-
-fn pixel_diff(p1: &image::Rgba<u8>, p2: &image::Rgba<u8>) -> i16 {
-    let r = (p1[0] as i16 - p2[0] as i16).abs();
-    let g = (p1[1] as i16 - p2[1] as i16).abs();
-    let b = (p1[2] as i16 - p2[2] as i16).abs();
-
-    // Dùng trung bình cộng thay vì max để dễ tính hơn với các điểm nhiễu
-    (r + g + b) / 3
-}
\ No newline at end of file
@@ -1,6 +1,32 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Ngôn ngữ có sẵn trong danh sách chọn: (tên gửi cho LLM, mã Google TTS)
+pub const LANGUAGES: &[(&str, &str)] = &[
+    ("Vietnamese", "vi"),
+    ("English", "en"),
+    ("Japanese", "ja"),
+    ("Korean", "ko"),
+    ("Simplified Chinese", "zh-CN"),
+    ("Traditional Chinese", "zh-TW"),
+    ("Thai", "th"),
+    ("Indonesian", "id"),
+    ("French", "fr"),
+    ("German", "de"),
+    ("Spanish", "es"),
+    ("Portuguese", "pt"),
+    ("Russian", "ru"),
+];
+
+// Key bị 429 sẽ bị bỏ qua trong khoảng này trước khi được thử lại
+const KEY_COOLDOWN_SECS: u64 = 60;
+
+// Các thread dịch ghi key_usage.json lần lượt (đọc - sửa - ghi)
+static KEY_USAGE_LOCK: Mutex<()> = Mutex::new(());
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Region {
@@ -8,11 +34,303 @@ pub struct Region {
     pub y: i32,
     pub width: u32,
     pub height: u32,
+    // Gắn với cửa sổ game: x/y/width/height ở trên chỉ là vị trí lúc lưu (dùng khi không thấy cửa sổ)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<WindowAnchor>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/// Vị trí vùng theo tỉ lệ (0..1) vùng client của cửa sổ game, tính lại mỗi lần chụp
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WindowAnchor {
+    // Tiêu đề cửa sổ (khớp chính xác), rỗng = tìm theo tiến trình
+    #[serde(default)]
+    pub window_title: String,
+    // Tên file của tiến trình, vd. "wwm.exe"
+    #[serde(default)]
+    pub process_name: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl WindowAnchor {
+    /// Neo vùng `region` (toạ độ màn hình) vào cửa sổ có vùng client `bounds` = (x, y, rộng, cao)
+    pub fn from_region(region: &Region, window_title: &str, process_name: &str, bounds: (i32, i32, u32, u32)) -> Self {
+        let (bx, by, bw, bh) = bounds;
+        let (bw, bh) = (bw.max(1) as f32, bh.max(1) as f32);
+        Self {
+            window_title: window_title.to_string(),
+            process_name: process_name.to_string(),
+            x: (region.x - bx) as f32 / bw,
+            y: (region.y - by) as f32 / bh,
+            width: region.width as f32 / bw,
+            height: region.height as f32 / bh,
+        }
+    }
+
+    /// Vùng theo toạ độ màn hình khi cửa sổ đang ở `bounds`
+    pub fn place(&self, bounds: (i32, i32, u32, u32)) -> Region {
+        let (bx, by, bw, bh) = bounds;
+        let (bw, bh) = (bw as f32, bh as f32);
+        Region {
+            x: bx + (self.x * bw).round() as i32,
+            y: by + (self.y * bh).round() as i32,
+            width: (self.width * bw).round().max(1.0) as u32,
+            height: (self.height * bh).round().max(1.0) as u32,
+            anchor: None,
+        }
+    }
+
+    /// Tên hiển thị của cửa sổ được gắn
+    pub fn target(&self) -> &str {
+        if self.window_title.is_empty() { &self.process_name } else { &self.window_title }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct CustomPrompt {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
     pub content: String,
+    // Dùng provider / model riêng khi chọn prompt này (rỗng = theo cài đặt chung)
+    #[serde(default)]
+    pub provider: String,
+    #[serde(default)]
+    pub model: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HttpHeader {
+    pub name: String,
+    pub value: String,
+}
+
+/// Bộ đếm / cooldown nằm trong key_usage.json (thread dịch ghi), không ghi vào file config
+/// để lần lưu config của UI không đè lên; vẫn đọc được từ config của bản cũ.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ApiKey {
+    pub key: String,
+    // Thời điểm (unix giây) key được dùng lại sau khi bị 429
+    #[serde(default, skip_serializing)]
+    pub cooldown_until: u64,
+    // Ngày (tính từ epoch, UTC) của bộ đếm used_today
+    #[serde(default, skip_serializing)]
+    pub usage_day: u64,
+    #[serde(default, skip_serializing)]
+    pub used_today: u32,
+    // Số request còn lại do server báo (nếu có)
+    #[serde(default, skip_serializing)]
+    pub remaining: Option<i32>,
+}
+
+/// Bộ đếm của 1 key trong key_usage.json
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+struct KeyUsage {
+    cooldown_until: u64,
+    usage_day: u64,
+    used_today: u32,
+    remaining: Option<i32>,
+}
+
+/// key_usage.json của 1 provider; key lưu dạng hash để không chép key ra thêm 1 file
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+struct PoolUsage {
+    active: usize,
+    keys: BTreeMap<String, KeyUsage>,
+}
+
+/// FNV-1a của key, dạng hex
+fn key_id(key: &str) -> String {
+    let hash = key.bytes().fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+    format!("{:016x}", hash)
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum KeyStrategy {
+    // Lần lượt từng key sau mỗi request
+    #[default]
+    RoundRobin,
+    // Luôn chọn key dùng ít nhất trong ngày
+    LeastUsed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct KeyPool {
+    #[serde(default)]
+    pub keys: Vec<ApiKey>,
+    #[serde(default)]
+    pub active: usize,
+    #[serde(default)]
+    pub strategy: KeyStrategy,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn today() -> u64 {
+    now_secs() / 86400
+}
+
+impl ApiKey {
+    pub fn new(key: String) -> Self {
+        Self { key, ..Default::default() }
+    }
+
+    pub fn is_cooling_down(&self) -> bool {
+        self.cooldown_until > now_secs()
+    }
+
+    /// Số request đã dùng hôm nay (bộ đếm của ngày cũ coi như 0)
+    pub fn used_today(&self) -> u32 {
+        if self.usage_day == today() { self.used_today } else { 0 }
+    }
+}
+
+impl KeyPool {
+    fn usable(&self) -> impl Iterator<Item = (usize, &ApiKey)> {
+        self.keys.iter().enumerate().filter(|(_, k)| !k.key.trim().is_empty())
+    }
+
+    /// Chọn key cho request tiếp theo theo chiến lược, bỏ qua key đang cooldown.
+    /// Nếu mọi key đều cooldown thì lấy key sắp hết cooldown nhất.
+    fn select(&mut self) -> Option<usize> {
+        let len = self.keys.len();
+        if len == 0 { return None; }
+        let ready = |k: &ApiKey| !k.key.trim().is_empty() && !k.is_cooling_down();
+        let picked = match self.strategy {
+            KeyStrategy::RoundRobin => (0..len).map(|i| (self.active + i) % len).find(|&i| ready(&self.keys[i])),
+            KeyStrategy::LeastUsed => self.usable().filter(|(_, k)| !k.is_cooling_down()).min_by_key(|(_, k)| k.used_today()).map(|(i, _)| i),
+        };
+        let picked = picked.or_else(|| self.usable().min_by_key(|(_, k)| k.cooldown_until).map(|(i, _)| i))?;
+        self.active = picked;
+        Some(picked)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ClipboardMode {
+    Off,
+    Translation,
+    Original,
+}
+
+/// Cài đặt riêng của một vùng dịch. None = dùng cài đặt chung.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct RegionSettings {
+    #[serde(default)]
+    pub prompt: Option<String>,
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub use_tts: Option<bool>,
+    // Mã giọng đọc Google TTS (vd "vi", "en", "ja")
+    #[serde(default)]
+    pub tts_voice: Option<String>,
+    #[serde(default)]
+    pub show_overlay: Option<bool>,
+    #[serde(default)]
+    pub clipboard: Option<ClipboardMode>,
+}
+
+impl RegionSettings {
+    pub fn is_default(&self) -> bool {
+        self.prompt.is_none() && self.provider.is_none() && self.use_tts.is_none()
+            && self.tts_voice.is_none() && self.show_overlay.is_none() && self.clipboard.is_none()
+    }
+}
+
+/// Cách auto mode biết khi nào có câu thoại mới
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum TriggerMode {
+    // Mũi tên "tiếp tục" xuất hiện trong vùng mũi tên
+    Arrow,
+    // Chữ trong vùng dịch đổi rồi đứng yên (hiệu ứng gõ chữ đã chạy xong)
+    TextStable,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct TriggerSettings {
+    pub mode: TriggerMode,
+    // Số lần kiểm tra liên tiếp chữ không đổi mới dịch
+    pub stable_frames: u32,
+    // Số bit hash (trên 512) khác nhau vẫn coi là cùng nội dung
+    pub change_threshold: u32,
+}
+
+impl Default for TriggerSettings {
+    fn default() -> Self {
+        Self { mode: TriggerMode::Arrow, stable_frames: 3, change_threshold: 6 }
+    }
+}
+
+/// Độ nhạy khi dò ảnh mẫu (mũi tên) trên màn hình
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct MatchSettings {
+    // Độ tin cậy tối thiểu (0..1) để coi là thấy mẫu
+    pub threshold: f32,
+    // Bước quét thưa (pixel), dò kỹ lại quanh điểm tốt nhất
+    pub step: u32,
+    // Điểm ảnh mẫu có alpha thấp hơn mức này là nền trong suốt
+    pub alpha_cutoff: u8,
+}
+
+impl Default for MatchSettings {
+    fn default() -> Self {
+        Self { threshold: 0.8, step: 2, alpha_cutoff: 50 }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ImageEncoding {
+    Png,
+    Jpeg,
+    // Nén mất dữ liệu theo `quality` như JPEG, thường nhỏ hơn JPEG cùng chất lượng
+    WebP,
+}
+
+/// Xử lý ảnh chụp trước khi gửi đi dịch / OCR
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct PreprocessSettings {
+    pub enabled: bool,
+    // Cạnh dài nhất sau khi thu nhỏ (0 = giữ nguyên)
+    pub max_dimension: u32,
+    pub grayscale: bool,
+    // Tăng/giảm tương phản (%), 0 = giữ nguyên
+    pub contrast: f32,
+    // Ngưỡng tách chữ trắng/đen (0 = tắt)
+    pub threshold: u8,
+    // Màu nền cần xoá và độ lệch cho phép mỗi kênh màu
+    pub color_key: Option<[u8; 3]>,
+    pub color_key_tolerance: u8,
+    pub encoding: ImageEncoding,
+    // Chất lượng nén JPEG/WebP (1-100)
+    #[serde(alias = "jpeg_quality")]
+    pub quality: u8,
+}
+
+impl Default for PreprocessSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_dimension: 1280,
+            grayscale: false,
+            contrast: 0.0,
+            threshold: 0,
+            color_key: None,
+            color_key_tolerance: 40,
+            encoding: ImageEncoding::Png,
+            quality: 85,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -22,26 +340,49 @@ pub struct AuxRegion {
     pub region: Option<Region>,
     pub hotkey_select: String,
     pub hotkey_translate: String,
+    #[serde(default)]
+    pub settings: RegionSettings,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Config {
-    pub gemini_api_key: String,
-    #[serde(default)]
-    pub groq_api_keys: Vec<String>,
+    // Key của từng provider (id provider -> danh sách key xoay vòng)
     #[serde(default)]
-    pub active_groq_index: usize,
+    pub key_pools: BTreeMap<String, KeyPool>,
+
+    // --- Trường cũ: chỉ đọc để chuyển sang key_pools, không ghi lại ---
+    #[serde(default, skip_serializing)]
+    gemini_api_key: String,
+    #[serde(default, skip_serializing)]
+    groq_api_keys: Vec<String>,
+    #[serde(default, skip_serializing)]
+    active_groq_index: usize,
 
     pub current_prompt: String,
 
+    // Ngôn ngữ đích thay cho {target_lang} trong prompt, và giọng đọc TTS
+    #[serde(default = "default_target_language")]
+    pub target_language: String,
+    // Ngôn ngữ gốc (rỗng = tự nhận diện)
+    #[serde(default)]
+    pub source_language: String,
+
     #[serde(default)]
     pub saved_prompts: Vec<CustomPrompt>,
+    // Prompt trong thư viện đang được chọn (None = prompt có sẵn / tự nhập)
+    #[serde(default)]
+    pub active_prompt: Option<usize>,
+    // Model do prompt đang chọn chỉ định cho Gemini/Groq, chỉ tồn tại lúc dịch
+    #[serde(skip)]
+    pub model_override: String,
 
     pub hotkey_translate: String,
     pub hotkey_select: String,
     pub hotkey_instant: String,
     pub hotkey_auto: String,
     pub hotkey_toggle_auto: String,
+    #[serde(default = "default_hotkey_none")]
+    pub hotkey_clear_context: String,
     pub split_tts: bool,
     pub use_tts: bool,
     pub show_overlay: bool,
@@ -52,12 +393,96 @@ pub struct Config {
 
     #[serde(default)]
     pub aux_regions: Vec<AuxRegion>,
+    // Cài đặt riêng của vùng dịch chính (fixed_regions)
+    #[serde(default)]
+    pub main_region_settings: RegionSettings,
+    // Giọng đọc TTS (rỗng = theo ngôn ngữ đích)
+    #[serde(default)]
+    pub tts_voice: String,
+    #[serde(default)]
+    pub preprocess: PreprocessSettings,
+    // Cách kích hoạt auto mode: chung, và riêng theo tên game (active_game)
+    #[serde(default)]
+    pub auto_trigger: TriggerSettings,
+    #[serde(default)]
+    pub game_triggers: BTreeMap<String, TriggerSettings>,
 
     pub selected_api: String,
     pub speed: f32,
 
+    // --- Endpoint tương thích OpenAI (LM Studio, vLLM, OpenRouter, llama.cpp...) ---
+    #[serde(default = "default_openai_base_url")]
+    pub openai_base_url: String,
+    #[serde(default)]
+    pub openai_model: String,
+    #[serde(default, skip_serializing)]
+    openai_api_key: String,
+    #[serde(default)]
+    pub openai_extra_headers: Vec<HttpHeader>,
+    #[serde(default = "default_temperature")]
+    pub openai_temperature: f32,
+
+    // --- Ollama (chạy model vision trên máy, không cần internet) ---
+    #[serde(default = "default_ollama_base_url")]
+    pub ollama_base_url: String,
+    #[serde(default = "default_ollama_model")]
+    pub ollama_model: String,
+
+    // --- Pipeline OCR: Tesseract trên máy -> chỉ gửi text đi dịch ---
+    #[serde(default)]
+    pub use_ocr_pipeline: bool,
+    #[serde(default = "default_tesseract_path")]
+    pub tesseract_path: String,
+    #[serde(default = "default_ocr_language")]
+    pub ocr_language: String,
+    // Provider dùng cho bước dịch text (rỗng = dùng dịch vụ đang chọn)
+    #[serde(default)]
+    pub ocr_translator: String,
+
+    // --- Cache kết quả dịch theo perceptual hash của ảnh ---
+    #[serde(default = "default_true")]
+    pub use_translation_cache: bool,
+    #[serde(default)]
+    pub translation_cache_on_disk: bool,
+    #[serde(default = "default_cache_size")]
+    pub translation_cache_size: usize,
+
+    // --- Mạng: timeout mỗi request và số lần thử lại khi lỗi mạng / 5xx ---
+    #[serde(default = "default_http_timeout")]
+    pub http_timeout_secs: u64,
+    #[serde(default = "default_http_retries")]
+    pub http_max_retries: u32,
+    // Số vùng cố định được dịch cùng lúc
+    #[serde(default = "default_parallel_regions")]
+    pub max_parallel_regions: usize,
+    // Gửi mọi vùng trong 1 request nhiều ảnh (Gemini / OpenAI-compatible / Groq)
+    #[serde(default)]
+    pub batch_regions: bool,
+    // Hiện bản dịch dần trong khung loading khi provider hỗ trợ stream
+    #[serde(default = "default_true")]
+    pub stream_translation: bool,
+    // Đọc TTS ngay khi câu đầu tiên stream về xong
+    #[serde(default)]
+    pub tts_first_sentence: bool,
+
+    // --- Thuật ngữ (glossary.json) ---
+    #[serde(default = "default_true")]
+    pub use_glossary: bool,
+    #[serde(default = "default_true")]
+    pub glossary_auto_fix: bool,
+    // Game đang chơi, để lọc thuật ngữ theo game
+    #[serde(default)]
+    pub active_game: String,
+
+    // Số câu thoại trước đó gửi kèm làm ngữ cảnh (0 = tắt)
+    #[serde(default = "default_context_window")]
+    pub context_window: usize,
+
     #[serde(default = "default_interval")]
     pub arrow_check_interval: f32,
+    // Độ nhạy dò mũi tên
+    #[serde(default)]
+    pub arrow_match: MatchSettings,
     #[serde(default)]
     pub auto_copy: bool,
     #[serde(default)]
@@ -74,6 +499,10 @@ pub struct Config {
 
     #[serde(default)]
     pub freeze_screen: bool,
+
+    // Vùng mới khoanh sẽ gắn với cửa sổ game nằm dưới nó
+    #[serde(default)]
+    pub bind_regions_to_window: bool,
 }
 
 // --- SỬA Ở ĐÂY: Mặc định là 0.1 giây ---
@@ -81,20 +510,40 @@ fn default_interval() -> f32 { 0.1 }
 // ---------------------------------------
 fn default_font_size() -> i32 { 24 }
 fn default_dark_mode() -> bool { false }
+fn default_openai_base_url() -> String { "http://localhost:1234/v1".to_string() }
+fn default_temperature() -> f32 { 0.1 }
+fn default_ollama_base_url() -> String { "http://localhost:11434".to_string() }
+fn default_ollama_model() -> String { "llava".to_string() }
+fn default_tesseract_path() -> String { "tesseract".to_string() }
+fn default_ocr_language() -> String { "chi_sim+eng".to_string() }
+fn default_true() -> bool { true }
+fn default_cache_size() -> usize { 200 }
+fn default_http_timeout() -> u64 { 60 }
+fn default_http_retries() -> u32 { 2 }
+fn default_parallel_regions() -> usize { 3 }
+fn default_context_window() -> usize { 3 }
+fn default_hotkey_none() -> String { "NONE".to_string() }
+fn default_target_language() -> String { "Vietnamese".to_string() }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            key_pools: BTreeMap::new(),
             gemini_api_key: String::new(),
             groq_api_keys: Vec::new(),
             active_groq_index: 0,
             current_prompt: Self::get_normal_prompt(),
+            target_language: default_target_language(),
+            source_language: String::new(),
             saved_prompts: Vec::new(),
+            active_prompt: None,
+            model_override: String::new(),
             hotkey_translate: "[".to_string(),
             hotkey_select: "]".to_string(),
             hotkey_instant: "\\".to_string(),
             hotkey_auto: ";".to_string(),
             hotkey_toggle_auto: "=".to_string(),
+            hotkey_clear_context: default_hotkey_none(),
             split_tts: true,
             use_tts: true,
             show_overlay: true,
@@ -103,47 +552,141 @@ impl Default for Config {
             arrow_region: None,
             instant_region: None,
             aux_regions: Vec::new(),
+            main_region_settings: RegionSettings::default(),
+            tts_voice: String::new(),
+            preprocess: PreprocessSettings::default(),
+            auto_trigger: TriggerSettings::default(),
+            game_triggers: BTreeMap::new(),
             selected_api: "groq".to_string(),
+            openai_base_url: default_openai_base_url(),
+            openai_model: String::new(),
+            openai_api_key: String::new(),
+            openai_extra_headers: Vec::new(),
+            openai_temperature: default_temperature(),
+            ollama_base_url: default_ollama_base_url(),
+            ollama_model: default_ollama_model(),
+            use_ocr_pipeline: false,
+            tesseract_path: default_tesseract_path(),
+            ocr_language: default_ocr_language(),
+            ocr_translator: String::new(),
+            use_translation_cache: true,
+            translation_cache_on_disk: false,
+            translation_cache_size: default_cache_size(),
+            http_timeout_secs: default_http_timeout(),
+            http_max_retries: default_http_retries(),
+            max_parallel_regions: default_parallel_regions(),
+            batch_regions: false,
+            stream_translation: true,
+            tts_first_sentence: false,
+            use_glossary: true,
+            glossary_auto_fix: true,
+            active_game: String::new(),
+            context_window: default_context_window(),
             // Đảm bảo load mặc định cũng là 0.1
             arrow_check_interval: 0.1,
+            arrow_match: MatchSettings::default(),
             auto_copy: false,
             copy_instant_only: false,
             copy_original: false,
             overlay_font_size: 24,
             is_dark_mode: false,
             freeze_screen: false,
+            bind_regions_to_window: false,
         }
     }
 }
 
 impl Config {
     pub fn get_wuxia_prompt() -> String {
-        "Perform OCR to extract all text from this image, regardless of the source language. Then, translate the extracted text into Vietnamese. The translation must strictly use vocabulary and tone consistent with wuxia novels, make it as short as possible. Crucially, provide ONLY the translated text and nothing else. Do not include any introductory phrases, explanations, or conversational elements. Note: just output the translated text and make it as short as possible".to_string()
+        "Perform OCR to extract all text from this image, regardless of the source language. Then, translate the extracted text into {target_lang}. The translation must strictly use vocabulary and tone consistent with wuxia novels, make it as short as possible. Crucially, provide ONLY the translated text and nothing else. Do not include any introductory phrases, explanations, or conversational elements. Note: just output the translated text and make it as short as possible".to_string()
     }
 
     pub fn get_wuxia_speaker_prompt() -> String {
         "Perform OCR to extract text. Check if a character name appears at the start. 
-        1. If Name exists: Output format 'Name Verb: Translated Text'. (Choose verb based on context: nói, cười lạnh, quát, than...).
+        1. If Name exists: Output format 'Name Verb: Translated Text'. (Choose a {target_lang} speech verb based on context, e.g. in Vietnamese: nói, cười lạnh, quát, than...).
         2. If NO Name exists: Output ONLY the 'Translated Text'.
         CRITICAL RULES:
         - Do NOT output phrases like 'No character name found', 'No name detected', or any explanations.
         - If you are unsure, just output the translation.
-        - Translate to Vietnamese wuxia style (Kiếm hiệp).
+        - Translate to {target_lang} in wuxia style (Kiếm hiệp).
         - Do NOT use quotation marks.".to_string()
     }
 
     pub fn get_normal_prompt() -> String {
-        "Perform OCR to extract all text visible in this image, regardless of the original language. Then, translate the extracted text directly into Vietnamese. Return only the Vietnamese translation, no introduction or notes.".to_string()
+        "Perform OCR to extract all text visible in this image, regardless of the original language. Then, translate the extracted text directly into {target_lang}. Return only the {target_lang} translation, no introduction or notes.".to_string()
     }
 
     pub fn get_analyze_prompt() -> String {
-        "Analyze the content of this image and provide a brief description in Vietnamese. Strictly output the analysis only, keep it short and concise. Do not add any introductory text or extra notes.".to_string()
+        "Analyze the content of this image and provide a brief description in {target_lang}. Strictly output the analysis only, keep it short and concise. Do not add any introductory text or extra notes.".to_string()
     }
 
     pub fn get_extract_text_prompt() -> String {
         "Perform OCR to extract all text from this image exactly as it appears. Output ONLY the extracted text, regardless of the language. Do not add any notes or explanations.".to_string()
     }
 
+    /// Tên ngôn ngữ đích thay cho {target_lang}
+    pub fn target_lang_name(&self) -> &str {
+        if self.target_language.trim().is_empty() { "Vietnamese" } else { self.target_language.trim() }
+    }
+
+    /// Tên ngôn ngữ gốc thay cho {source_lang}
+    pub fn source_lang_name(&self) -> &str {
+        if self.source_language.trim().is_empty() { "the original language" } else { self.source_language.trim() }
+    }
+
+    /// Bản config để dịch một vùng: áp các cài đặt riêng của vùng lên cài đặt chung.
+    /// Trả về kèm cờ có copy kết quả hay không.
+    pub fn for_region(&self, settings: &RegionSettings, should_copy: bool) -> (Config, bool) {
+        let mut config = self.clone();
+        let mut should_copy = should_copy;
+        if let Some(prompt) = &settings.prompt {
+            config.current_prompt = prompt.clone();
+            // Prompt riêng của vùng thay cho prompt đang chọn trong thư viện
+            config.active_prompt = None;
+        }
+        if let Some(provider) = &settings.provider { config.selected_api = provider.clone(); }
+        if let Some(use_tts) = settings.use_tts { config.use_tts = use_tts; }
+        if let Some(voice) = &settings.tts_voice { config.tts_voice = voice.clone(); }
+        if let Some(show_overlay) = settings.show_overlay { config.show_overlay = show_overlay; }
+        match settings.clipboard {
+            Some(ClipboardMode::Off) => should_copy = false,
+            Some(ClipboardMode::Translation) => { should_copy = true; config.copy_original = false; }
+            Some(ClipboardMode::Original) => { should_copy = true; config.copy_original = true; }
+            None => {}
+        }
+        (config, should_copy)
+    }
+
+    pub fn active_saved_prompt(&self) -> Option<&CustomPrompt> {
+        self.active_prompt.and_then(|i| self.saved_prompts.get(i))
+    }
+
+    /// Áp provider / model riêng của prompt đang chọn (chỉ trên bản config dùng để dịch, không lưu)
+    pub fn apply_prompt_overrides(&mut self) {
+        let Some(prompt) = self.active_saved_prompt().cloned() else { return };
+        if !prompt.provider.is_empty() { self.selected_api = prompt.provider; }
+        if prompt.model.is_empty() { return; }
+        match self.selected_api.as_str() {
+            "openai" => self.openai_model = prompt.model,
+            "ollama" => self.ollama_model = prompt.model,
+            _ => self.model_override = prompt.model,
+        }
+    }
+
+    /// Mã ngôn ngữ TTS cho ngôn ngữ đích (tên lạ thì coi là đã nhập sẵn mã, vd "it")
+    pub fn tts_language_code(&self) -> &str {
+        if !self.tts_voice.trim().is_empty() { return self.tts_voice.trim(); }
+        let target = self.target_language.trim();
+        if target.is_empty() { return "vi"; }
+        LANGUAGES.iter().find(|(name, _)| name.eq_ignore_ascii_case(target)).map_or(target, |(_, code)| *code)
+    }
+
+    /// Cách kích hoạt auto của game đang chơi (chưa đặt riêng thì dùng cài đặt chung)
+    pub fn trigger_settings(&self) -> &TriggerSettings {
+        let game = self.active_game.trim();
+        self.game_triggers.iter().find(|(name, _)| !game.is_empty() && name.eq_ignore_ascii_case(game)).map_or(&self.auto_trigger, |(_, t)| t)
+    }
+
     pub fn get_config_dir() -> PathBuf {
         let home = std::env::var("USERPROFILE").unwrap_or_else(|_| ".".to_string());
         std::path::Path::new(&home).join(".screen_translator")
@@ -157,15 +700,23 @@ impl Config {
         Self::get_config_dir().join("custom_arrow.png")
     }
 
+    pub fn get_dictionary_path() -> PathBuf {
+        Self::get_config_dir().join("dictionary.tsv")
+    }
+
+    /// Provider dùng cho bước dịch text của pipeline OCR
+    pub fn ocr_translator_api(&self) -> String {
+        if self.ocr_translator.is_empty() { self.selected_api.clone() } else { self.ocr_translator.clone() }
+    }
+
     pub fn load() -> Self {
         let path = Self::get_config_path();
         if path.exists() {
             match fs::read_to_string(path) {
                 Ok(content) => {
                     let mut config: Config = serde_json::from_str(&content).unwrap_or_default();
-                    if config.active_groq_index >= config.groq_api_keys.len() && !config.groq_api_keys.is_empty() {
-                        config.active_groq_index = 0;
-                    }
+                    config.migrate_legacy_keys();
+                    config.apply_key_usage(&Self::load_key_usage());
                     if config.arrow_check_interval < 0.02 { config.arrow_check_interval = 0.02; }
                     if config.arrow_check_interval > 0.2 { config.arrow_check_interval = 0.2; }
                     if config.overlay_font_size < 10 { config.overlay_font_size = 10; }
@@ -190,9 +741,157 @@ impl Config {
         Ok(())
     }
 
-    pub fn get_current_groq_key(&self) -> String {
-        if self.groq_api_keys.is_empty() { return String::new(); }
-        if self.active_groq_index < self.groq_api_keys.len() { self.groq_api_keys[self.active_groq_index].clone() }
-        else { self.groq_api_keys[0].clone() }
+    /// Chuyển gemini_api_key / groq_api_keys / openai_api_key của bản cũ sang key_pools
+    fn migrate_legacy_keys(&mut self) {
+        let legacy = [
+            ("gemini", vec![std::mem::take(&mut self.gemini_api_key)]),
+            ("groq", std::mem::take(&mut self.groq_api_keys)),
+            ("openai", vec![std::mem::take(&mut self.openai_api_key)]),
+        ];
+        for (api, keys) in legacy {
+            let keys: Vec<String> = keys.into_iter().filter(|k| !k.trim().is_empty()).collect();
+            if keys.is_empty() || self.key_pools.get(api).map_or(false, |p| !p.keys.is_empty()) { continue; }
+            let active = if api == "groq" && self.active_groq_index < keys.len() { self.active_groq_index } else { 0 };
+            self.key_pools.insert(api.to_string(), KeyPool { keys: keys.into_iter().map(ApiKey::new).collect(), active, strategy: KeyStrategy::default() });
+        }
+        self.active_groq_index = 0;
+    }
+
+    pub fn key_pool(&self, api: &str) -> Option<&KeyPool> {
+        self.key_pools.get(api)
     }
-}
\ No newline at end of file
+
+    pub fn key_pool_mut(&mut self, api: &str) -> &mut KeyPool {
+        self.key_pools.entry(api.to_string()).or_default()
+    }
+
+    /// Số key có thể xoay vòng của một provider (tối thiểu 1)
+    pub fn key_count(&self, api: &str) -> usize {
+        self.key_pool(api).map_or(0, |p| p.usable().count()).max(1)
+    }
+
+    /// Chọn key cho request tiếp theo (rỗng nếu provider chưa có key)
+    pub fn get_current_key(&mut self, api: &str) -> String {
+        let pool = self.key_pool_mut(api);
+        match pool.select() {
+            Some(i) => pool.keys[i].key.clone(),
+            None => String::new(),
+        }
+    }
+
+    /// Ghi nhận 1 request thành công cho `key` (các vùng dịch song song có thể dùng key khác nhau)
+    pub fn record_key_usage(&mut self, api: &str, key: &str, remaining: Option<i32>) {
+        let pool = self.key_pool_mut(api);
+        let len = pool.keys.len();
+        let Some(index) = pool.keys.iter().position(|k| k.key == key) else { return };
+        if let Some(key) = pool.keys.get_mut(index) {
+            let day = today();
+            if key.usage_day != day {
+                key.usage_day = day;
+                key.used_today = 0;
+                key.remaining = None;
+            }
+            key.used_today += 1;
+            if remaining.is_some() { key.remaining = remaining; }
+            if pool.strategy == KeyStrategy::RoundRobin { pool.active = (index + 1) % len; }
+        }
+    }
+
+    /// Đánh dấu `key` bị 429 và chuyển sang key khác.
+    /// Trả về false nếu provider không còn key nào khác chưa bị cooldown.
+    pub fn rotate_key(&mut self, api: &str, key: &str) -> bool {
+        let pool = self.key_pool_mut(api);
+        let current = pool.keys.iter().position(|k| k.key == key);
+        if let Some(k) = current.and_then(|i| pool.keys.get_mut(i)) {
+            k.cooldown_until = now_secs() + KEY_COOLDOWN_SECS;
+            k.remaining = Some(0);
+        }
+        matches!(pool.select(), Some(next) if Some(next) != current)
+    }
+
+    /// Lấy bộ đếm/cooldown của các key trùng nhau từ `other` (thường là config vừa đọc từ file),
+    /// giữ nguyên danh sách key của bản này.
+    pub fn merge_key_usage(&mut self, other: &Config) {
+        for (api, pool) in self.key_pools.iter_mut() {
+            let Some(other_pool) = other.key_pools.get(api) else { continue };
+            for key in pool.keys.iter_mut() {
+                if let Some(o) = other_pool.keys.iter().find(|o| o.key == key.key) {
+                    key.cooldown_until = o.cooldown_until;
+                    key.usage_day = o.usage_day;
+                    key.used_today = o.used_today;
+                    key.remaining = o.remaining;
+                }
+            }
+            if other_pool.active < pool.keys.len() { pool.active = other_pool.active; }
+        }
+    }
+
+    fn get_key_usage_path() -> PathBuf {
+        Self::get_config_dir().join("key_usage.json")
+    }
+
+    fn load_key_usage() -> BTreeMap<String, PoolUsage> {
+        fs::read_to_string(Self::get_key_usage_path()).ok().and_then(|c| serde_json::from_str(&c).ok()).unwrap_or_default()
+    }
+
+    /// Lấy bộ đếm / cooldown đã lưu cho các key đang có
+    fn apply_key_usage(&mut self, usage: &BTreeMap<String, PoolUsage>) {
+        for (api, pool) in self.key_pools.iter_mut() {
+            let Some(saved) = usage.get(api) else { continue };
+            for key in pool.keys.iter_mut() {
+                if let Some(u) = saved.keys.get(&key_id(&key.key)) {
+                    key.cooldown_until = u.cooldown_until;
+                    key.usage_day = u.usage_day;
+                    key.used_today = u.used_today;
+                    key.remaining = u.remaining;
+                }
+            }
+            if saved.active < pool.keys.len() { pool.active = saved.active; }
+        }
+    }
+
+    /// Lưu bộ đếm key của `api` vào key_usage.json (không đụng tới file config mà UI đang sửa)
+    pub fn persist_key_usage(&self, api: &str) {
+        let Some(pool) = self.key_pool(api) else { return };
+        let _guard = KEY_USAGE_LOCK.lock().unwrap();
+        let mut usage = Self::load_key_usage();
+        let keys = pool.keys.iter().map(|k| (key_id(&k.key), KeyUsage {
+            cooldown_until: k.cooldown_until,
+            usage_day: k.usage_day,
+            used_today: k.used_today,
+            remaining: k.remaining,
+        })).collect();
+        usage.insert(api.to_string(), PoolUsage { active: pool.active, keys });
+        let path = Self::get_key_usage_path();
+        if let Some(parent) = path.parent() { let _ = fs::create_dir_all(parent); }
+        if let Ok(content) = serde_json::to_string_pretty(&usage) { let _ = fs::write(path, content); }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_usage_lives_outside_config_file() {
+        let mut config = Config::default();
+        config.key_pool_mut("groq").keys = vec![ApiKey::new("gsk-a".to_string()), ApiKey::new("gsk-b".to_string())];
+        config.record_key_usage("groq", "gsk-a", Some(41));
+        config.rotate_key("groq", "gsk-b");
+
+        // File config (UI ghi) không mang bộ đếm, đọc lại thì bộ đếm trống
+        let saved: Config = serde_json::from_str(&serde_json::to_string(&config).unwrap()).unwrap();
+        assert!(saved.key_pools["groq"].keys.iter().all(|k| k.used_today == 0 && k.remaining.is_none() && k.cooldown_until == 0));
+
+        // Bộ đếm lấy từ key_usage.json theo hash của key, key đã xoá thì bỏ qua
+        let pool = &config.key_pools["groq"];
+        let keys = pool.keys.iter().map(|k| (key_id(&k.key), KeyUsage { cooldown_until: k.cooldown_until, usage_day: k.usage_day, used_today: k.used_today, remaining: k.remaining })).collect();
+        let usage = BTreeMap::from([("groq".to_string(), PoolUsage { active: pool.active, keys })]);
+        let mut loaded = saved.clone();
+        loaded.key_pools.get_mut("groq").unwrap().keys.remove(1);
+        loaded.apply_key_usage(&usage);
+        let key = &loaded.key_pools["groq"].keys[0];
+        assert_eq!((key.used_today(), key.remaining), (1, Some(41)));
+        assert!(!key.is_cooling_down());
+        assert!(!serde_json::to_string(&usage).unwrap().contains("gsk-"));
+    }
+}
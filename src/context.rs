@@ -0,0 +1,63 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use crate::config::Region;
+
+/// Một câu thoại đã dịch, dùng làm ngữ cảnh cho câu tiếp theo
+#[derive(Clone, Debug)]
+pub struct DialogueLine {
+    pub original: String,
+    pub translated: String,
+    pub speaker: Option<String>,
+}
+
+// Ngữ cảnh riêng cho từng vùng dịch (key = toạ độ vùng), chỉ giữ trong RAM
+static CONTEXT: OnceLock<Mutex<HashMap<String, VecDeque<DialogueLine>>>> = OnceLock::new();
+
+fn buffers() -> &'static Mutex<HashMap<String, VecDeque<DialogueLine>>> {
+    CONTEXT.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn region_key(region: &Region) -> String {
+    format!("{},{},{},{}", region.x, region.y, region.width, region.height)
+}
+
+/// N câu gần nhất của vùng, cũ trước mới sau
+pub fn history(region: &Region, window: usize) -> Vec<DialogueLine> {
+    if window == 0 { return Vec::new(); }
+    let buffers = buffers().lock().unwrap();
+    match buffers.get(&region_key(region)) {
+        Some(lines) => lines.iter().skip(lines.len().saturating_sub(window)).cloned().collect(),
+        None => Vec::new(),
+    }
+}
+
+pub fn push(region: &Region, original: &str, translated: &str, speaker: Option<&str>, window: usize) {
+    if window == 0 || translated.trim().is_empty() { return; }
+    let mut buffers = buffers().lock().unwrap();
+    let lines = buffers.entry(region_key(region)).or_default();
+    // Auto mode hay chụp lại đúng câu vừa dịch, không thêm trùng
+    if lines.back().map_or(false, |last| last.translated == translated) { return; }
+    lines.push_back(DialogueLine { original: original.to_string(), translated: translated.to_string(), speaker: speaker.map(|s| s.to_string()) });
+    while lines.len() > window { lines.pop_front(); }
+}
+
+/// Dạng text của các câu thoại để đưa vào prompt
+pub fn format_lines(lines: &[DialogueLine]) -> String {
+    lines.iter().map(|l| {
+        let translated = match &l.speaker {
+            Some(speaker) => format!("{}: {}", speaker, l.translated),
+            None => l.translated.clone(),
+        };
+        if l.original.is_empty() { format!("- {}", translated) } else { format!("- {} => {}", l.original, translated) }
+    }).collect::<Vec<_>>().join("\n")
+}
+
+/// Người nói gần nhất trong các câu thoại (dùng cho biến {speaker})
+pub fn last_speaker(lines: &[DialogueLine]) -> Option<&str> {
+    lines.iter().rev().find_map(|l| l.speaker.as_deref())
+}
+
+/// Xoá ngữ cảnh của mọi vùng (khi đổi cảnh / đổi nhân vật)
+pub fn clear() {
+    buffers().lock().unwrap().clear();
+}
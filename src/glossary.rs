@@ -0,0 +1,183 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::config::Config;
+use crate::translation::TranslationResult;
+
+// Khi không có text gốc trước (dịch thẳng từ ảnh) thì gửi tối đa từng này mục của game
+const MAX_INJECTED: usize = 40;
+
+// Các vi phạm không tự sửa được của lần dịch gần nhất (hiện trong cửa sổ Thuật ngữ)
+pub static LAST_VIOLATIONS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct GlossaryEntry {
+    pub source: String,
+    pub target: String,
+    #[serde(default)]
+    pub notes: String,
+    // Tên game áp dụng (rỗng = mọi game)
+    #[serde(default)]
+    pub game: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Glossary {
+    pub entries: Vec<GlossaryEntry>,
+}
+
+impl GlossaryEntry {
+    fn applies_to(&self, game: &str) -> bool {
+        !self.source.trim().is_empty() && !self.target.trim().is_empty()
+            && (self.game.trim().is_empty() || self.game.trim().eq_ignore_ascii_case(game.trim()))
+    }
+}
+
+/// Tách 1 dòng CSV (hỗ trợ "..." và "" bên trong) hoặc TSV
+fn split_row(line: &str, delimiter: char) -> Vec<String> {
+    if delimiter == '\t' {
+        return line.split('\t').map(|s| s.trim().to_string()).collect();
+    }
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => { field.push('"'); chars.next(); }
+            '"' => in_quotes = !in_quotes,
+            c if c == delimiter && !in_quotes => fields.push(std::mem::take(&mut field).trim().to_string()),
+            c => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_string());
+    fields
+}
+
+fn escape_field(value: &str, delimiter: char) -> String {
+    if delimiter == '\t' {
+        return value.replace(['\t', '\n'], " ");
+    }
+    if value.contains([',', '"', '\n']) { format!("\"{}\"", value.replace('"', "\"\"")) } else { value.to_string() }
+}
+
+fn delimiter_for(path: &Path) -> char {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("csv") => ',',
+        _ => '\t',
+    }
+}
+
+impl Glossary {
+    pub fn get_path() -> PathBuf {
+        Self::path_in(&Config::get_config_dir())
+    }
+
+    fn path_in(dir: &Path) -> PathBuf {
+        dir.join("glossary.json")
+    }
+
+    pub fn load() -> Self {
+        Self::load_from(&Config::get_config_dir())
+    }
+
+    /// Đọc glossary.json trong thư mục cấu hình `dir`
+    pub fn load_from(dir: &Path) -> Self {
+        fs::read_to_string(Self::path_in(dir)).ok().and_then(|c| serde_json::from_str(&c).ok()).unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.save_to(&Config::get_config_dir())
+    }
+
+    pub fn save_to(&self, dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let path = Self::path_in(dir);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Nhập từ CSV/TSV (cột: gốc, dịch, ghi chú, game). Mục trùng (gốc + game) sẽ được ghi đè.
+    pub fn import(&mut self, path: &Path) -> Result<usize, anyhow::Error> {
+        let content = fs::read_to_string(path)?;
+        let delimiter = delimiter_for(path);
+        let mut count = 0;
+        for (i, line) in content.lines().enumerate() {
+            if line.trim().is_empty() { continue; }
+            let mut fields = split_row(line.trim_start_matches('\u{feff}'), delimiter).into_iter();
+            let entry = GlossaryEntry {
+                source: fields.next().unwrap_or_default(),
+                target: fields.next().unwrap_or_default(),
+                notes: fields.next().unwrap_or_default(),
+                game: fields.next().unwrap_or_default(),
+            };
+            // Bỏ dòng tiêu đề
+            if i == 0 && entry.source.eq_ignore_ascii_case("source") { continue; }
+            if entry.source.is_empty() || entry.target.is_empty() { continue; }
+            match self.entries.iter_mut().find(|e| e.source == entry.source && e.game.eq_ignore_ascii_case(&entry.game)) {
+                Some(existing) => *existing = entry,
+                None => self.entries.push(entry),
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    pub fn export(&self, path: &Path) -> Result<(), anyhow::Error> {
+        let delimiter = delimiter_for(path);
+        let sep = delimiter.to_string();
+        let mut out = ["source", "target", "notes", "game"].join(&sep);
+        out.push('\n');
+        for e in &self.entries {
+            let row = [&e.source, &e.target, &e.notes, &e.game].iter().map(|v| escape_field(v, delimiter)).collect::<Vec<_>>().join(&sep);
+            out.push_str(&row);
+            out.push('\n');
+        }
+        fs::write(path, out)?;
+        Ok(())
+    }
+
+    /// Các mục cần gửi kèm prompt: nếu đã có text gốc (OCR) thì chỉ lấy mục xuất hiện trong text
+    pub fn matching(&self, game: &str, source_text: Option<&str>) -> Vec<&GlossaryEntry> {
+        let active = self.entries.iter().filter(|e| e.applies_to(game));
+        match source_text {
+            Some(text) => active.filter(|e| text.contains(e.source.trim())).collect(),
+            None => active.take(MAX_INJECTED).collect(),
+        }
+    }
+
+    pub fn prompt_block(entries: &[&GlossaryEntry]) -> Option<String> {
+        if entries.is_empty() { return None; }
+        let lines: Vec<String> = entries.iter().map(|e| {
+            if e.notes.trim().is_empty() { format!("- {} => {}", e.source.trim(), e.target.trim()) }
+            else { format!("- {} => {} ({})", e.source.trim(), e.target.trim(), e.notes.trim()) }
+        }).collect();
+        Some(format!("GLOSSARY: whenever one of these terms appears, you MUST translate it exactly as given:\n{}", lines.join("\n")))
+    }
+
+    /// Kiểm tra bản dịch: mục có trong text gốc nhưng bản dịch không dùng đúng thuật ngữ.
+    /// Nếu `auto_fix` thì thay các chỗ model để nguyên từ gốc bằng bản dịch chuẩn.
+    /// Trả về các vi phạm không sửa được.
+    pub fn enforce(&self, game: &str, result: &mut TranslationResult, auto_fix: bool) -> Vec<String> {
+        let Some(original) = result.original_text.clone() else { return Vec::new() };
+        let mut violations = Vec::new();
+        for e in self.entries.iter().filter(|e| e.applies_to(game)) {
+            let (source, target) = (e.source.trim(), e.target.trim());
+            if auto_fix {
+                if let Some(speaker) = result.speaker.as_mut().filter(|s| s.contains(source)) {
+                    *speaker = speaker.replace(source, target);
+                }
+            }
+            if !original.contains(source) || result.translated_text.contains(target) { continue; }
+            if auto_fix && result.translated_text.contains(source) {
+                result.translated_text = result.translated_text.replace(source, target);
+                continue;
+            }
+            violations.push(format!("{} => {}", source, target));
+        }
+        violations
+    }
+}
@@ -0,0 +1,414 @@
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use reqwest::header::HeaderMap;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::Duration;
+use crate::config::Config;
+
+const CONNECT_TIMEOUT_SECS: u64 = 10;
+const BASE_DELAY_MS: u64 = 500;
+// Chờ lâu hơn mức này thì trả 429 về luôn để bên gọi xoay key thay vì treo
+const MAX_DELAY_MS: u64 = 8000;
+
+static CLIENT: OnceLock<Client> = OnceLock::new();
+static TIMEOUT_SECS: AtomicU64 = AtomicU64::new(60);
+static MAX_RETRIES: AtomicU32 = AtomicU32::new(2);
+
+/// Client dùng chung cho mọi provider (giữ connection pool, không tạo mới mỗi lần dịch)
+pub fn client() -> &'static Client {
+    CLIENT.get_or_init(|| {
+        Client::builder()
+            .connect_timeout(Duration::from_secs(CONNECT_TIMEOUT_SECS))
+            .build()
+            .unwrap_or_else(|_| Client::new())
+    })
+}
+
+/// Cập nhật timeout / số lần thử lại theo config hiện tại
+pub fn configure(config: &Config) {
+    TIMEOUT_SECS.store(config.http_timeout_secs.max(1), Ordering::Relaxed);
+    MAX_RETRIES.store(config.http_max_retries, Ordering::Relaxed);
+}
+
+/// Đọc thời gian server yêu cầu chờ: `Retry-After` (giây) hoặc `x-ratelimit-reset-*` (dạng "1m2.5s", "150ms")
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    if let Some(secs) = headers.get("retry-after").and_then(|h| h.to_str().ok()).and_then(|s| s.trim().parse::<f64>().ok()) {
+        return Some(Duration::from_secs_f64(secs.max(0.0)));
+    }
+    ["x-ratelimit-reset-requests", "x-ratelimit-reset-tokens", "x-ratelimit-reset"].iter()
+        .filter_map(|name| headers.get(*name).and_then(|h| h.to_str().ok()).and_then(parse_reset))
+        .max()
+}
+
+fn parse_reset(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<f64>() {
+        return Some(Duration::from_secs_f64(secs.max(0.0)));
+    }
+    let mut total = 0.0;
+    let mut number = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() || c == '.' {
+            number.push(c);
+            continue;
+        }
+        let amount: f64 = number.parse().ok()?;
+        number.clear();
+        total += match c {
+            'h' => amount * 3600.0,
+            'm' if chars.peek() == Some(&'s') => { chars.next(); amount / 1000.0 },
+            'm' => amount * 60.0,
+            's' => amount,
+            _ => return None,
+        };
+    }
+    if !number.is_empty() { return None; }
+    Some(Duration::from_secs_f64(total))
+}
+
+fn backoff(attempt: u32) -> Duration {
+    let exp = BASE_DELAY_MS.saturating_mul(1 << attempt.min(10));
+    let jitter = rand::random::<u64>() % BASE_DELAY_MS;
+    Duration::from_millis(exp.min(MAX_DELAY_MS) + jitter)
+}
+
+// Chỉ lỗi mạng tạm thời; URL / header sai (base URL, header tự nhập) thì báo luôn
+fn is_retryable_error(e: &reqwest::Error) -> bool {
+    e.is_connect() || e.is_timeout()
+}
+
+/// Gửi request với timeout, thử lại khi lỗi mạng / 5xx (exponential backoff),
+/// và khi 429 mà server hẹn thời gian chờ ngắn. Các lỗi còn lại trả nguyên response cho bên gọi.
+pub async fn send(builder: RequestBuilder) -> Result<Response, reqwest::Error> {
+    send_with(builder, tokio::time::sleep).await
+}
+
+/// Như `send` nhưng chờ giữa các lần thử bằng `sleep` (test ghi lại thời gian chờ thay vì chờ thật)
+async fn send_with<F: Future<Output = ()>>(builder: RequestBuilder, sleep: impl Fn(Duration) -> F) -> Result<Response, reqwest::Error> {
+    let max_retries = MAX_RETRIES.load(Ordering::Relaxed);
+    let timeout = Duration::from_secs(TIMEOUT_SECS.load(Ordering::Relaxed));
+    let mut attempt = 0;
+
+    loop {
+        // Body JSON luôn clone được; nếu không clone được thì chỉ gửi 1 lần
+        let request = match builder.try_clone() {
+            Some(b) => b,
+            None => return builder.timeout(timeout).send().await,
+        };
+        let can_retry = attempt < max_retries;
+
+        match request.timeout(timeout).send().await {
+            Ok(response) => {
+                let status = response.status();
+                if !can_retry { return Ok(response); }
+                let wait = if status == StatusCode::TOO_MANY_REQUESTS {
+                    match retry_after(response.headers()) {
+                        Some(d) if d <= Duration::from_millis(MAX_DELAY_MS) => d,
+                        _ => return Ok(response),
+                    }
+                } else if status.is_server_error() {
+                    retry_after(response.headers()).filter(|d| *d <= Duration::from_millis(MAX_DELAY_MS)).unwrap_or_else(|| backoff(attempt))
+                } else {
+                    return Ok(response);
+                };
+                sleep(wait).await;
+            }
+            Err(e) if can_retry && is_retryable_error(&e) => {
+                sleep(backoff(attempt)).await;
+            }
+            Err(e) => return Err(e),
+        }
+        attempt += 1;
+    }
+}
+
+/// Tách luồng Server-Sent Events thành payload của từng event (dữ liệu về theo từng mảnh tuỳ ý,
+/// có thể cắt ngang giữa dòng hoặc giữa ký tự UTF-8). Nhiều dòng `data:` của 1 event nối bằng "\n",
+/// event kết thúc ở dòng trống.
+#[derive(Default)]
+pub struct SseParser {
+    buffer: Vec<u8>,
+    // Các dòng `data:` của event đang đọc dở
+    data: Vec<String>,
+}
+
+impl SseParser {
+    /// Nạp thêm 1 mảnh, trả về payload của các event đã trọn vẹn
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            events.extend(self.line(line.trim_end_matches(['\r', '\n'])));
+        }
+        events
+    }
+
+    /// Dòng / event cuối không có xuống dòng khi server đóng kết nối
+    pub fn finish(&mut self) -> Option<String> {
+        let line = String::from_utf8_lossy(&std::mem::take(&mut self.buffer)).into_owned();
+        let line = line.trim_end();
+        if !line.is_empty() {
+            if let Some(event) = self.line(line) { return Some(event); }
+        }
+        self.line("")
+    }
+
+    /// 1 dòng trọn vẹn; dòng trống kết thúc event. Các trường khác (`event:`, `id:`, chú thích `:`) bỏ qua.
+    fn line(&mut self, line: &str) -> Option<String> {
+        if line.is_empty() {
+            if self.data.is_empty() { return None; }
+            return Some(std::mem::take(&mut self.data).join("\n"));
+        }
+        if let Some(data) = line.strip_prefix("data:") {
+            self.data.push(data.strip_prefix(' ').unwrap_or(data).to_string());
+        }
+        None
+    }
+}
+
+/// Đọc body SSE đến hết, gọi `on_data` với mỗi payload (bỏ qua `[DONE]` của OpenAI)
+pub async fn read_sse(mut response: Response, mut on_data: impl FnMut(&str)) -> Result<(), reqwest::Error> {
+    let mut parser = SseParser::default();
+    while let Some(chunk) = response.chunk().await? {
+        for data in parser.push(&chunk) {
+            if data != "[DONE]" { on_data(&data); }
+        }
+    }
+    if let Some(data) = parser.finish().filter(|d| d != "[DONE]") { on_data(&data); }
+    Ok(())
+}
+
+/// Server HTTP giả cho test: nghe trên cổng ngẫu nhiên của 127.0.0.1, mỗi kết nối trả 1 response soạn sẵn
+/// (theo thứ tự) rồi đóng, ghi lại request và thời điểm nhận.
+#[cfg(test)]
+pub(crate) mod mock {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// Response gửi thành từng mảnh (cách nhau 1 chút) để thử đọc stream
+    pub type Response = Vec<Vec<u8>>;
+
+    pub struct Request {
+        // Dòng đầu + header
+        pub head: String,
+        pub body: String,
+    }
+
+    impl Request {
+        /// Giá trị header (không phân biệt hoa thường)
+        pub fn header(&self, name: &str) -> Option<&str> {
+            self.head.lines().find_map(|l| {
+                let (key, value) = l.split_once(':')?;
+                if key.trim().eq_ignore_ascii_case(name) { Some(value.trim()) } else { None }
+            })
+        }
+    }
+
+    pub struct MockServer {
+        pub url: String,
+        requests: Arc<Mutex<Vec<Request>>>,
+    }
+
+    /// Response thường với body trọn vẹn
+    pub fn response(status: &str, headers: &[(&str, &str)], body: &str) -> Response {
+        let mut head = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n", status, body.len());
+        for (name, value) in headers { head.push_str(&format!("{}: {}\r\n", name, value)); }
+        vec![format!("{}\r\n{}", head, body).into_bytes()]
+    }
+
+    /// Response SSE không có Content-Length, body là các mảnh `parts` gửi lần lượt
+    pub fn sse(parts: &[&[u8]]) -> Response {
+        let mut out = vec![b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n".to_vec()];
+        out.extend(parts.iter().map(|p| p.to_vec()));
+        out
+    }
+
+    fn read_request(stream: &mut std::net::TcpStream) -> Option<Request> {
+        let mut data = Vec::new();
+        let mut buf = [0u8; 4096];
+        let head_end = loop {
+            let n = stream.read(&mut buf).ok()?;
+            if n == 0 { return None; }
+            data.extend_from_slice(&buf[..n]);
+            if let Some(pos) = data.windows(4).position(|w| w == b"\r\n\r\n") { break pos + 4; }
+        };
+        let mut request = Request { head: String::from_utf8_lossy(&data[..head_end]).into_owned(), body: String::new() };
+        let length = request.header("content-length").and_then(|v| v.parse::<usize>().ok()).unwrap_or(0);
+        while data.len() < head_end + length {
+            let n = stream.read(&mut buf).ok()?;
+            if n == 0 { break; }
+            data.extend_from_slice(&buf[..n]);
+        }
+        request.body = String::from_utf8_lossy(&data[head_end..]).into_owned();
+        Some(request)
+    }
+
+    impl MockServer {
+        pub fn start(responses: Vec<Response>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let log = requests.clone();
+            std::thread::spawn(move || {
+                for response in responses {
+                    let Ok((mut stream, _)) = listener.accept() else { return };
+                    let Some(request) = read_request(&mut stream) else { continue };
+                    log.lock().unwrap().push(request);
+                    for (i, part) in response.iter().enumerate() {
+                        if i > 0 { std::thread::sleep(Duration::from_millis(20)); }
+                        if stream.write_all(part).and_then(|_| stream.flush()).is_err() { break; }
+                    }
+                }
+            });
+            Self { url, requests }
+        }
+
+        pub fn requests(&self) -> std::sync::MutexGuard<'_, Vec<Request>> {
+            self.requests.lock().unwrap()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::mock::{self, MockServer};
+    use reqwest::header::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs { map.insert(*name, HeaderValue::from_str(value).unwrap()); }
+        map
+    }
+
+    fn parse_all(chunks: &[&[u8]]) -> Vec<String> {
+        let mut parser = SseParser::default();
+        let mut events: Vec<String> = chunks.iter().flat_map(|c| parser.push(c)).collect();
+        events.extend(parser.finish());
+        events
+    }
+
+    #[test]
+    fn sse_events_split_mid_line() {
+        let events = parse_all(&[b"data: {\"a\"", b":1}\n", b"\nda", b"ta: {\"b\":2}\r\n\r\n"]);
+        assert_eq!(events, vec![r#"{"a":1}"#, r#"{"b":2}"#]);
+    }
+
+    #[test]
+    fn sse_events_split_mid_utf8() {
+        let body = "data: Xin chào thế giới\n\n".as_bytes();
+        // Cắt ở mọi vị trí, kể cả giữa các byte của "à" / "ế"
+        for cut in 0..body.len() {
+            assert_eq!(parse_all(&[&body[..cut], &body[cut..]]), vec!["Xin chào thế giới"], "cut at {}", cut);
+        }
+        let bytes: Vec<&[u8]> = body.chunks(1).collect();
+        assert_eq!(parse_all(&bytes), vec!["Xin chào thế giới"]);
+    }
+
+    #[test]
+    fn sse_multiline_data_and_other_fields() {
+        let events = parse_all(&[b": keep-alive\nevent: message\nid: 1\ndata: line 1\ndata:line 2\n\ndata: last"]);
+        assert_eq!(events, vec!["line 1\nline 2", "last"]);
+        assert!(parse_all(&[b"\n\n: comment\n\n"]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn read_sse_skips_done() {
+        let server = MockServer::start(vec![mock::sse(&[b"data: one\n\n", b"data: tw", b"o\n\ndata: [DONE]\n\n"])]);
+        let response = client().get(&server.url).send().await.unwrap();
+        let mut events = Vec::new();
+        read_sse(response, |data| events.push(data.to_string())).await.unwrap();
+        assert_eq!(events, vec!["one", "two"]);
+    }
+
+    #[test]
+    fn parse_reset_formats() {
+        assert_eq!(parse_reset("1m2.5s"), Some(Duration::from_millis(62_500)));
+        assert_eq!(parse_reset("150ms"), Some(Duration::from_millis(150)));
+        assert_eq!(parse_reset("2h"), Some(Duration::from_secs(7200)));
+        assert_eq!(parse_reset("6m0s"), Some(Duration::from_secs(360)));
+        assert_eq!(parse_reset(" 0.5 "), Some(Duration::from_millis(500)));
+        assert_eq!(parse_reset("abc"), None);
+        assert_eq!(parse_reset("10x"), None);
+        assert_eq!(parse_reset("1m2"), None);
+    }
+
+    #[test]
+    fn retry_after_prefers_header_then_longest_reset() {
+        assert_eq!(retry_after(&headers(&[("retry-after", "2"), ("x-ratelimit-reset-requests", "1m")])), Some(Duration::from_secs(2)));
+        let reset = headers(&[("x-ratelimit-reset-requests", "1m2.5s"), ("x-ratelimit-reset-tokens", "150ms")]);
+        assert_eq!(retry_after(&reset), Some(Duration::from_millis(62_500)));
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn backoff_grows_and_is_capped() {
+        let first = backoff(0);
+        assert!(first >= Duration::from_millis(BASE_DELAY_MS) && first < Duration::from_millis(2 * BASE_DELAY_MS));
+        assert!(backoff(1) >= Duration::from_millis(2 * BASE_DELAY_MS));
+        assert!(backoff(30) < Duration::from_millis(MAX_DELAY_MS + BASE_DELAY_MS));
+    }
+
+    /// Gửi qua `send_with`, trả về kết quả và các lần chờ (không chờ thật)
+    async fn send_recorded(builder: RequestBuilder) -> (Result<Response, reqwest::Error>, Vec<Duration>) {
+        let waits = std::sync::Mutex::new(Vec::new());
+        let result = send_with(builder, |d| {
+            waits.lock().unwrap().push(d);
+            std::future::ready(())
+        }).await;
+        (result, waits.into_inner().unwrap())
+    }
+
+    #[tokio::test]
+    async fn send_retries_429_then_503_then_succeeds() {
+        let server = MockServer::start(vec![
+            mock::response("429 Too Many Requests", &[("Retry-After", "1")], ""),
+            mock::response("503 Service Unavailable", &[], ""),
+            mock::response("200 OK", &[], "ok"),
+        ]);
+        let (response, waits) = send_recorded(client().get(&server.url)).await;
+        let response = response.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.text().await.unwrap(), "ok");
+        assert_eq!(server.requests().len(), 3);
+        // Chờ đúng Retry-After sau 429, rồi backoff lần 2 (1s + jitter) sau 503
+        assert_eq!(waits.len(), 2);
+        assert_eq!(waits[0], Duration::from_secs(1));
+        assert!(waits[1] >= Duration::from_millis(2 * BASE_DELAY_MS) && waits[1] < Duration::from_millis(3 * BASE_DELAY_MS), "{:?}", waits[1]);
+    }
+
+    #[tokio::test]
+    async fn send_returns_429_when_retry_after_exceeds_cap() {
+        let server = MockServer::start(vec![
+            mock::response("429 Too Many Requests", &[("Retry-After", "30")], ""),
+            mock::response("200 OK", &[], "ok"),
+        ]);
+        let (response, waits) = send_recorded(client().get(&server.url)).await;
+        assert_eq!(response.unwrap().status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(server.requests().len(), 1);
+        assert!(waits.is_empty());
+    }
+
+    #[tokio::test]
+    async fn send_retries_connect_errors_only() {
+        // Cổng vừa đóng: không ai nghe -> lỗi kết nối, thử lại đủ số lần
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let (result, waits) = send_recorded(client().get(format!("http://127.0.0.1:{}/", port))).await;
+        assert!(result.unwrap_err().is_connect());
+        assert_eq!(waits.len(), MAX_RETRIES.load(Ordering::Relaxed) as usize);
+
+        // Header tự nhập sai: báo lỗi ngay, không thử lại
+        let (result, waits) = send_recorded(client().get(format!("http://127.0.0.1:{}/", port)).header("X-Title", "a\nb")).await;
+        assert!(result.unwrap_err().is_builder());
+        assert!(waits.is_empty());
+        let (result, waits) = send_recorded(client().get("http://[bad url")).await;
+        assert!(result.is_err());
+        assert!(waits.is_empty());
+    }
+}
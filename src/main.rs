@@ -2,7 +2,19 @@
 
 mod config;
 mod capture;
+mod window;
+mod matcher;
+mod preprocess;
 mod translation;
+mod cache;
+mod http;
+mod ocr;
+mod context;
+mod glossary;
+mod trigger;
+mod templates;
+mod profiles;
+mod prompt;
 mod tts;
 mod overlay;
 mod key_utils;
@@ -11,7 +23,7 @@ mod ui;
 use crate::overlay::{show_result_window, show_result_window_internal};
 use crate::ui::UiRenderer; 
 use eframe::egui;
-use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::atomic::AtomicU64;
 use std::sync::mpsc::{self, Receiver, Sender};
 use winapi::shared::windef::{RECT, HWND};
@@ -21,10 +33,10 @@ use winapi::um::libloaderapi::GetModuleHandleW;
 use std::ffi::OsStr;
 use std::os::windows::ffi::OsStrExt;
 use image::{self, GenericImageView};
-use std::fs;
 use webbrowser;
 use arboard::Clipboard;
 use std::sync::Arc;
+use futures::StreamExt;
 use std::time::Duration;
 use rand;
 
@@ -43,7 +55,6 @@ const VK_MIN: i32 = 8;
 const VK_MAX: i32 = 255;
 const KEY_STATE_MASK: u16 = 0x8000;
 const TIMER_INTERVAL_MS: u32 = 500;
-const MISS_COUNTER_THRESHOLD: i32 = 5;
 const SUCCESS_DISPLAY_DURATION_SECS: f32 = 1.0;
 const CONFIG_SYNC_INTERVAL_SECS: u64 = 1;
 const TTS_SPEED_MIN: f32 = 0.5;
@@ -55,42 +66,10 @@ const FONT_SIZE_MIN: u32 = 10;
 const FONT_SIZE_MAX: u32 = 72;
 const PIXELS_PER_POINT: f32 = 1.2;
 
-// WWM Region Ratios (16:9)
-/* WWM Region Ratios (16:9) - Updated based on user coordinates
-Region 1: Normal Text (423, 925) -> (1496, 1037)
-W = 1496 - 423 = 1073
-H = 1037 - 925 = 112 */
-const WWM_TEXT_REGION_X_RATIO: f32 = 423.0 / 1920.0;
-const WWM_TEXT_REGION_Y_RATIO: f32 = 925.0 / 1080.0;
-const WWM_TEXT_REGION_W_RATIO: f32 = 1073.0 / 1920.0;
-const WWM_TEXT_REGION_H_RATIO: f32 = 112.0 / 1080.0;
-
-/* Region 2: With Name (423, 868) -> (1496, 1037)
-W = 1496 - 423 = 1073
-H = 1037 - 868 = 169 */
-const WWM_NAME_REGION_X_RATIO: f32 = 423.0 / 1920.0;
-const WWM_NAME_REGION_Y_RATIO: f32 = 868.0 / 1080.0;
-const WWM_NAME_REGION_W_RATIO: f32 = 1073.0 / 1920.0;
-const WWM_NAME_REGION_H_RATIO: f32 = 169.0 / 1080.0;
-
-/* Region 3: Arrow (937, 1049) -> (979, 1079)
-W = 979 - 937 = 42
-H = 1079 - 1049 = 30 */
-const WWM_ARROW_REGION_X_RATIO: f32 = 937.0 / 1920.0;
-const WWM_ARROW_REGION_Y_RATIO: f32 = 1049.0 / 1080.0;
-const WWM_ARROW_REGION_W_RATIO: f32 = 42.0 / 1920.0;
-const WWM_ARROW_REGION_H_RATIO: f32 = 30.0 / 1080.0;
-
-// Padding (Giữ nguyên hoặc chỉnh về 0 nếu muốn chính xác tuyệt đối theo tọa độ bạn đưa)
-const WWM_REGION_PADDING: i32 = 0;
-const WWM_REGION_EXTRA_WIDTH: u32 = 0;
-const WWM_REGION_EXTRA_HEIGHT: u32 = 0;
-
 static LAST_SELECT: AtomicU64 = AtomicU64::new(0);
 static OVERLAY_ACTIVE: AtomicBool = AtomicBool::new(false);
 static LISTENING_PAUSED: AtomicBool = AtomicBool::new(false);
 static AUTO_TRANSLATE_ENABLED: AtomicBool = AtomicBool::new(false);
-static GROQ_REMAINING: AtomicI32 = AtomicI32::new(-1);
 static HOTKEYS_NEED_UPDATE: AtomicBool = AtomicBool::new(false);
 static IS_BINDING_MODE: AtomicBool = AtomicBool::new(false);
 
@@ -118,6 +97,7 @@ pub enum BindingTarget {
     Instant,
     Auto,
     ToggleAuto,
+    ClearContext,
     AuxSelect(usize),
     AuxTranslate(usize),
 }
@@ -137,12 +117,11 @@ pub struct MainApp {
     pub ui_state: ui::UiState,
     pub config_state: ui::ConfigState,
     pub hotkey_state: ui::HotkeyState,
-    pub wwm_state: ui::WwmState,
+    pub game_state: ui::GameState,
 
     pub binding_target: Option<BindingTarget>,
     pub is_paused: bool,
     pub listener_spawned: bool,
-    pub arrow_texture: Option<egui::TextureHandle>,
     pub last_config_sync: std::time::Instant,
 
     pub reader_rx: Receiver<ReaderSignal>,
@@ -157,14 +136,174 @@ pub struct MainApp {
     rx_signal: Receiver<AppSignal>,
 }
 
+/// Kết quả của 1 vùng: text hiển thị/đọc và text gốc (để copy)
+#[derive(Default)]
+struct RegionOutput {
+    show: String,
+    copy: String,
+}
+
+/// Câu đầu tiên đã trọn vẹn trong bản dịch tạm: có dấu kết câu và đã có chữ phía sau
+fn first_sentence(text: &str) -> Option<&str> {
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let Some(&(_, next)) = chars.peek() else { break };
+        let ends = match c {
+            '.' | '!' | '?' | '…' => next.is_whitespace(),
+            '。' | '！' | '？' => true,
+            _ => false,
+        };
+        if ends { return Some(text[..i + c.len_utf8()].trim()); }
+    }
+    None
+}
+
+/// Những thứ mọi vùng trong 1 lần dịch dùng chung
+struct RegionJob<'a> {
+    config: &'a config::Config,
+    keys: std::sync::Mutex<config::Config>,
+    api: &'a str,
+    needs_key: bool,
+    ocr_engine: Option<&'a dyn ocr::OcrEngine>,
+    glossary: &'a glossary::Glossary,
+    template: &'a str,
+}
+
+impl RegionJob<'_> {
+    /// Prompt mẫu đã thay biến; biến nào prompt không tự đặt chỗ thì nối vào cuối như mặc định
+    fn render_prompt(&self, glossary_block: &str, previous_lines: &str, speaker: &str) -> String {
+        let config = self.config;
+        let vars = prompt::PromptVars {
+            target_lang: config.target_lang_name(),
+            source_lang: config.source_lang_name(),
+            game: &config.active_game,
+            glossary: glossary_block,
+            previous_lines,
+            speaker,
+        };
+        let mut rendered = prompt::render(self.template, &vars);
+        if !config.source_language.trim().is_empty() && !prompt::uses(self.template, "{source_lang}") {
+            rendered = format!("{}\nThe source text is in {}.", rendered, vars.source_lang);
+        }
+        if !glossary_block.is_empty() && !prompt::uses(self.template, "{glossary}") {
+            rendered = format!("{}\n\n{}", rendered, glossary_block);
+        }
+        rendered
+    }
+
+    /// Kiểm tra thuật ngữ, lưu ngữ cảnh và tạo text hiển thị cho 1 kết quả
+    fn finish(&self, region: &config::Region, mut result: translation::TranslationResult) -> RegionOutput {
+        let config = self.config;
+        let violations = self.glossary.enforce(&config.active_game, &mut result, config.glossary_auto_fix);
+        // Lần dịch đúng thuật ngữ cũng ghi đè (danh sách rỗng) để cửa sổ Thuật ngữ không hiện lỗi cũ
+        *glossary::LAST_VIOLATIONS.lock().unwrap() = violations;
+        context::push(region, result.original_text.as_deref().unwrap_or(""), &result.translated_text, result.speaker.as_deref(), config.context_window);
+        RegionOutput { show: result.display_text(), copy: result.original_text.unwrap_or_default() }
+    }
+
+    /// Gửi mọi vùng trong 1 request. None nếu không dịch gộp được (provider từ chối, trả sai dạng...)
+    /// để bên gọi dịch lại từng vùng.
+    async fn translate_batch(&self, regions: &[config::Region], images: &[Vec<u8>]) -> Option<Vec<RegionOutput>> {
+        let config = self.config;
+        // Bỏ vùng chụp lỗi, nhớ vị trí để trả kết quả về đúng vùng
+        let indices: Vec<usize> = (0..regions.len()).filter(|&i| !images[i].is_empty()).collect();
+        if indices.len() < 2 { return None; }
+        let batch_images: Vec<&[u8]> = indices.iter().map(|&i| images[i].as_slice()).collect();
+        // Ngữ cảnh mỗi vùng đi kèm số thứ tự ảnh thay cho {previous_lines}
+        let histories: Vec<Vec<context::DialogueLine>> = indices.iter().map(|&i| context::history(&regions[i], config.context_window)).collect();
+        let glossary_block = glossary::Glossary::prompt_block(&self.glossary.matching(&config.active_game, None)).unwrap_or_default();
+        let batch_prompt = self.render_prompt(&glossary_block, "", "");
+
+        for _ in 0..config.key_count(self.api) {
+            let api_key = self.keys.lock().unwrap().get_current_key(self.api);
+            if self.needs_key && api_key.is_empty() { return None; }
+
+            match translation::translate_batch(config, &api_key, &batch_prompt, &batch_images, &histories).await {
+                Ok(results) => {
+                    let remaining = results.first().and_then(|r| r.remaining_requests);
+                    self.keys.lock().unwrap().record_key_usage(self.api, &api_key, remaining);
+                    let mut outputs: Vec<RegionOutput> = regions.iter().map(|_| RegionOutput::default()).collect();
+                    for (&i, result) in indices.iter().zip(results) {
+                        outputs[i] = self.finish(&regions[i], result);
+                    }
+                    return Some(outputs);
+                },
+                Err(translation::TranslationError::RateLimitExceeded) => {
+                    if !self.keys.lock().unwrap().rotate_key(self.api, &api_key) { return None; }
+                },
+                Err(_) => return None,
+            }
+        }
+        None
+    }
+
+    async fn translate(&self, region: &config::Region, image_bytes: &[u8], on_partial: Option<&translation::PartialFn<'_>>) -> RegionOutput {
+        let mut output = RegionOutput::default();
+        if image_bytes.is_empty() { return output; }
+        let config = self.config;
+        let history = context::history(region, config.context_window);
+
+        // Bước 1 (nếu bật OCR): lấy text gốc trên máy
+        let ocr_text = match self.ocr_engine {
+            Some(engine) => match engine.recognize(image_bytes) {
+                Ok(text) if text.trim().is_empty() => return output,
+                Ok(text) => Some(text),
+                Err(e) => {
+                    output.show = format!("Lỗi OCR: {}", e);
+                    return output;
+                }
+            },
+            None => None,
+        };
+
+        // Chỉ gửi kèm các thuật ngữ liên quan để prompt không phình to
+        let glossary_block = glossary::Glossary::prompt_block(&self.glossary.matching(&config.active_game, ocr_text.as_deref())).unwrap_or_default();
+        let region_prompt = self.render_prompt(&glossary_block, &context::format_lines(&history), context::last_speaker(&history).unwrap_or(""));
+        let history: &[context::DialogueLine] = if prompt::uses(self.template, "{previous_lines}") { &[] } else { &history };
+
+        let max_attempts = config.key_count(self.api);
+        for _ in 0..max_attempts {
+            let api_key = self.keys.lock().unwrap().get_current_key(self.api);
+
+            if self.needs_key && api_key.is_empty() {
+                output.show = "(Chưa nhập Key)".to_string();
+                return output;
+            }
+
+            let response = match &ocr_text {
+                Some(text) => translation::translate_text(config, self.api, &api_key, &region_prompt, text, history, on_partial).await,
+                None => translation::translate_from_image(config, &api_key, &region_prompt, image_bytes, history, on_partial).await,
+            };
+            match response {
+                Ok(result) => {
+                    if !result.from_cache {
+                        self.keys.lock().unwrap().record_key_usage(self.api, &api_key, result.remaining_requests);
+                    }
+                    return self.finish(region, result);
+                },
+                Err(translation::TranslationError::RateLimitExceeded) => {
+                    if !self.keys.lock().unwrap().rotate_key(self.api, &api_key) {
+                        output.show = "(Hết lượt Request & hết Key dự phòng)".to_string();
+                        return output;
+                    }
+                },
+                Err(e) => {
+                    output.show = format!("Lỗi: {}", e);
+                    return output;
+                }
+            }
+        }
+        output.show = "...".to_string();
+        output
+    }
+}
+
 impl MainApp {
     fn new(cc: &eframe::CreationContext<'_>, tray_icon: TrayIcon, rx: Receiver<AppSignal>) -> Self {
-        let mut config = config::Config::load();
-        if config.groq_api_keys.is_empty() {
-            config.groq_api_keys.push(String::new());
-        }
+        let config = config::Config::load();
 
         overlay::set_font_size(config.overlay_font_size);
+        tts::set_language(config.tts_language_code());
 
         std::thread::spawn(|| {
             std::thread::sleep(Duration::from_millis(THREAD_SLEEP_MS));
@@ -185,11 +324,10 @@ impl MainApp {
             ui_state: ui::UiState::new(),
             config_state: ui::ConfigState::new(config.clone()),
             hotkey_state: ui::HotkeyState::new(&config),
-            wwm_state: ui::WwmState::new(),
+            game_state: ui::GameState::new(),
             binding_target: None,
             is_paused: false,
             listener_spawned: false,
-            arrow_texture: None,
             last_config_sync: std::time::Instant::now(),
 
             reader_rx: r_rx,
@@ -248,93 +386,80 @@ impl MainApp {
     async fn translate_regions(
         mut config: config::Config,
         regions: Vec<config::Region>,
-        tx: Sender<(String, bool, f32, bool, u64)>,
+        tx: Sender<tts::Speech>,
         should_copy: bool,
     ) {
         // 1. Chuẩn bị Prompt
-        let mut final_prompt = config.current_prompt.clone();
-        
-        // Nếu bật "Copy bản gốc", ta ép buộc LLM trả về format dạng: <Original> ||||| <Translation>
-        // Điều này cho phép ta lấy được text gốc để copy và text dịch để đọc chỉ trong 1 request.
-        let use_split_mode = should_copy && config.copy_original;
-        
-        if use_split_mode {
-            final_prompt = format!("{}\n\nSPECIAL INSTRUCTION: You must output the result in two parts. Part 1 is the raw original text extracted from the image. Part 2 is the result of the prompt above. Separate them with the delimiter '|||||'. Do not add any other text. Format: [Original Text] ||||| [Result Text]", final_prompt);
-        }
+        // Tầng dịch luôn yêu cầu JSON có cấu trúc, nên text gốc (để copy) và bản dịch (để đọc)
+        // về cùng một request mà không cần ép model dùng dấu phân cách.
+        // Prompt trong thư viện có thể chỉ định provider/model riêng
+        config.apply_prompt_overrides();
+        let template = config.current_prompt.clone();
 
-        let mut final_text_to_show = String::new(); // Dùng để hiển thị/đọc
-        let mut final_text_to_copy = String::new(); // Dùng để copy (nếu split mode)
-        
-        overlay::set_font_size(config.overlay_font_size);
-
-        for region in &regions {
-            let image_bytes = capture::capture_image(region).unwrap_or_default();
-            if !image_bytes.is_empty() {
-                let mut attempts = 0;
-                let max_attempts = if config.selected_api == "groq" { config.groq_api_keys.len() } else { 1 };
-                let mut success = false;
-
-                while attempts < max_attempts {
-                    let api_key = if config.selected_api == "gemini" {
-                        config.gemini_api_key.clone()
-                    } else {
-                        config.get_current_groq_key()
-                    };
-
-                    if api_key.is_empty() {
-                        final_text_to_show.push_str("(Chưa nhập Key) ");
-                        break;
-                    }
-
-                    // Gọi API với prompt đã chỉnh sửa
-                    match translation::translate_from_image(&config.selected_api, &api_key, &final_prompt, &image_bytes).await {
-                        Ok(result) => {
-                            // Xử lý kết quả trả về
-                            if use_split_mode {
-                                let parts: Vec<&str> = result.text.split("|||||").collect();
-                                if parts.len() >= 2 {
-                                    final_text_to_copy.push_str(parts[0].trim());
-                                    final_text_to_show.push_str(parts[1].trim());
-                                } else {
-                                    // Fallback nếu LLM không tuân thủ format (hiếm khi xảy ra)
-                                    final_text_to_copy.push_str(&result.text);
-                                    final_text_to_show.push_str(&result.text);
-                                }
-                                final_text_to_copy.push(' ');
-                            } else {
-                                final_text_to_show.push_str(&result.text);
-                            }
+        // Pipeline OCR: nhận dạng chữ trên máy rồi chỉ gửi text đi dịch
+        let use_ocr = config.use_ocr_pipeline;
 
-                            if let Some(rem) = result.remaining_requests {
-                                GROQ_REMAINING.store(rem, Ordering::Relaxed);
-                            }
-                            success = true;
-                            break;
-                        },
-                        Err(translation::TranslationError::RateLimitExceeded) => {
-                            if config.selected_api == "groq" && config.groq_api_keys.len() > 1 {
-                                config.active_groq_index = (config.active_groq_index + 1) % config.groq_api_keys.len();
-                                attempts += 1;
-                                continue;
-                            } else {
-                                final_text_to_show.push_str("(Hết lượt Request & hết Key dự phòng) ");
-                                break;
-                            }
-                        },
-                        Err(translation::TranslationError::Other(e)) => {
-                            let error_msg = format!("Lỗi: {} ", e);
-                            final_text_to_show.push_str(&error_msg);
-                            break;
-                        }
+        overlay::set_font_size(config.overlay_font_size);
+        // Giọng đọc của vùng này (có thể khác giọng chung), gửi kèm từng đoạn cho thread TTS
+        let tts_lang = config.tts_language_code().to_string();
+
+        let api = if use_ocr { config.ocr_translator_api() } else { config.selected_api.clone() };
+        let provider = translation::get_provider(&api, &config);
+        let needs_key = provider.as_ref().map_or(true, |p| p.capabilities().needs_api_key);
+        let ocr_engine = if use_ocr { Some(ocr::engine_from_config(&config)) } else { None };
+        let glossary = if config.use_glossary { glossary::Glossary::load() } else { glossary::Glossary::default() };
+
+        // Chụp mọi vùng trước để các vùng cùng thuộc 1 khung hình, rồi mới gửi đi dịch.
+        // OCR chạy trên máy nên giữ PNG, chỉ nén khi gửi ảnh lên mạng.
+        let encoding = if use_ocr { config::ImageEncoding::Png } else { config.preprocess.encoding };
+        let images: Vec<Vec<u8>> = regions.iter()
+            .map(|r| capture::capture_raw(r).and_then(|img| preprocess::prepare(img, &config.preprocess, encoding)).unwrap_or_default())
+            .collect();
+
+        // Các vùng dịch song song dùng chung bộ key (chọn key / ghi nhận 429 phải tuần tự)
+        let job = RegionJob {
+            config: &config,
+            keys: std::sync::Mutex::new(config.clone()),
+            api: &api,
+            needs_key,
+            ocr_engine: ocr_engine.as_deref(),
+            glossary: &glossary,
+            template: &template,
+        };
+        // Bản dịch tạm của vùng đầu tiên hiện dần trong khung loading (mode auto)
+        let spoken_early = std::sync::Mutex::new(String::new());
+        let on_partial = |partial: &str| {
+            if config.show_overlay { overlay::update_loading_text(partial); }
+            // Đọc luôn câu đầu tiên, phần còn lại đọc khi có kết quả cuối
+            if config.use_tts && config.tts_first_sentence {
+                let mut spoken = spoken_early.lock().unwrap();
+                if spoken.is_empty() {
+                    if let Some(sentence) = first_sentence(partial) {
+                        *spoken = sentence.to_string();
+                        let _ = tx.send(tts::Speech { text: sentence.to_string(), lang: tts_lang.clone(), split: config.split_tts, speed: config.speed, use_tts: true, req_id: rand::random::<u64>() });
                     }
                 }
-                if !success && final_text_to_show.is_empty() { final_text_to_show.push_str("... "); }
-                final_text_to_show.push(' ');
             }
-        }
+        };
+        let stream_first = config.stream_translation;
+
+        // Gộp mọi vùng vào 1 request nếu bật (chỉ khi dịch thẳng từ ảnh); không được thì dịch từng vùng
+        let batched = if config.batch_regions && !use_ocr && regions.len() > 1 { job.translate_batch(&regions, &images).await } else { None };
+        let outputs: Vec<RegionOutput> = match batched {
+            Some(outputs) => outputs,
+            // `buffered` giữ đúng thứ tự vùng dù vùng sau dịch xong trước
+            None => futures::stream::iter(regions.iter().zip(images.iter()).enumerate())
+                .map(|(i, (region, image_bytes))| job.translate(region, image_bytes, if i == 0 && stream_first { Some(&on_partial) } else { None }))
+                .buffered(config.max_parallel_regions.max(1))
+                .collect()
+                .await,
+        };
 
-        let cleaned_show = final_text_to_show.trim().to_string();
-        let cleaned_copy = final_text_to_copy.trim().to_string();
+        // Lưu bộ đếm / cooldown của key để UI hiển thị và lần dịch sau chọn key đúng
+        if needs_key { job.keys.into_inner().unwrap().persist_key_usage(&api); }
+
+        let cleaned_show = outputs.iter().map(|o| o.show.as_str()).filter(|t| !t.is_empty()).collect::<Vec<_>>().join(" ");
+        let cleaned_copy = outputs.iter().map(|o| o.copy.as_str()).filter(|t| !t.is_empty()).collect::<Vec<_>>().join(" ");
 
         if !cleaned_show.is_empty() {
             if should_copy {
@@ -346,17 +471,31 @@ impl MainApp {
             }
 
             let req_id = rand::random::<u64>();
-            let _ = tx.send((cleaned_show.clone(), config.split_tts, config.speed, config.use_tts, req_id));
+            // Bỏ phần đã đọc sớm khi stream (nếu bản cuối vẫn chứa nguyên câu đó)
+            let spoken = spoken_early.into_inner().unwrap();
+            let tts_text = match cleaned_show.find(spoken.as_str()) {
+                Some(pos) if !spoken.is_empty() => cleaned_show[pos + spoken.len()..].trim().to_string(),
+                _ => cleaned_show.clone(),
+            };
+            if !tts_text.is_empty() {
+                let _ = tx.send(tts::Speech { text: tts_text, lang: tts_lang, split: config.split_tts, speed: config.speed, use_tts: config.use_tts, req_id });
+            }
             if config.show_overlay {
-                if let Some(region) = regions.first() {
+                // Mỗi vùng một khung kết quả ngay trên vùng đó, cùng req_id để TTS đọc xong thì đóng hết
+                let mut use_loading = true;
+                for (region, output) in regions.iter().zip(outputs) {
+                    if output.show.is_empty() { continue; }
+                    let try_loading = std::mem::replace(&mut use_loading, false);
+                    // Vùng gắn cửa sổ game: hiện đúng chỗ cửa sổ đang đứng
+                    let region = window::resolve(region);
                     let rect = RECT { left: region.x, top: region.y, right: region.x + region.width as i32, bottom: region.y + region.height as i32 };
-                    let duration_ms = (cleaned_show.chars().count() as f32 / 10.0 * 1000.0) as u32;
-                    let text_final = cleaned_show.clone();
-                    let req_id_clone = req_id;
+                    // Thời gian hiện theo độ dài chữ của riêng vùng này
+                    let duration_ms = (output.show.chars().count() as f32 / 10.0 * 1000.0) as u32;
+                    let text_final = output.show;
                     std::thread::spawn(move || {
-                        // Nếu không update được (do không phải mode auto/không có loading), thì hiện cửa sổ mới
-                        if !overlay::update_loading_window(text_final.clone()) {
-                              overlay::show_result_window_internal(rect, text_final, duration_ms, false, req_id_clone);
+                        // Khung loading (mode auto) dành cho vùng đầu tiên có kết quả; không update được thì hiện cửa sổ mới
+                        if !try_loading || !overlay::update_loading_window(text_final.clone()) {
+                            overlay::show_result_window_internal(rect, text_final, duration_ms, false, req_id);
                         }
                     });
                 }
@@ -390,12 +529,12 @@ impl MainApp {
     }
 
     fn start_service(&mut self) {
-        let (tx, rx) = std::sync::mpsc::channel::<(String, bool, f32, bool, u64)>();
+        let (tx, rx) = std::sync::mpsc::channel::<tts::Speech>();
 
         std::thread::spawn(move || {
             let rt = tokio::runtime::Runtime::new().unwrap();
-            while let Ok((text, split_tts, speed, use_tts, req_id)) = rx.recv() {
-                rt.block_on(async { if let Err(_e) = tts::speak(&text, split_tts, speed, use_tts, req_id).await {} });
+            while let Ok(speech) = rx.recv() {
+                rt.block_on(async { if let Err(_e) = tts::speak_in(&speech.text, &speech.lang, speech.split, speech.speed, speech.use_tts, speech.req_id).await {} });
             }
         });
 
@@ -405,61 +544,52 @@ impl MainApp {
         // Thread Auto Translate
         std::thread::spawn(move || {
             let rt = tokio::runtime::Runtime::new().unwrap();
-            let load_arrow = || -> Vec<u8> {
-                let custom_path = config::Config::get_custom_arrow_path();
-                if custom_path.exists() { if let Ok(b) = fs::read(custom_path) { return b; } }
-                DEFAULT_ARROW.to_vec()
+            let load_arrows = |config: &config::Config| -> Vec<matcher::TemplateMatcher> {
+                let library = templates::TemplateLibrary::load();
+                let matchers: Vec<_> = library.for_game(&config.active_game).into_iter()
+                    .filter_map(|t| matcher::TemplateMatcher::from_bytes(&t.read()?, &t.match_settings(&config.arrow_match)))
+                    .collect();
+                // Chưa có mẫu nào cho game này thì dùng mũi tên có sẵn
+                if matchers.is_empty() { matcher::TemplateMatcher::from_bytes(DEFAULT_ARROW, &config.arrow_match).into_iter().collect() } else { matchers }
             };
-            let arrow_bytes = load_arrow();
-            let mut last_found_state = false;
-            let mut miss_counter = 0;
+            let mut auto_trigger = trigger::AutoTrigger::new(Box::new(load_arrows));
 
             loop {
                 let config = config::Config::load();
                 let check_interval = config.arrow_check_interval;
                 let enabled = AUTO_TRANSLATE_ENABLED.load(Ordering::Relaxed);
-                
-                if !enabled || arrow_bytes.is_empty() {
+
+                if !enabled {
+                    auto_trigger.reset();
                     std::thread::sleep(std::time::Duration::from_millis(THREAD_SLEEP_MS));
                     continue;
                 }
 
-                if let Some(arrow_region) = &config.arrow_region {
-                    let found = capture::is_template_present(arrow_region, &arrow_bytes);
-
-                    // --- THÊM DÒNG NÀY ĐỂ CẬP NHẬT TRẠNG THÁI DEBUG ---
+                // Có câu thoại mới cần dịch hay không, theo cách kích hoạt của game đang chơi
+                let fire = auto_trigger.check(capture::source().as_ref(), &config);
+                if let Some(found) = auto_trigger.arrow_found() {
                     crate::overlay::ARROW_DEBUG_STATE.store(found, Ordering::Relaxed);
-                    // --------------------------------------------------
-
-                    if found {
-                        miss_counter = 0;
-                        if !last_found_state {
-                            // --- SỬA Ở ĐÂY: Hiện Loading Overlay ngay lập tức ---
-                            if config.show_overlay {
-                                if let Some(target_region) = config.fixed_regions.first() {
-                                    let rect = RECT {
-                                        left: target_region.x,
-                                        top: target_region.y,
-                                        right: target_region.x + target_region.width as i32,
-                                        bottom: target_region.y + target_region.height as i32
-                                    };
-                                    // Hiện cửa sổ loading "..."
-                                    std::thread::spawn(move || { overlay::show_loading_window(rect); });
-                                }
-                            }
-                            // ----------------------------------------------------
+                }
 
-                            let tx_inner = tx_auto.clone();
-                            let should_copy = config.auto_copy && !config.copy_instant_only;
-                            rt.block_on(async { Self::translate_regions(config.clone(), config.fixed_regions.clone(), tx_inner, should_copy).await; });
-                            last_found_state = true;
-                        }
-                    } else {
-                        if last_found_state {
-                            miss_counter += 1;
-                            if miss_counter > MISS_COUNTER_THRESHOLD { last_found_state = false; miss_counter = 0; }
+                if fire {
+                    let (region_config, should_copy) = config.for_region(&config.main_region_settings, config.auto_copy && !config.copy_instant_only);
+                    // --- SỬA Ở ĐÂY: Hiện Loading Overlay ngay lập tức ---
+                    if region_config.show_overlay {
+                        if let Some(target_region) = config.fixed_regions.first().map(window::resolve) {
+                            let rect = RECT {
+                                left: target_region.x,
+                                top: target_region.y,
+                                right: target_region.x + target_region.width as i32,
+                                bottom: target_region.y + target_region.height as i32
+                            };
+                            // Hiện cửa sổ loading "..."
+                            std::thread::spawn(move || { overlay::show_loading_window(rect); });
                         }
                     }
+                    // ----------------------------------------------------
+
+                    let tx_inner = tx_auto.clone();
+                    rt.block_on(async { Self::translate_regions(region_config, config.fixed_regions.clone(), tx_inner, should_copy).await; });
                 }
                 std::thread::sleep(std::time::Duration::from_secs_f32(check_interval)); 
             }
@@ -489,11 +619,13 @@ impl MainApp {
                     let k3 = crate::key_utils::get_vk_from_name(&cfg.hotkey_instant);
                     let k4 = crate::key_utils::get_vk_from_name(&cfg.hotkey_auto);
                     let k5 = crate::key_utils::get_vk_from_name(&cfg.hotkey_toggle_auto);
+                    let k6 = crate::key_utils::get_vk_from_name(&cfg.hotkey_clear_context);
                     if k1 > 0 { RegisterHotKey(hwnd, 1, 0, k1 as UINT); }
                     if k2 > 0 { RegisterHotKey(hwnd, 2, 0, k2 as UINT); }
                     if k3 > 0 { RegisterHotKey(hwnd, 3, 0, k3 as UINT); }
                     if k4 > 0 { RegisterHotKey(hwnd, 4, 0, k4 as UINT); }
                     if k5 > 0 { RegisterHotKey(hwnd, 5, 0, k5 as UINT); }
+                    if k6 > 0 { RegisterHotKey(hwnd, 6, 0, k6 as UINT); }
 
                     // --- AUX REGIONS KEYS ---
                     for (i, aux) in cfg.aux_regions.iter().enumerate() {
@@ -523,8 +655,8 @@ impl MainApp {
                             if id == 1 { // Translate
                                 let tx = tx_clone.clone();
                                 let rt = tokio::runtime::Runtime::new().unwrap();
-                                let should_copy = config.auto_copy && !config.copy_instant_only;
-                                std::thread::spawn(move || { rt.block_on(async { Self::translate_regions(config.clone(), config.fixed_regions.clone(), tx, should_copy).await; }); });
+                                let (region_config, should_copy) = config.for_region(&config.main_region_settings, config.auto_copy && !config.copy_instant_only);
+                                std::thread::spawn(move || { rt.block_on(async { Self::translate_regions(region_config, config.fixed_regions.clone(), tx, should_copy).await; }); });
                             } else if id == 2 { // Select
                                 if !OVERLAY_ACTIVE.load(Ordering::Relaxed) {
                                     let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as u64;
@@ -561,6 +693,9 @@ impl MainApp {
                                 AUTO_TRANSLATE_ENABLED.store(new_state, Ordering::Relaxed);
                                 // Gọi hàm thông báo
                                 show_toggle_notification(new_state);
+                            } else if id == 6 { // Clear Context
+                                context::clear();
+                                show_notification("Đã xóa ngữ cảnh hội thoại");
                             }
                             // --- AUX REGIONS KEYS ---
                             else if id >= 100 && id < 200 { // Select Aux
@@ -576,9 +711,9 @@ impl MainApp {
                                     if let Some(region) = &config.aux_regions[idx].region {
                                         let tx = tx_clone.clone();
                                         let rt = tokio::runtime::Runtime::new().unwrap();
-                                        let should_copy = config.auto_copy && !config.copy_instant_only;
+                                        let (region_config, should_copy) = config.for_region(&config.aux_regions[idx].settings, config.auto_copy && !config.copy_instant_only);
                                         let reg_clone = region.clone();
-                                        std::thread::spawn(move || { rt.block_on(async { Self::translate_regions(config.clone(), vec![reg_clone], tx, should_copy).await; }); });
+                                        std::thread::spawn(move || { rt.block_on(async { Self::translate_regions(region_config, vec![reg_clone], tx, should_copy).await; }); });
                                     }
                                 }
                             }
@@ -702,19 +837,13 @@ impl eframe::App for MainApp {
 
         // SYNC UI: Đảm bảo nút "Bật Tự Động Dịch" hiển thị đúng theo biến Global
         // Nếu dùng phím tắt để tắt/bật thì nút trên giao diện phải đổi màu theo
-        self.wwm_state.auto_translate_active = AUTO_TRANSLATE_ENABLED.load(Ordering::Relaxed);
+        self.game_state.auto_translate_active = AUTO_TRANSLATE_ENABLED.load(Ordering::Relaxed);
 
         if self.last_config_sync.elapsed() > Duration::from_secs(CONFIG_SYNC_INTERVAL_SECS) {
             self.sync_config_from_file();
             self.last_config_sync = std::time::Instant::now();
         }
 
-        if self.arrow_texture.is_none() {
-              let custom_path = config::Config::get_custom_arrow_path();
-              if custom_path.exists() { if let Ok(b) = fs::read(custom_path) { self.load_texture(ctx, &b, true); } }
-              else { self.load_texture(ctx, DEFAULT_ARROW, true); }
-        }
-
         // --- LAYOUT CHIA 2 CỘT ---
         egui::CentralPanel::default().show(ctx, |ui| {
             self.render_header(ui);
@@ -732,6 +861,9 @@ impl eframe::App for MainApp {
                         ui.add_space(10.0);
                         
                         self.render_settings_section(ui);
+                        ui.add_space(10.0);
+
+                        self.render_preprocess_section(ui);
                     });
 
                     // Cột phải
@@ -742,17 +874,19 @@ impl eframe::App for MainApp {
                         self.render_aux_regions_section(ui);
                         ui.add_space(10.0);
                         
-                        self.render_wwm_section(ctx, ui);
+                        self.render_game_section(ctx, ui);
                     });
                 });
             });
         });
 
         self.render_reader_window(ctx);
+        self.render_glossary_window(ctx);
+        self.render_arrow_templates_window(ctx);
 
         if self.ui_state.show_popup {
             let mut open = true;
-            let title = if self.ui_state.popup_text == "gemini" { "Hướng dẫn Gemini" } else { "Hướng dẫn Groq" };
+            let title = match self.ui_state.popup_text.as_str() { "gemini" => "Hướng dẫn Gemini", "openai" => "Hướng dẫn OpenAI-compatible", "ollama" => "Hướng dẫn Ollama", _ => "Hướng dẫn Groq" };
             egui::Window::new(title).collapsible(false).resizable(false).open(&mut open).show(ctx, |ui| {
                 ui.spacing_mut().item_spacing = egui::vec2(10.0, 10.0);
                 if self.ui_state.popup_text == "gemini" {
@@ -763,6 +897,16 @@ impl eframe::App for MainApp {
                     ui.heading("Groq API (Nhanh)");
                     ui.horizontal(|ui| { ui.label("1. Vào:"); ui.hyperlink("https://console.groq.com/keys"); });
                     ui.label("2. Đăng nhập -> Create API Key"); ui.label("3. Copy key và dán vào tool");
+                } else if self.ui_state.popup_text == "openai" {
+                    ui.heading("Endpoint OpenAI-compatible");
+                    ui.label("1. Chạy server (LM Studio, vLLM, llama.cpp) hoặc dùng OpenRouter");
+                    ui.label("2. Nhập Base URL (vd: http://localhost:1234/v1) và tên Model có hỗ trợ ảnh");
+                    ui.label("3. Nhập Key nếu server yêu cầu, header thêm nếu cần (vd: HTTP-Referer)");
+                } else if self.ui_state.popup_text == "ollama" {
+                    ui.heading("Ollama (Offline)");
+                    ui.horizontal(|ui| { ui.label("1. Cài Ollama:"); ui.hyperlink("https://ollama.com/download"); });
+                    ui.label("2. Tải model có hỗ trợ ảnh: ollama pull llava (hoặc qwen2.5vl, minicpm-v...)");
+                    ui.label("3. Bấm 🔄 để tải danh sách model, chọn model rồi bấm Kiểm tra");
                 }
                 ui.separator();
                 ui.vertical_centered(|ui| { if ui.button("Đã hiểu").clicked() { self.ui_state.show_popup = false; } });
@@ -777,15 +921,12 @@ impl eframe::App for MainApp {
                 ui.add_space(10.0);
                 ui.horizontal(|ui| {
                     if ui.button("✅ Có (Giữ Key)").clicked() {
-                        let saved_gemini = self.config_state.gemini_api_key.clone();
-                        let saved_groq = self.config_state.config.groq_api_keys.clone();
+                        let saved_keys = self.config_state.config.key_pools.clone();
                         self.config_state.config = config::Config::default();
-                        self.config_state.config.gemini_api_key = saved_gemini;
-                        self.config_state.config.groq_api_keys = saved_groq;
+                        self.config_state.config.key_pools = saved_keys;
 
-                        self.config_state.gemini_api_key = self.config_state.config.gemini_api_key.clone();
                         self.config_state.current_prompt = self.config_state.config.current_prompt.clone();
-                        self.config_state.editing_prompt_index = None;
+                        self.config_state.config.active_prompt = None;
                         self.hotkey_state.hotkey_translate = self.config_state.config.hotkey_translate.clone();
                         self.hotkey_state.hotkey_select = self.config_state.config.hotkey_select.clone();
                         self.hotkey_state.hotkey_instant = self.config_state.config.hotkey_instant.clone();
@@ -800,10 +941,8 @@ impl eframe::App for MainApp {
                     }
                     if ui.button("❌ Không (Xóa sạch)").clicked() {
                         self.config_state.config = config::Config::default();
-                        self.config_state.gemini_api_key = String::new();
-                        self.config_state.config.groq_api_keys = vec![String::new()];
                         self.config_state.current_prompt = self.config_state.config.current_prompt.clone();
-                        self.config_state.editing_prompt_index = None;
+                        self.config_state.config.active_prompt = None;
                         self.hotkey_state.hotkey_translate = self.config_state.config.hotkey_translate.clone();
                         self.hotkey_state.hotkey_select = self.config_state.config.hotkey_select.clone();
                         self.hotkey_state.hotkey_instant = self.config_state.config.hotkey_instant.clone();
@@ -824,19 +963,6 @@ impl eframe::App for MainApp {
             if !open { self.ui_state.show_reset_confirm = false; }
         }
 
-        if self.ui_state.show_arrow_window {
-            let mut open = true;
-            egui::Window::new("Mũi tên hiện tại").open(&mut open).collapsible(false).resizable(true).default_size(egui::vec2(300.0, 300.0)).show(ctx, |ui| {
-                  ui.label("Đây là hình ảnh mũi tên đang được dùng để nhận diện:");
-                  ui.separator();
-                if let Some(texture) = &self.arrow_texture {
-                    let s = texture.size_vec2() * 2.0;
-                    ui.centered_and_justified(|ui| { ui.image((texture.id(), s)); });
-                } else { ui.label("Không tìm thấy arrow.png"); }
-            });
-            if !open { self.ui_state.show_arrow_window = false; }
-        }
-
         if self.ui_state.show_arrow_help {
             let mut open = true;
             egui::Window::new("Giải thích Mũi tên").open(&mut open).collapsible(false).resizable(false).show(ctx, |ui| {
@@ -851,6 +977,11 @@ impl eframe::App for MainApp {
 }
 
 fn main() -> Result<(), eframe::Error> {
+    // Chạy thử bằng ảnh chụp sẵn thay cho màn hình thật (biến môi trường SCREEN_TRANSLATOR_FIXTURES)
+    if let Err(e) = capture::init_from_env() {
+        show_notification(&format!("Không đọc được ảnh chụp sẵn: {}", e));
+    }
+
     let mut options = eframe::NativeOptions::default();
     options.viewport.transparent = Some(false);
 
@@ -924,6 +1055,7 @@ fn main() -> Result<(), eframe::Error> {
                         let _ = tx_clone2.send(AppSignal::Show);
                         ctx_clone2.request_repaint();
                     } else if event.id.as_ref() == "quit" {
+                        cache::flush();
                         std::process::exit(0);
                     }
                 }
@@ -936,7 +1068,10 @@ fn main() -> Result<(), eframe::Error> {
 
 // --- THÊM HÀM NÀY VÀO CUỐI FILE ---
 pub fn show_toggle_notification(enabled: bool) {
-    let text = if enabled { "Đã bật tự động dịch" } else { "Đã tắt tự động dịch" };
+    show_notification(if enabled { "Đã bật tự động dịch" } else { "Đã tắt tự động dịch" });
+}
+
+pub fn show_notification(text: &str) {
     let req_id = rand::random::<u64>();
 
     // 1. Phát âm thanh (TTS)
@@ -945,7 +1080,7 @@ pub fn show_toggle_notification(enabled: bool) {
     std::thread::spawn(move || {
         let rt = tokio::runtime::Runtime::new().unwrap();
         rt.block_on(async {
-            let _ = crate::tts::speak(&text_audio, false, 1.2, true, req_id_tts).await;
+            let _ = crate::tts::speak_in(&text_audio, "vi", false, 1.2, true, req_id_tts).await;
         });
     });
 
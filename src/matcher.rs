@@ -0,0 +1,401 @@
+use image::{Rgba, RgbaImage, imageops::FilterType};
+use crate::config::MatchSettings;
+
+// Các tỉ lệ mẫu thử khi game đổi độ phân giải, tránh mức 0.5 (quá nhỏ)
+const SCALES: [f32; 11] = [1.0, 0.95, 1.05, 0.9, 1.1, 0.8, 1.2, 0.75, 1.25, 0.7, 1.3];
+// Quét đủ mọi tỉ lệ sau mỗi chừng này lần không thấy
+const SWEEP_EVERY: u32 = 5;
+// Mẫu nhỏ hơn mức này ở tầng thô thì quét thẳng ảnh gốc
+const MIN_COARSE_SIZE: u32 = 4;
+// Lề (pixel) quanh vị trí thấy lần trước để dò lại trước tiên
+const ROI_MARGIN: u32 = 6;
+// Bán kính dò lại ở ảnh gốc quanh điểm tìm được ở tầng thô
+const REFINE_RADIUS: u32 = 2;
+
+/// Vị trí khớp nhất của mẫu trong ảnh và độ tin cậy 0..1
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MatchResult {
+    pub x: u32,
+    pub y: u32,
+    pub confidence: f32,
+}
+
+fn luma(p: &Rgba<u8>) -> f64 {
+    (p[0] as f64 * 299.0 + p[1] as f64 * 587.0 + p[2] as f64 * 114.0) / 1000.0
+}
+
+/// Ảnh mẫu đã chuẩn hoá sẵn (trừ trung bình), dùng lại cho mọi lần quét
+struct Template {
+    width: u32,
+    height: u32,
+    // Độ sáng trừ trung bình, theo hàng
+    values: Vec<f64>,
+    // sqrt(tổng bình phương) của `values`
+    norm: f64,
+}
+
+impl Template {
+    /// Điểm có alpha dưới `alpha_cutoff` là nền trong suốt: tô bằng mức sáng tương phản nhất với hình
+    /// (mũi tên trắng -> nền đen) để NCC so được cả hình dạng chứ không chỉ màu.
+    fn new(img: &RgbaImage, alpha_cutoff: u8) -> Self {
+        let (width, height) = img.dimensions();
+        let opaque = |p: &Rgba<u8>| p[3] >= alpha_cutoff;
+        let (sum, count) = img.pixels().filter(|p| opaque(p)).fold((0.0, 0usize), |(sum, count), p| (sum + luma(p), count + 1));
+        let background = if count > 0 && sum / count as f64 > 127.0 { 0.0 } else { 255.0 };
+        let mut values: Vec<f64> = img.pixels().map(|p| if opaque(p) { luma(p) } else { background }).collect();
+        let mean = values.iter().sum::<f64>() / values.len().max(1) as f64;
+        for v in values.iter_mut() { *v -= mean; }
+        let norm = values.iter().map(|v| v * v).sum::<f64>().sqrt();
+        Self { width, height, values, norm }
+    }
+
+    fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+}
+
+/// Bảng cộng dồn tổng và tổng bình phương độ sáng, lấy tổng 1 ô chữ nhật bất kỳ trong O(1)
+struct Integral {
+    stride: usize,
+    sum: Vec<f64>,
+    sq: Vec<f64>,
+}
+
+impl Integral {
+    fn new(gray: &[f64], width: u32, height: u32) -> Self {
+        let stride = width as usize + 1;
+        let mut sum = vec![0.0; stride * (height as usize + 1)];
+        let mut sq = sum.clone();
+        for y in 0..height as usize {
+            let (mut row_sum, mut row_sq) = (0.0, 0.0);
+            for x in 0..width as usize {
+                let v = gray[y * width as usize + x];
+                row_sum += v;
+                row_sq += v * v;
+                sum[(y + 1) * stride + x + 1] = sum[y * stride + x + 1] + row_sum;
+                sq[(y + 1) * stride + x + 1] = sq[y * stride + x + 1] + row_sq;
+            }
+        }
+        Self { stride, sum, sq }
+    }
+
+    fn window(&self, table: &[f64], x: usize, y: usize, w: usize, h: usize) -> f64 {
+        let s = self.stride;
+        table[(y + h) * s + x + w] - table[y * s + x + w] - table[(y + h) * s + x] + table[y * s + x]
+    }
+}
+
+/// 1 khung hình đã đổi sang độ sáng, kèm bảng cộng dồn
+struct Frame {
+    width: u32,
+    height: u32,
+    gray: Vec<f64>,
+    integral: Integral,
+}
+
+impl Frame {
+    fn new(img: &RgbaImage) -> Self {
+        let (width, height) = img.dimensions();
+        Self::from_gray(img.pixels().map(luma).collect(), width, height)
+    }
+
+    fn from_gray(gray: Vec<f64>, width: u32, height: u32) -> Self {
+        let integral = Integral::new(&gray, width, height);
+        Self { width, height, gray, integral }
+    }
+
+    /// Tầng thô của kim tự tháp: thu nhỏ 1/2 bằng trung bình 2x2
+    fn half(&self) -> Self {
+        let (width, height) = (self.width / 2, self.height / 2);
+        let w = self.width as usize;
+        let mut gray = Vec::with_capacity((width * height) as usize);
+        for y in 0..height as usize {
+            for x in 0..width as usize {
+                let i = 2 * y * w + 2 * x;
+                gray.push((self.gray[i] + self.gray[i + 1] + self.gray[i + w] + self.gray[i + w + 1]) / 4.0);
+            }
+        }
+        Self::from_gray(gray, width, height)
+    }
+
+    /// Hệ số tương quan chuẩn hoá (ZNCC) tại 1 vị trí, cắt về 0..1 (tương quan âm coi như không khớp)
+    fn score(&self, template: &Template, x: u32, y: u32) -> f32 {
+        let (tw, th) = (template.width as usize, template.height as usize);
+        let n = (tw * th) as f64;
+        let sum = self.integral.window(&self.integral.sum, x as usize, y as usize, tw, th);
+        let sq = self.integral.window(&self.integral.sq, x as usize, y as usize, tw, th);
+        let variance = sq - sum * sum / n;
+        // Vùng phẳng (không có hình gì) hoặc mẫu phẳng: không so được
+        if variance <= 1e-6 || template.norm <= 1e-6 { return 0.0; }
+
+        // Mẫu đã trừ trung bình nên không cần trừ trung bình của vùng ảnh ở tử số
+        let mut dot = 0.0;
+        for row in 0..th {
+            let src = &self.gray[(y as usize + row) * self.width as usize + x as usize..][..tw];
+            let tpl = &template.values[row * tw..][..tw];
+            dot += src.iter().zip(tpl).map(|(a, b)| a * b).sum::<f64>();
+        }
+        (dot / (variance.sqrt() * template.norm)).clamp(0.0, 1.0) as f32
+    }
+
+    /// Vị trí khớp nhất có góc trên trái trong khoảng [x0, x1] x [y0, y1] (tự cắt theo khung hình).
+    /// Quét thưa theo `step` rồi dò kỹ quanh điểm tốt nhất.
+    fn best_in(&self, template: &Template, (x0, y0): (u32, u32), (x1, y1): (u32, u32), step: u32) -> Option<MatchResult> {
+        let (tw, th) = template.dimensions();
+        if tw == 0 || th == 0 || self.width < tw || self.height < th { return None; }
+        let (x1, y1) = (x1.min(self.width - tw), y1.min(self.height - th));
+        if x0 > x1 || y0 > y1 { return None; }
+        let step = step.max(1);
+
+        let mut best = MatchResult { x: x0, y: y0, confidence: -1.0 };
+        let consider = |x: u32, y: u32, best: &mut MatchResult| {
+            let confidence = self.score(template, x, y);
+            if confidence > best.confidence { *best = MatchResult { x, y, confidence }; }
+        };
+        for y in (y0..=y1).step_by(step as usize) {
+            for x in (x0..=x1).step_by(step as usize) {
+                consider(x, y, &mut best);
+            }
+        }
+        if step > 1 {
+            let (cx, cy) = (best.x, best.y);
+            for y in cy.saturating_sub(step - 1).max(y0)..=(cy + step - 1).min(y1) {
+                for x in cx.saturating_sub(step - 1).max(x0)..=(cx + step - 1).min(x1) {
+                    consider(x, y, &mut best);
+                }
+            }
+        }
+        Some(best)
+    }
+}
+
+/// Mẫu ở 1 tỉ lệ, kèm bản thu nhỏ 1/2 cho tầng thô
+struct ScaledTemplate {
+    full: Template,
+    coarse: Option<Template>,
+}
+
+impl ScaledTemplate {
+    fn new(img: &RgbaImage, alpha_cutoff: u8) -> Self {
+        let (w, h) = (img.width() / 2, img.height() / 2);
+        let coarse = (w >= MIN_COARSE_SIZE && h >= MIN_COARSE_SIZE)
+            .then(|| Template::new(&image::imageops::resize(img, w, h, FilterType::Triangle), alpha_cutoff));
+        Self { full: Template::new(img, alpha_cutoff), coarse }
+    }
+}
+
+/// Dò 1 ảnh mẫu qua nhiều khung hình liên tiếp: mẫu được giải mã và phóng to/thu nhỏ sẵn 1 lần,
+/// dò lại quanh vị trí thấy lần trước rồi mới quét thô -> tinh (kim tự tháp 2 tầng) cả vùng.
+pub struct TemplateMatcher {
+    settings: MatchSettings,
+    // (tỉ lệ, mẫu); tỉ lệ nào làm mẫu nhỏ quá thì không có
+    templates: Vec<(f32, ScaledTemplate)>,
+    // Tỉ lệ khớp gần nhất, thử đầu tiên
+    last_scale: f32,
+    last_hit: Option<MatchResult>,
+    misses: u32,
+}
+
+impl TemplateMatcher {
+    pub fn new(template: &RgbaImage, settings: &MatchSettings) -> Self {
+        let (w, h) = template.dimensions();
+        let templates = SCALES.iter().filter_map(|&scale| {
+            let (sw, sh) = ((w as f32 * scale) as u32, (h as f32 * scale) as u32);
+            if sw < 5 || sh < 5 { return None; }
+            let scaled = if scale == 1.0 { template.clone() } else { image::imageops::resize(template, sw, sh, FilterType::Lanczos3) };
+            Some((scale, ScaledTemplate::new(&scaled, settings.alpha_cutoff)))
+        }).collect();
+        Self { settings: settings.clone(), templates, last_scale: 1.0, last_hit: None, misses: 0 }
+    }
+
+    pub fn from_bytes(bytes: &[u8], settings: &MatchSettings) -> Option<Self> {
+        let img = image::load_from_memory(bytes).ok()?.to_rgba8();
+        Some(Self::new(&img, settings))
+    }
+
+    /// Vị trí mẫu trong `haystack` nếu đạt ngưỡng
+    pub fn find(&mut self, haystack: &RgbaImage) -> Option<MatchResult> {
+        if self.templates.is_empty() { return None; }
+        let threshold = self.settings.threshold;
+        let frame = Frame::new(haystack);
+
+        // 1. Mũi tên thường đứng yên chỗ cũ: chỉ dò 1 ô nhỏ quanh đó
+        if let (Some(hit), Some(template)) = (self.last_hit, self.template(self.last_scale)) {
+            let template = &template.full;
+            let from = (hit.x.saturating_sub(ROI_MARGIN), hit.y.saturating_sub(ROI_MARGIN));
+            let found = frame.best_in(template, from, (hit.x + ROI_MARGIN, hit.y + ROI_MARGIN), 1).filter(|m| m.confidence >= threshold);
+            if found.is_some() {
+                self.last_hit = found;
+                return found;
+            }
+        }
+        self.last_hit = None;
+
+        // 2. Quét cả vùng: tỉ lệ lần trước và tỉ lệ gốc mỗi lần, đủ mọi tỉ lệ thì thưa hơn
+        let coarse_frame = frame.half();
+        let sweep = self.misses % SWEEP_EVERY == 0;
+        let mut order = vec![self.last_scale];
+        if self.last_scale != 1.0 { order.push(1.0); }
+        if sweep { order.extend(self.templates.iter().map(|(scale, _)| *scale).filter(|&scale| scale != self.last_scale && scale != 1.0)); }
+
+        for scale in order {
+            let Some(template) = self.template(scale) else { continue };
+            let found = self.search(&frame, &coarse_frame, template).filter(|m| m.confidence >= threshold);
+            if found.is_some() {
+                self.last_scale = scale;
+                self.last_hit = found;
+                self.misses = 0;
+                return found;
+            }
+        }
+        self.misses = self.misses.wrapping_add(1);
+        None
+    }
+
+    /// Vị trí khớp nhất ở mọi tỉ lệ, không xét ngưỡng (để xem thử độ tin cậy của mẫu mới)
+    pub fn best(&self, haystack: &RgbaImage) -> Option<MatchResult> {
+        let frame = Frame::new(haystack);
+        let coarse_frame = frame.half();
+        self.templates.iter().filter_map(|(_, t)| self.search(&frame, &coarse_frame, t)).max_by(|a, b| a.confidence.total_cmp(&b.confidence))
+    }
+
+    fn template(&self, scale: f32) -> Option<&ScaledTemplate> {
+        self.templates.iter().find(|(s, _)| *s == scale).map(|(_, t)| t)
+    }
+
+    fn search(&self, frame: &Frame, coarse_frame: &Frame, template: &ScaledTemplate) -> Option<MatchResult> {
+        let everywhere = ((0, 0), (u32::MAX, u32::MAX));
+        match &template.coarse {
+            Some(coarse) => {
+                let rough = coarse_frame.best_in(coarse, everywhere.0, everywhere.1, self.settings.step)?;
+                let (cx, cy) = (rough.x * 2, rough.y * 2);
+                frame.best_in(&template.full, (cx.saturating_sub(REFINE_RADIUS), cy.saturating_sub(REFINE_RADIUS)), (cx + REFINE_RADIUS, cy + REFINE_RADIUS), 1)
+            }
+            None => frame.best_in(&template.full, everywhere.0, everywhere.1, self.settings.step),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Nền có vân (nhiễu cố định) để NCC không khớp bừa vào vùng phẳng
+    fn background(width: u32, height: u32) -> RgbaImage {
+        let mut seed = 0x2545_f491_u32;
+        RgbaImage::from_fn(width, height, |x, y| {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let v = (40 + (x + y) / 6 + (seed >> 27)) as u8;
+            Rgba([v, v.saturating_sub(5), v / 2 + 20, 255])
+        })
+    }
+
+    /// Mũi tên trắng trỏ xuống trên nền trong suốt, giống ảnh mẫu người dùng cắt ra
+    fn arrow(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            let half = width as f32 / 2.0;
+            let inside = (x as f32 + 0.5 - half).abs() <= half * (1.0 - y as f32 / height as f32);
+            if inside { Rgba([245, 245, 235, 255]) } else { Rgba([0, 0, 0, 0]) }
+        })
+    }
+
+    /// Mũi tên có vân ca rô: khác tỉ lệ là lệch vân, không khớp nhầm như hình đặc
+    fn checkered(width: u32, height: u32, cell: f32) -> RgbaImage {
+        let mut img = arrow(width, height);
+        for (x, y, p) in img.enumerate_pixels_mut() {
+            if p[3] > 0 && ((x as f32 / cell) as u32 + (y as f32 / cell) as u32) % 2 == 1 { *p = Rgba([90, 60, 200, 255]); }
+        }
+        img
+    }
+
+    /// Dán `template` (trộn theo alpha) vào nền tại (x, y)
+    fn scene(template: &RgbaImage, x: u32, y: u32) -> RgbaImage {
+        let mut frame = background(160, 90);
+        for (tx, ty, p) in template.enumerate_pixels() {
+            let a = p[3] as f32 / 255.0;
+            let dst = frame.get_pixel_mut(x + tx, y + ty);
+            for c in 0..3 { dst[c] = (p[c] as f32 * a + dst[c] as f32 * (1.0 - a)).round() as u8; }
+        }
+        frame
+    }
+
+    fn matcher(template: &RgbaImage) -> TemplateMatcher {
+        TemplateMatcher::new(template, &MatchSettings::default())
+    }
+
+    #[test]
+    fn exact_hit() {
+        // Mẫu đục (cắt thẳng từ màn hình, có cả nền)
+        let frame = scene(&arrow(24, 14), 63, 41);
+        let template = image::imageops::crop_imm(&frame, 60, 38, 30, 20).to_image();
+        let found = matcher(&template).find(&frame).unwrap();
+        assert_eq!((found.x, found.y), (60, 38));
+        assert!(found.confidence > 0.99, "{:?}", found);
+    }
+
+    #[test]
+    fn shifted_hit() {
+        let template = arrow(24, 14);
+        let mut m = matcher(&template);
+        let found = m.find(&scene(&template, 63, 41)).unwrap();
+        assert_eq!((found.x, found.y), (63, 41));
+        // Lệch ít: thấy ngay trong ô quanh vị trí cũ
+        let found = m.find(&scene(&template, 66, 39)).unwrap();
+        assert_eq!((found.x, found.y), (66, 39));
+        // Chạy sang chỗ khác: quét lại cả vùng
+        let found = m.find(&scene(&template, 12, 70)).unwrap();
+        assert_eq!((found.x, found.y), (12, 70));
+        assert!(found.confidence >= 0.8, "{:?}", found);
+    }
+
+    #[test]
+    fn scaled_hit() {
+        // Game chạy ở độ phân giải khác: mũi tên to hơn mẫu 25%
+        let mut m = matcher(&checkered(32, 16, 4.0));
+        let found = m.find(&scene(&checkered(40, 20, 5.0), 40, 30)).unwrap();
+        assert!(found.x.abs_diff(40) <= 1 && found.y.abs_diff(30) <= 1, "{:?}", found);
+        assert!(found.confidence >= 0.8, "{:?}", found);
+    }
+
+    #[test]
+    fn small_template_remembers_scale() {
+        // Mẫu cao 6px: các tỉ lệ <= 0.8 bị bỏ, tỉ lệ nhớ lại vẫn phải đúng mẫu đã khớp
+        let template = checkered(24, 6, 3.0);
+        let mut m = TemplateMatcher::new(&template, &MatchSettings { threshold: 0.95, ..MatchSettings::default() });
+        assert!(m.templates.len() < SCALES.len());
+        let big = image::imageops::resize(&template, 31, 7, FilterType::Lanczos3);
+        let found = m.find(&scene(&big, 30, 20)).unwrap();
+        let scale = m.last_scale;
+        assert_eq!(scale, 1.3);
+        // Lần sau dò lại quanh chỗ cũ bằng đúng mẫu tỉ lệ đó
+        let again = m.find(&scene(&big, 32, 21)).unwrap();
+        assert_eq!((again.x, again.y), (32, 21));
+        assert!(found.confidence >= 0.95 && again.confidence >= 0.95, "{:?} {:?}", found, again);
+        assert_eq!(m.last_scale, scale);
+    }
+
+    #[test]
+    fn transparent_background_template() {
+        // Mũi tên có sẵn của app (PNG nền trong suốt) trên nền sáng / tối khác nhau
+        let template = image::load_from_memory(include_bytes!("arrow.png")).unwrap().to_rgba8();
+        assert!(template.pixels().any(|p| p[3] == 0));
+        let mut m = TemplateMatcher::from_bytes(include_bytes!("arrow.png"), &MatchSettings::default()).unwrap();
+        for (x, y) in [(20, 15), (110, 60)] {
+            let found = m.find(&scene(&template, x, y)).unwrap();
+            assert_eq!((found.x, found.y), (x, y));
+            assert!(found.confidence >= 0.8, "{:?}", found);
+        }
+    }
+
+    #[test]
+    fn missing_template() {
+        let m = matcher(&arrow(24, 14));
+        // Có hình khác (khối vuông sáng) nhưng không có mũi tên
+        let square = RgbaImage::from_pixel(20, 12, Rgba([245, 245, 235, 255]));
+        for frame in [background(160, 90), scene(&square, 50, 40)] {
+            let best = m.best(&frame).unwrap();
+            assert!(best.confidence < MatchSettings::default().threshold, "{:?}", best);
+            assert!(matcher(&arrow(24, 14)).find(&frame).is_none());
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::os::windows::process::CommandExt;
+use crate::config::Config;
+
+// Không bật cửa sổ console khi gọi tesseract.exe
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+/// Engine nhận dạng chữ chạy trên máy, trả về text thô từ ảnh PNG.
+pub trait OcrEngine: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn recognize(&self, png_bytes: &[u8]) -> Result<String, anyhow::Error>;
+}
+
+/// Gọi Tesseract qua dòng lệnh: ảnh đưa vào stdin, text đọc từ stdout.
+pub struct TesseractCli {
+    pub executable: String,
+    pub language: String,
+}
+
+impl TesseractCli {
+    pub fn from_config(config: &Config) -> Self {
+        Self { executable: config.tesseract_path.clone(), language: config.ocr_language.clone() }
+    }
+}
+
+impl OcrEngine for TesseractCli {
+    fn name(&self) -> &'static str { "Tesseract" }
+
+    fn recognize(&self, png_bytes: &[u8]) -> Result<String, anyhow::Error> {
+        let mut child = Command::new(&self.executable)
+            .args(["stdin", "stdout", "-l", &self.language, "--psm", "6"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .creation_flags(CREATE_NO_WINDOW)
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("Không chạy được Tesseract ({}): {}", self.executable, e))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(png_bytes)?;
+        }
+
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!("Tesseract Error: {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
+
+        // Tesseract giữ nguyên xuống dòng của hộp thoại, gộp lại thành 1 đoạn cho dễ dịch
+        let text = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        Ok(text)
+    }
+}
+
+pub fn engine_from_config(config: &Config) -> Box<dyn OcrEngine> {
+    Box::new(TesseractCli::from_config(config))
+}
@@ -50,6 +50,8 @@ static HOVER_MAP: OnceLock<Mutex<HashMap<usize, bool>>> = OnceLock::new();
 static WINDOW_REQ_IDS: OnceLock<Mutex<HashMap<usize, u64>>> = OnceLock::new();
 
 static SELECTION_MODE: AtomicU8 = AtomicU8::new(0);
+// Vùng vừa khoanh ở chế độ tạo mẫu mũi tên (mode 3), chụp sau khi overlay đã đóng
+static TEMPLATE_SELECTION: Mutex<Option<config::Region>> = Mutex::new(None);
 static DEBUG_ACTIVE: AtomicBool = AtomicBool::new(false);
 static CURRENT_FONT_SIZE: AtomicI32 = AtomicI32::new(24);
 
@@ -64,6 +66,10 @@ pub fn set_selection_mode(mode: u8) {
     SELECTION_MODE.store(mode, Ordering::Relaxed);
 }
 
+pub fn take_template_selection() -> Option<config::Region> {
+    TEMPLATE_SELECTION.lock().unwrap().take()
+}
+
 pub fn set_font_size(size: i32) {
     CURRENT_FONT_SIZE.store(size, Ordering::Relaxed);
 }
@@ -318,7 +324,7 @@ unsafe extern "system" fn selection_wnd_proc(hwnd: HWND, msg: u32, wparam: WPARA
                 IS_DRAGGING = false; ReleaseCapture();
                 let rect = RECT { left: START_POS.x.min(CURR_POS.x), top: START_POS.y.min(CURR_POS.y), right: START_POS.x.max(CURR_POS.x), bottom: START_POS.y.max(CURR_POS.y) };
                 if (rect.right - rect.left) > 10 && (rect.bottom - rect.top) > 10 {
-                    let region = config::Region { x: rect.left, y: rect.top, width: (rect.right - rect.left) as u32, height: (rect.bottom - rect.top) as u32 };
+                    let region = config::Region { x: rect.left, y: rect.top, width: (rect.right - rect.left) as u32, height: (rect.bottom - rect.top) as u32, anchor: None };
                     let hwnd_usize = hwnd as usize;
                     std::thread::spawn(move || { process_region(region); unsafe { PostMessageW(hwnd_usize as HWND, WM_CLOSE, 0, 0); } });
                 } else { PostMessageW(hwnd, WM_CLOSE, 0, 0); }
@@ -326,7 +332,7 @@ unsafe extern "system" fn selection_wnd_proc(hwnd: HWND, msg: u32, wparam: WPARA
         }
         WM_SETCURSOR => {
             let mode = SELECTION_MODE.load(Ordering::Relaxed);
-            if mode == 2 {
+            if mode == 2 || mode == 3 {
                 SetCursor(LoadCursorW(std::ptr::null_mut(), IDC_ARROW));
             } else {
                 SetCursor(std::ptr::null_mut());
@@ -437,8 +443,14 @@ unsafe extern "system" fn selection_wnd_proc(hwnd: HWND, msg: u32, wparam: WPARA
 }
 
 fn process_region(region: config::Region) {
-    let mut config = config::Config::load();
     let mode = SELECTION_MODE.load(Ordering::Relaxed);
+    if mode == 3 {
+        *TEMPLATE_SELECTION.lock().unwrap() = Some(region);
+        return;
+    }
+    let mut config = config::Config::load();
+    // Gắn vùng với cửa sổ game bên dưới để đi theo khi cửa sổ di chuyển / đổi cỡ
+    let region = if config.bind_regions_to_window { crate::window::bind_to_window_under(&region).unwrap_or(region) } else { region };
 
     if mode == 0 {
         config.fixed_regions.clear();
@@ -495,6 +507,18 @@ pub fn show_loading_window(rect: RECT) {
 
 // --- HÀM MỚI: Cập nhật nội dung cửa sổ "Loading" ---
 pub fn update_loading_window(text: String) -> bool { // Thêm -> bool
+    if !set_loading_text(&text) { return false; }
+    // Reset cờ chờ
+    PENDING_LOADING_HWND.store(0, Ordering::Relaxed);
+    true // Trả về true
+}
+
+/// Hiện bản dịch tạm (đang stream) trong cửa sổ "Loading", vẫn giữ cửa sổ để cập nhật tiếp
+pub fn update_loading_text(text: &str) -> bool {
+    set_loading_text(text)
+}
+
+fn set_loading_text(text: &str) -> bool {
     let hwnd_val = PENDING_LOADING_HWND.load(Ordering::Relaxed);
     if hwnd_val == 0 { return false; } // Trả về false
     let hwnd = hwnd_val as HWND;
@@ -517,7 +541,7 @@ pub fn update_loading_window(text: String) -> bool { // Thêm -> bool
         let max_text_width = width - padding * 2;
 
         let mut text_rect = RECT { left: 0, top: 0, right: max_text_width, bottom: 0 };
-        let wide_text = to_wide(&text);
+        let wide_text = to_wide(text);
         DrawTextW(hdc, wide_text.as_ptr(), -1, &mut text_rect, DT_CALCRECT | DT_WORDBREAK);
         let new_height = (text_rect.bottom - text_rect.top) + padding * 2;
 
@@ -528,11 +552,8 @@ pub fn update_loading_window(text: String) -> bool { // Thêm -> bool
         SetWindowTextW(hwnd, wide_text.as_ptr());
         MoveWindow(hwnd, rect.left, rect.top, width, new_height, 1);
         InvalidateRect(hwnd, std::ptr::null(), TRUE);
-
-        // Reset cờ chờ
-        PENDING_LOADING_HWND.store(0, Ordering::Relaxed);
     }
-    true // Trả về true
+    true
 }
 
 // --- HÀM CHUNG ---
@@ -579,7 +600,8 @@ pub fn show_result_window_internal(target_rect: RECT, text: String, duration_ms:
         };
         for hwnd in valid_hwnds {
             let mut r = RECT { left: 0, top: 0, right: 0, bottom: 0 };
-            if GetWindowRect(hwnd, &mut r) != 0 {
+            // Chỉ đẩy các cửa sổ chồng theo chiều ngang, khung của vùng khác đứng yên cạnh vùng đó
+            if GetWindowRect(hwnd, &mut r) != 0 && r.left < x + width && x < r.right {
                 // Đẩy lên đúng bằng chiều cao cửa sổ mới + khoảng cách
                 MoveWindow(hwnd, r.left, r.top - height - 10, r.right - r.left, r.bottom - r.top, 1);
             }
@@ -830,7 +852,7 @@ unsafe extern "system" fn result_wnd_proc(hwnd: HWND, msg: u32, wparam: WPARAM,
             // Xóa khỏi TTS Map
             let mut req_id_map = WINDOW_REQ_IDS.get_or_init(|| Mutex::new(HashMap::new()));
             if let Some(req_id) = req_id_map.lock().unwrap().remove(&hwnd_u) {
-                crate::tts::unregister_window(req_id);
+                crate::tts::unregister_window(req_id, hwnd_u);
             }
 
             { let mut list = OVERLAY_LIST.lock().unwrap(); list.retain(|&h| h != hwnd_u); }
@@ -0,0 +1,107 @@
+use image::{DynamicImage, ImageFormat, Rgba, imageops::FilterType};
+use image::codecs::jpeg::JpegEncoder;
+use std::io::Cursor;
+use crate::config::{ImageEncoding, PreprocessSettings};
+
+/// Độ sáng theo chuẩn Rec.601 (giống `to_luma8`)
+fn luma(p: &Rgba<u8>) -> u32 {
+    (p[0] as u32 * 299 + p[1] as u32 * 587 + p[2] as u32 * 114) / 1000
+}
+
+/// Xoá nền: điểm gần màu `key` được tô bằng màu tương phản nhất với phần còn lại (thường là chữ)
+fn color_key(img: DynamicImage, key: [u8; 3], tolerance: u8) -> DynamicImage {
+    let mut rgba = img.to_rgba8();
+    let near = |p: &Rgba<u8>| (0..3).all(|c| (p[c] as i16 - key[c] as i16).unsigned_abs() <= tolerance as u16);
+    let (sum, count) = rgba.pixels().filter(|p| !near(p)).fold((0u64, 0u64), |(sum, count), p| (sum + luma(p) as u64, count + 1));
+    if count == 0 { return DynamicImage::ImageRgba8(rgba); }
+    let fill = if sum / count > 127 { Rgba([0, 0, 0, 255]) } else { Rgba([255, 255, 255, 255]) };
+    for p in rgba.pixels_mut() {
+        if near(p) { *p = fill; }
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// Thu nhỏ -> xoá nền -> xám -> tương phản -> ngưỡng
+pub fn apply(mut img: DynamicImage, settings: &PreprocessSettings) -> DynamicImage {
+    if settings.max_dimension > 0 && img.width().max(img.height()) > settings.max_dimension {
+        img = img.resize(settings.max_dimension, settings.max_dimension, FilterType::Triangle);
+    }
+    if let Some(key) = settings.color_key {
+        img = color_key(img, key, settings.color_key_tolerance);
+    }
+    if settings.grayscale || settings.threshold > 0 {
+        img = DynamicImage::ImageLuma8(img.to_luma8());
+    }
+    if settings.contrast != 0.0 {
+        img = img.adjust_contrast(settings.contrast);
+    }
+    if settings.threshold > 0 {
+        let mut gray = img.to_luma8();
+        for p in gray.pixels_mut() {
+            p[0] = if p[0] >= settings.threshold { 255 } else { 0 };
+        }
+        img = DynamicImage::ImageLuma8(gray);
+    }
+    img
+}
+
+pub fn encode(img: &DynamicImage, encoding: ImageEncoding, quality: u8) -> Result<Vec<u8>, anyhow::Error> {
+    let mut buffer = Vec::new();
+    match encoding {
+        ImageEncoding::Png => img.write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)?,
+        ImageEncoding::Jpeg => {
+            // JPEG không có kênh alpha
+            let img = match img {
+                DynamicImage::ImageLuma8(_) => img.clone(),
+                _ => DynamicImage::ImageRgb8(img.to_rgb8()),
+            };
+            img.write_with_encoder(JpegEncoder::new_with_quality(&mut buffer, quality.clamp(1, 100)))?;
+        }
+        ImageEncoding::WebP => {
+            // libwebp chỉ nhận RGB/RGBA, ảnh xám đổi sang RGB
+            let memory = match img {
+                DynamicImage::ImageRgba8(rgba) => webp::Encoder::from_rgba(rgba, rgba.width(), rgba.height()).encode(quality.clamp(1, 100) as f32),
+                _ => {
+                    let rgb = img.to_rgb8();
+                    webp::Encoder::from_rgb(&rgb, rgb.width(), rgb.height()).encode(quality.clamp(1, 100) as f32)
+                }
+            };
+            buffer.extend_from_slice(&memory);
+        }
+    }
+    Ok(buffer)
+}
+
+/// Ảnh chụp -> bytes gửi đi. Tắt xử lý thì giữ PNG gốc như trước.
+/// `encoding` do bên gọi chọn (OCR trên máy luôn dùng PNG, không cần nén).
+pub fn prepare(img: DynamicImage, settings: &PreprocessSettings, encoding: ImageEncoding) -> Result<Vec<u8>, anyhow::Error> {
+    if !settings.enabled {
+        return encode(&img, ImageEncoding::Png, settings.quality);
+    }
+    encode(&apply(img, settings), encoding, settings.quality)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbImage;
+
+    #[test]
+    fn webp_quality_controls_size() {
+        // Ảnh nhiễu để bộ nén không thể giữ nguyên ở chất lượng thấp
+        let img = DynamicImage::ImageRgb8(RgbImage::from_fn(200, 120, |x, y| {
+            let v = (x.wrapping_mul(7919) ^ y.wrapping_mul(104729)).wrapping_mul(2654435761) >> 24;
+            image::Rgb([v as u8, (v * 3) as u8, (v * 7) as u8])
+        }));
+        let low = encode(&img, ImageEncoding::WebP, 20).unwrap();
+        let high = encode(&img, ImageEncoding::WebP, 95).unwrap();
+        let png = encode(&img, ImageEncoding::Png, 95).unwrap();
+        assert!(low.len() < high.len(), "{} >= {}", low.len(), high.len());
+        assert!(high.len() < png.len(), "{} >= {}", high.len(), png.len());
+        assert_eq!(image::guess_format(&low).unwrap(), ImageFormat::WebP);
+
+        // Ảnh xám vẫn nén được
+        let gray = DynamicImage::ImageLuma8(img.to_luma8());
+        assert!(!encode(&gray, ImageEncoding::WebP, 50).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,421 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::config::{Config, Region, TriggerSettings, WindowAnchor};
+use crate::glossary::{Glossary, GlossaryEntry};
+use crate::templates::TemplateLibrary;
+use crate::window::{self, Bounds};
+
+/// Vùng dùng để làm gì khi chọn
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RegionRole {
+    // Vùng dịch (thay các vùng cố định hiện có)
+    #[default]
+    Text,
+    // Vùng dò mũi tên
+    Arrow,
+}
+
+/// Toạ độ vùng theo tỉ lệ (0..1) vùng client cửa sổ, cho 1 tỉ lệ khung hình
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RegionLayout {
+    // Tỉ lệ khung hình, vd. "16:9"
+    pub aspect: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// 1 vùng đặt sẵn của game (vd. "Thoại", "Thoại có tên", "Mũi tên")
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RegionPreset {
+    pub name: String,
+    #[serde(default)]
+    pub role: RegionRole,
+    // Prompt riêng khi chọn vùng này, rỗng = prompt của profile
+    #[serde(default)]
+    pub prompt: String,
+    pub layouts: Vec<RegionLayout>,
+}
+
+/// Hồ sơ 1 game: cửa sổ, vùng đặt sẵn, mũi tên, prompt, thuật ngữ và cách kích hoạt auto.
+/// Mỗi file .json / .toml trong thư mục `profiles` là 1 profile; trùng tên với profile có sẵn thì thay nó.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct GameProfile {
+    // Tên game, dùng làm `active_game`
+    pub name: String,
+    // Tiêu đề cửa sổ (khớp chính xác), rỗng = tìm theo tiến trình
+    #[serde(default)]
+    pub window_title: String,
+    // Tên file của tiến trình, vd. "wwm.exe"
+    #[serde(default)]
+    pub process_name: String,
+    #[serde(default)]
+    pub regions: Vec<RegionPreset>,
+    // Ảnh mẫu mũi tên (PNG, đường dẫn tính từ thư mục `profiles`), chép vào thư viện mẫu khi chọn profile
+    #[serde(default)]
+    pub arrow_templates: Vec<String>,
+    // Prompt khi chọn vùng dịch, rỗng = giữ prompt hiện tại
+    #[serde(default)]
+    pub prompt: String,
+    // Thuật ngữ của game, thêm vào glossary.json khi chọn profile (không ghi đè mục đã có)
+    #[serde(default)]
+    pub glossary: Vec<GlossaryEntry>,
+    // Cách kích hoạt auto mặc định của game, None = theo cấu hình chung
+    #[serde(default)]
+    pub trigger: Option<TriggerSettings>,
+    #[serde(skip)]
+    pub builtin: bool,
+}
+
+/// "16:9" -> 1.777...
+fn parse_aspect(aspect: &str) -> Option<f32> {
+    let (w, h) = aspect.split_once(':')?;
+    let (w, h): (f32, f32) = (w.trim().parse().ok()?, h.trim().parse().ok()?);
+    if w > 0.0 && h > 0.0 { Some(w / h) } else { None }
+}
+
+impl RegionPreset {
+    /// Bố cục có tỉ lệ khung hình gần với cửa sổ nhất
+    pub fn layout_for(&self, bounds: Bounds) -> Option<&RegionLayout> {
+        let (_, _, w, h) = bounds;
+        let window_aspect = w as f32 / h.max(1) as f32;
+        self.layouts.iter()
+            .filter_map(|l| parse_aspect(&l.aspect).map(|a| (l, (a - window_aspect).abs())))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(l, _)| l)
+    }
+
+    /// Các tỉ lệ khung hình có sẵn, vd. "16:9, 21:9"
+    pub fn aspects(&self) -> String {
+        self.layouts.iter().map(|l| l.aspect.as_str()).collect::<Vec<_>>().join(", ")
+    }
+}
+
+impl GameProfile {
+    pub fn get_dir() -> PathBuf {
+        Self::dir_in(&Config::get_config_dir())
+    }
+
+    fn dir_in(dir: &Path) -> PathBuf {
+        dir.join("profiles")
+    }
+
+    /// Profile có sẵn + profile của người dùng, kèm lỗi của các file đọc không được
+    pub fn load_all() -> (Vec<Self>, Vec<String>) {
+        Self::load_all_from(&Config::get_config_dir())
+    }
+
+    /// Như `load_all` nhưng đọc thư mục `profiles` của thư mục cấu hình `dir`
+    pub fn load_all_from(dir: &Path) -> (Vec<Self>, Vec<String>) {
+        let mut profiles = vec![Self::where_winds_meet()];
+        let mut errors = Vec::new();
+        let mut files: Vec<PathBuf> = fs::read_dir(Self::dir_in(dir)).map(|entries| {
+            entries.filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| p.extension().and_then(|e| e.to_str()).map_or(false, |e| e.eq_ignore_ascii_case("json") || e.eq_ignore_ascii_case("toml")))
+                .collect()
+        }).unwrap_or_default();
+        files.sort();
+        for path in files {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("").to_string();
+            let profile = match fs::read_to_string(&path).map_err(anyhow::Error::from).and_then(|c| Self::parse(&path, &c)) {
+                Ok(profile) if !profile.name.trim().is_empty() => profile,
+                Ok(_) => { errors.push(format!("{}: thiếu \"name\"", name)); continue; }
+                Err(e) => { errors.push(format!("{}: {}", name, e)); continue; }
+            };
+            match profiles.iter().position(|p| p.name.trim().eq_ignore_ascii_case(profile.name.trim())) {
+                Some(i) => profiles[i] = profile,
+                None => profiles.push(profile),
+            }
+        }
+        (profiles, errors)
+    }
+
+    /// Đọc nội dung 1 file profile, TOML hay JSON theo đuôi file
+    fn parse(path: &Path, content: &str) -> Result<Self, anyhow::Error> {
+        if path.extension().and_then(|e| e.to_str()).map_or(false, |e| e.eq_ignore_ascii_case("toml")) {
+            Ok(toml::from_str(content)?)
+        } else {
+            Ok(serde_json::from_str(content)?)
+        }
+    }
+
+    /// Ghi profile thành `profiles/<tên>.json` (hoặc `.toml` khi `as_toml`) để sửa tay / làm mẫu cho game khác
+    pub fn export(&self, as_toml: bool) -> Result<PathBuf, anyhow::Error> {
+        let dir = Self::get_dir();
+        fs::create_dir_all(&dir)?;
+        let stem: String = self.name.chars().map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect();
+        let (path, content) = if as_toml {
+            (dir.join(format!("{}.toml", stem)), toml::to_string_pretty(self)?)
+        } else {
+            (dir.join(format!("{}.json", stem)), serde_json::to_string_pretty(self)?)
+        };
+        fs::write(&path, content)?;
+        Ok(path)
+    }
+
+    /// Vùng client của cửa sổ game lúc này
+    pub fn find_window(&self) -> Option<Bounds> {
+        window::find_bounds(&self.window_title, &self.process_name)
+    }
+
+    /// Tên cửa sổ / tiến trình để báo khi không tìm thấy game
+    pub fn window_label(&self) -> &str {
+        if self.window_title.is_empty() { &self.process_name } else { &self.window_title }
+    }
+
+    /// Vùng đặt sẵn theo vị trí cửa sổ hiện tại, gắn với cửa sổ game để đi theo khi di chuyển / đổi cỡ
+    pub fn region(&self, preset: &RegionPreset, bounds: Bounds) -> Option<Region> {
+        let layout = preset.layout_for(bounds)?;
+        let (bx, by, bw, bh) = bounds;
+        let (bw, bh) = (bw as f32, bh as f32);
+        // Bỏ phần lẻ (như các nút chọn vùng WWM trước đây) rồi mới gắn vào cửa sổ
+        let region = Region {
+            x: bx + (bw * layout.x) as i32,
+            y: by + (bh * layout.y) as i32,
+            width: (bw * layout.width) as u32,
+            height: (bh * layout.height) as u32,
+            anchor: None,
+        };
+        Some(Region { anchor: Some(WindowAnchor::from_region(&region, &self.window_title, &self.process_name, bounds)), ..region })
+    }
+
+    /// Prompt khi chọn vùng dịch `preset` (rỗng = giữ prompt hiện tại)
+    pub fn prompt_for<'a>(&'a self, preset: &'a RegionPreset) -> &'a str {
+        if preset.prompt.trim().is_empty() { &self.prompt } else { &preset.prompt }
+    }
+
+    /// Chọn game này: đặt `active_game`, cách kích hoạt auto (nếu chưa chỉnh riêng),
+    /// thêm thuật ngữ và ảnh mẫu mũi tên còn thiếu. Không tự lưu `config`.
+    /// `dir` là thư mục cấu hình (nơi có glossary.json, templates.json, `profiles`), thường là `Config::get_config_dir()`.
+    /// File nào lỗi thì bỏ qua và làm tiếp phần còn lại, trả về lỗi của từng file (rỗng = xong hết).
+    pub fn activate(&self, config: &mut Config, dir: &Path) -> Vec<String> {
+        let game = self.name.trim().to_string();
+        let mut errors = Vec::new();
+        config.active_game = game.clone();
+        if let Some(trigger) = &self.trigger {
+            config.game_triggers.entry(game.clone()).or_insert_with(|| trigger.clone());
+        }
+
+        if !self.glossary.is_empty() {
+            let mut glossary = Glossary::load_from(dir);
+            let before = glossary.entries.len();
+            for entry in &self.glossary {
+                let exists = glossary.entries.iter().any(|e| e.source.trim() == entry.source.trim() && (e.game.trim().is_empty() || e.game.trim().eq_ignore_ascii_case(&game)));
+                if !exists { glossary.entries.push(GlossaryEntry { game: game.clone(), ..entry.clone() }); }
+            }
+            if glossary.entries.len() != before {
+                if let Err(e) = glossary.save_to(dir) { errors.push(format!("glossary.json: {}", e)); }
+            }
+        }
+
+        if !self.arrow_templates.is_empty() {
+            let mut library = TemplateLibrary::load_from(dir);
+            let before = library.templates.len();
+            for file in &self.arrow_templates {
+                let path = Self::dir_in(dir).join(file);
+                let stem = Path::new(file).file_stem().and_then(|s| s.to_str()).unwrap_or("").to_string();
+                if library.templates.iter().any(|t| t.name == stem && t.game.trim().eq_ignore_ascii_case(&game)) { continue; }
+                if let Err(e) = library.add_to(dir, &path, &game) { errors.push(format!("{}: {}", file, e)); }
+            }
+            if library.templates.len() != before {
+                if let Err(e) = library.save_to(dir) { errors.push(format!("templates.json: {}", e)); }
+            }
+        }
+        errors
+    }
+
+    /// Profile có sẵn: Where Winds Meet (toạ độ đo trên màn 1920x1080)
+    fn where_winds_meet() -> Self {
+        let layout = |x: f32, y: f32, w: f32, h: f32| RegionLayout {
+            aspect: "16:9".to_string(),
+            x: x / 1920.0,
+            y: y / 1080.0,
+            width: w / 1920.0,
+            height: h / 1080.0,
+        };
+        Self {
+            name: "Where Winds Meet".to_string(),
+            window_title: "Where Winds Meet".to_string(),
+            process_name: String::new(),
+            regions: vec![
+                // Thoại thường: (423, 925) -> (1496, 1037)
+                RegionPreset { name: "vùng dịch".to_string(), role: RegionRole::Text, prompt: String::new(), layouts: vec![layout(423.0, 925.0, 1073.0, 112.0)] },
+                // Thoại có tên người nói: (423, 868) -> (1496, 1037)
+                RegionPreset { name: "vùng dịch (có tên người thoại)".to_string(), role: RegionRole::Text, prompt: Config::get_wuxia_speaker_prompt(), layouts: vec![layout(423.0, 868.0, 1073.0, 169.0)] },
+                // Mũi tên: (937, 1049) -> (979, 1079)
+                RegionPreset { name: "vùng Mũi tên".to_string(), role: RegionRole::Arrow, prompt: String::new(), layouts: vec![layout(937.0, 1049.0, 42.0, 30.0)] },
+            ],
+            arrow_templates: Vec::new(),
+            prompt: Config::get_wuxia_prompt(),
+            glossary: Vec::new(),
+            trigger: None,
+            builtin: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TriggerMode;
+
+    fn layout(aspect: &str, x: f32, y: f32, width: f32, height: f32) -> RegionLayout {
+        RegionLayout { aspect: aspect.to_string(), x, y, width, height }
+    }
+
+    fn preset(layouts: Vec<RegionLayout>) -> RegionPreset {
+        RegionPreset { name: "vùng dịch".to_string(), role: RegionRole::Text, prompt: String::new(), layouts }
+    }
+
+    fn profile() -> GameProfile {
+        GameProfile { name: "Test".to_string(), window_title: "Test Game".to_string(), ..GameProfile::default() }
+    }
+
+    fn rect(region: &Region) -> (i32, i32, u32, u32) {
+        (region.x, region.y, region.width, region.height)
+    }
+
+    #[test]
+    fn picks_layout_by_aspect() {
+        let preset = preset(vec![layout("16:9", 0.25, 0.8, 0.5, 0.1), layout("21:9", 0.3, 0.85, 0.4, 0.1)]);
+        let profile = profile();
+
+        let region = profile.region(&preset, (100, 50, 1920, 1080)).unwrap();
+        assert_eq!(rect(&region), (100 + 480, 50 + 864, 960, 108));
+        let region = profile.region(&preset, (0, 0, 2560, 1080)).unwrap();
+        assert_eq!(rect(&region), (768, 918, 1024, 108));
+        assert_eq!(region.anchor.as_ref().map(|a| a.window_title.as_str()), Some("Test Game"));
+
+        // Không có bố cục đúng tỉ lệ: dùng cái gần nhất (4:3 gần 16:9 hơn 21:9)
+        assert_eq!(preset.layout_for((0, 0, 1024, 768)).map(|l| l.aspect.as_str()), Some("16:9"));
+        assert_eq!(preset.layout_for((0, 0, 3840, 1080)).map(|l| l.aspect.as_str()), Some("21:9"));
+        let region = profile.region(&preset, (0, 0, 1024, 768)).unwrap();
+        assert_eq!(rect(&region), (256, 614, 512, 76));
+    }
+
+    #[test]
+    fn ignores_unparsable_aspects() {
+        let profile = profile();
+        assert!(profile.region(&preset(vec![layout("wide", 0.0, 0.0, 1.0, 1.0)]), (0, 0, 1920, 1080)).is_none());
+        assert!(profile.region(&preset(Vec::new()), (0, 0, 1920, 1080)).is_none());
+        let preset = preset(vec![layout("0:9", 0.0, 0.0, 0.5, 0.5), layout("16:10", 0.1, 0.1, 0.5, 0.5)]);
+        assert_eq!(preset.layout_for((0, 0, 1920, 1080)).map(|l| l.aspect.as_str()), Some("16:10"));
+    }
+
+    #[test]
+    fn activate_keeps_going_past_missing_templates() {
+        // Thư mục cấu hình tạm, không đụng tới cấu hình thật
+        let dir = std::env::temp_dir().join(format!("screen_translator_profile_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(GameProfile::dir_in(&dir)).unwrap();
+        fs::write(GameProfile::dir_in(&dir).join("arrow.png"), include_bytes!("arrow.png")).unwrap();
+
+        let profile = GameProfile {
+            arrow_templates: vec!["missing.png".to_string(), "arrow.png".to_string()],
+            glossary: vec![GlossaryEntry { source: "江湖".to_string(), target: "giang hồ".to_string(), notes: String::new(), game: String::new() }],
+            trigger: Some(TriggerSettings::default()),
+            ..profile()
+        };
+        let mut config = Config::default();
+        let errors = profile.activate(&mut config, &dir);
+
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].starts_with("missing.png: "), "{:?}", errors);
+        // Phần còn lại vẫn làm xong và đã lưu
+        assert_eq!(config.active_game, "Test");
+        assert!(config.game_triggers.contains_key("Test"));
+        let library = TemplateLibrary::load_from(&dir);
+        assert_eq!(library.templates.iter().map(|t| (t.name.as_str(), t.game.as_str())).collect::<Vec<_>>(), [("arrow", "Test")]);
+        let glossary = Glossary::load_from(&dir);
+        assert_eq!(glossary.entries.len(), 1);
+        assert_eq!(glossary.entries[0].game, "Test");
+
+        // Chọn lại: mẫu đã có thì không thêm nữa, chỉ còn lỗi của file thiếu
+        assert_eq!(profile.activate(&mut config, &dir).len(), 1);
+        assert_eq!(TemplateLibrary::load_from(&dir).templates.len(), 1);
+        assert!(dir.join("arrows").join("arrow.png").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn loads_json_and_toml_profiles() {
+        let dir = std::env::temp_dir().join(format!("screen_translator_profiles_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let profiles_dir = GameProfile::dir_in(&dir);
+        fs::create_dir_all(&profiles_dir).unwrap();
+        fs::write(profiles_dir.join("a.json"), r#"{"name": "Json Game", "process_name": "json.exe"}"#).unwrap();
+        fs::write(profiles_dir.join("b.toml"), r#"
+name = "Toml Game"
+process_name = "toml.exe"
+arrow_templates = ["arrow.png"]
+
+[trigger]
+mode = "TextStable"
+
+[[regions]]
+name = "Thoại"
+
+[[regions.layouts]]
+aspect = "16:9"
+x = 0.2
+y = 0.8
+width = 0.6
+height = 0.15
+
+[[glossary]]
+source = "江湖"
+target = "giang hồ"
+"#).unwrap();
+        fs::write(profiles_dir.join("c.toml"), "name = ").unwrap();
+        // Ghi đè profile có sẵn theo tên
+        fs::write(profiles_dir.join("d.TOML"), "name = \"where winds meet\"\nprocess_name = \"wwm.exe\"\n").unwrap();
+
+        let (profiles, errors) = GameProfile::load_all_from(&dir);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].starts_with("c.toml: "), "{:?}", errors);
+        assert_eq!(profiles.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(), ["where winds meet", "Json Game", "Toml Game"]);
+        assert_eq!(profiles[0].process_name, "wwm.exe");
+
+        let toml = &profiles[2];
+        assert_eq!(toml.trigger.as_ref().map(|t| t.mode), Some(TriggerMode::TextStable));
+        assert_eq!(toml.regions[0].role, RegionRole::Text);
+        assert_eq!(toml.regions[0].layouts[0].width, 0.6);
+        assert_eq!(toml.glossary[0].target, "giang hồ");
+        assert_eq!(toml.arrow_templates, ["arrow.png"]);
+
+        // Xuất TOML đọc lại được như cũ
+        let wwm = GameProfile::where_winds_meet();
+        let parsed = GameProfile::parse(Path::new("wwm.toml"), &toml::to_string_pretty(&wwm).unwrap()).unwrap();
+        assert_eq!(parsed.regions, wwm.regions);
+        assert_eq!(parsed.prompt, wwm.prompt);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn where_winds_meet_matches_old_regions() {
+        // Công thức của các nút "Tự động chọn vùng ... WWM" trước khi có profile
+        let old = |bounds: Bounds, (x, y, w, h): (f32, f32, f32, f32)| {
+            let (win_x, win_y, win_w, win_h) = bounds;
+            let (f_w, f_h) = (win_w as f32, win_h as f32);
+            (win_x + (f_w * (x / 1920.0)) as i32, win_y + (f_h * (y / 1080.0)) as i32, (f_w * (w / 1920.0)) as u32, (f_h * (h / 1080.0)) as u32)
+        };
+        let wwm = GameProfile::where_winds_meet();
+        let expected = [(423.0, 925.0, 1073.0, 112.0), (423.0, 868.0, 1073.0, 169.0), (937.0, 1049.0, 42.0, 30.0)];
+        assert_eq!(wwm.regions.len(), expected.len());
+        assert_eq!(wwm.regions.iter().map(|r| r.role).collect::<Vec<_>>(), [RegionRole::Text, RegionRole::Text, RegionRole::Arrow]);
+
+        for bounds in [(0, 0, 1920, 1080), (0, 0, 2560, 1440), (-1366, 120, 1366, 768), (8, 31, 1600, 900), (0, 0, 1280, 720)] {
+            for (preset, coords) in wwm.regions.iter().zip(expected) {
+                let region = wwm.region(preset, bounds).unwrap();
+                assert_eq!(rect(&region), old(bounds, coords), "{} at {:?}", preset.name, bounds);
+                // Lúc chụp vùng được tính lại từ anchor: phải ra đúng chỗ đó
+                let anchor = region.anchor.as_ref().unwrap();
+                assert_eq!(anchor.window_title, "Where Winds Meet");
+                assert_eq!(rect(&anchor.place(bounds)), rect(&region));
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use crate::config::CustomPrompt;
+
+/// Biến dùng được trong prompt mẫu (hiện trong UI làm gợi ý)
+pub const VARIABLES: &[(&str, &str)] = &[
+    ("{target_lang}", "Ngôn ngữ đích"),
+    ("{source_lang}", "Ngôn ngữ gốc"),
+    ("{game}", "Game đang chơi"),
+    ("{glossary}", "Thuật ngữ liên quan"),
+    ("{previous_lines}", "Các câu thoại trước"),
+    ("{speaker}", "Người nói ở câu trước"),
+];
+
+/// Giá trị thay vào prompt cho một lần dịch
+#[derive(Default)]
+pub struct PromptVars<'a> {
+    pub target_lang: &'a str,
+    pub source_lang: &'a str,
+    pub game: &'a str,
+    pub glossary: &'a str,
+    pub previous_lines: &'a str,
+    pub speaker: &'a str,
+}
+
+pub fn uses(template: &str, variable: &str) -> bool {
+    template.contains(variable)
+}
+
+pub fn render(template: &str, vars: &PromptVars) -> String {
+    template
+        .replace("{target_lang}", vars.target_lang)
+        .replace("{source_lang}", vars.source_lang)
+        .replace("{game}", vars.game)
+        .replace("{glossary}", vars.glossary)
+        .replace("{previous_lines}", vars.previous_lines)
+        .replace("{speaker}", vars.speaker)
+}
+
+/// File chia sẻ bộ prompt giữa mọi người
+#[derive(Serialize, Deserialize, Default)]
+pub struct PromptLibrary {
+    pub prompts: Vec<CustomPrompt>,
+}
+
+pub fn export_library(prompts: &[CustomPrompt], path: &Path) -> Result<(), anyhow::Error> {
+    let library = PromptLibrary { prompts: prompts.to_vec() };
+    fs::write(path, serde_json::to_string_pretty(&library)?)?;
+    Ok(())
+}
+
+/// Gộp bộ prompt từ file vào danh sách hiện có (trùng tên thì ghi đè). Trả về số prompt đã nhập.
+pub fn import_library(prompts: &mut Vec<CustomPrompt>, path: &Path) -> Result<usize, anyhow::Error> {
+    let content = fs::read_to_string(path)?;
+    // Chấp nhận cả {"prompts": [...]} lẫn mảng trần
+    let imported = match serde_json::from_str::<PromptLibrary>(&content) {
+        Ok(library) => library.prompts,
+        Err(_) => serde_json::from_str::<Vec<CustomPrompt>>(&content)?,
+    };
+    let count = imported.len();
+    for prompt in imported {
+        match prompts.iter_mut().find(|p| !p.name.is_empty() && p.name == prompt.name) {
+            Some(existing) => *existing = prompt,
+            None => prompts.push(prompt),
+        }
+    }
+    Ok(count)
+}
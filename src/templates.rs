@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use image::{Rgba, RgbaImage};
+use crate::config::{Config, MatchSettings};
+
+// Tăng mỗi lần lưu thư viện, để thread auto biết cần nạp lại mẫu
+static REVISION: AtomicU64 = AtomicU64::new(0);
+
+/// 1 ảnh mẫu mũi tên (mỗi skin / kiểu hộp thoại sáng tối 1 ảnh)
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ArrowTemplate {
+    pub name: String,
+    // Tên file PNG trong thư mục `arrows`
+    pub file: String,
+    // Tên game áp dụng (rỗng = mọi game)
+    #[serde(default)]
+    pub game: String,
+    // Ngưỡng riêng, None = theo cấu hình chung
+    #[serde(default)]
+    pub threshold: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TemplateLibrary {
+    pub templates: Vec<ArrowTemplate>,
+}
+
+impl ArrowTemplate {
+    pub fn applies_to(&self, game: &str) -> bool {
+        self.game.trim().is_empty() || self.game.trim().eq_ignore_ascii_case(game.trim())
+    }
+
+    pub fn path(&self) -> PathBuf {
+        TemplateLibrary::get_dir().join(&self.file)
+    }
+
+    pub fn read(&self) -> Option<Vec<u8>> {
+        fs::read(self.path()).ok()
+    }
+
+    /// Cấu hình dò của mẫu này (ghi đè ngưỡng nếu có)
+    pub fn match_settings(&self, base: &MatchSettings) -> MatchSettings {
+        MatchSettings { threshold: self.threshold.unwrap_or(base.threshold), ..base.clone() }
+    }
+}
+
+impl TemplateLibrary {
+    pub fn get_path() -> PathBuf {
+        Self::path_in(&Config::get_config_dir())
+    }
+
+    pub fn get_dir() -> PathBuf {
+        Self::arrows_in(&Config::get_config_dir())
+    }
+
+    fn path_in(dir: &Path) -> PathBuf {
+        dir.join("templates.json")
+    }
+
+    fn arrows_in(dir: &Path) -> PathBuf {
+        dir.join("arrows")
+    }
+
+    pub fn revision() -> u64 {
+        REVISION.load(Ordering::Relaxed)
+    }
+
+    pub fn load() -> Self {
+        match fs::read_to_string(Self::get_path()) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+            Err(_) => Self::migrate_custom_arrow(),
+        }
+    }
+
+    /// Đọc thư viện trong thư mục cấu hình `dir` (không chuyển ảnh `custom_arrow.png` của bản cũ)
+    pub fn load_from(dir: &Path) -> Self {
+        fs::read_to_string(Self::path_in(dir)).ok().and_then(|c| serde_json::from_str(&c).ok()).unwrap_or_default()
+    }
+
+    /// Bản cũ chỉ có 1 ảnh `custom_arrow.png`: chuyển thành mục đầu tiên của thư viện
+    fn migrate_custom_arrow() -> Self {
+        let mut library = Self::default();
+        let custom_path = Config::get_custom_arrow_path();
+        if custom_path.exists() && library.add(&custom_path, "").is_ok() {
+            if let Some(template) = library.templates.last_mut() { template.name = "Mũi tên tuỳ chỉnh".to_string(); }
+            if library.save().is_ok() { let _ = fs::remove_file(custom_path); }
+        }
+        library
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.save_to(&Config::get_config_dir())
+    }
+
+    pub fn save_to(&self, dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let path = Self::path_in(dir);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        REVISION.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Tên file chưa dùng trong thư mục ảnh mẫu `dir` (thêm _2, _3... nếu trùng)
+    fn unique_file(dir: &Path, stem: &str) -> Result<String, anyhow::Error> {
+        fs::create_dir_all(dir)?;
+        let stem: String = stem.chars().map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect();
+        let stem = if stem.is_empty() { "arrow".to_string() } else { stem };
+        let mut file = format!("{}.png", stem);
+        let mut n = 1;
+        while dir.join(&file).exists() {
+            n += 1;
+            file = format!("{}_{}.png", stem, n);
+        }
+        Ok(file)
+    }
+
+    /// Chép ảnh vào thư mục `arrows` và thêm vào thư viện
+    pub fn add(&mut self, source: &Path, game: &str) -> Result<(), anyhow::Error> {
+        self.add_to(&Config::get_config_dir(), source, game)
+    }
+
+    /// Như `add` nhưng chép vào thư mục `arrows` của thư mục cấu hình `dir`
+    pub fn add_to(&mut self, dir: &Path, source: &Path, game: &str) -> Result<(), anyhow::Error> {
+        // Kiểm tra đúng là ảnh trước khi chép
+        let bytes = fs::read(source)?;
+        image::load_from_memory(&bytes)?;
+
+        let stem = source.file_stem().and_then(|s| s.to_str()).unwrap_or("arrow").to_string();
+        let arrows = Self::arrows_in(dir);
+        let file = Self::unique_file(&arrows, &stem)?;
+        fs::write(arrows.join(&file), &bytes)?;
+        self.templates.push(ArrowTemplate { name: stem, file, game: game.trim().to_string(), threshold: None });
+        Ok(())
+    }
+
+    /// Lưu ảnh mẫu vừa tạo (chụp từ màn hình) thành PNG trong thư viện
+    pub fn add_image(&mut self, img: &RgbaImage, name: &str, game: &str) -> Result<&ArrowTemplate, anyhow::Error> {
+        let name = if name.trim().is_empty() { "arrow" } else { name.trim() };
+        let dir = Self::get_dir();
+        let file = Self::unique_file(&dir, name)?;
+        img.save_with_format(dir.join(&file), image::ImageFormat::Png)?;
+        self.templates.push(ArrowTemplate { name: name.to_string(), file, game: game.trim().to_string(), threshold: None });
+        Ok(self.templates.last().unwrap())
+    }
+
+    /// Xoá mục và file ảnh của nó
+    pub fn remove(&mut self, index: usize) {
+        if index >= self.templates.len() { return; }
+        let template = self.templates.remove(index);
+        let _ = fs::remove_file(template.path());
+    }
+
+    /// Các mẫu dùng cho game đang chơi
+    pub fn for_game(&self, game: &str) -> Vec<&ArrowTemplate> {
+        self.templates.iter().filter(|t| t.applies_to(game)).collect()
+    }
+}
+
+/// Làm sạch ảnh vừa khoanh trên màn hình: màu nền lấy theo viền ảnh, `auto_crop` cắt sát phần khác nền,
+/// `transparent` cho nền trong suốt (điểm lệch màu nền không quá `tolerance` mỗi kênh).
+pub fn clean_capture(img: &RgbaImage, auto_crop: bool, transparent: bool, tolerance: u8) -> RgbaImage {
+    let (w, h) = img.dimensions();
+    if w == 0 || h == 0 { return img.clone(); }
+
+    // Màu nền = trung bình các điểm trên viền
+    let border: Vec<&Rgba<u8>> = img.enumerate_pixels().filter(|(x, y, _)| *x == 0 || *y == 0 || *x == w - 1 || *y == h - 1).map(|(_, _, p)| p).collect();
+    let background: [u8; 3] = std::array::from_fn(|c| (border.iter().map(|p| p[c] as u32).sum::<u32>() / border.len() as u32) as u8);
+    let is_background = |p: &Rgba<u8>| (0..3).all(|c| (p[c] as i16 - background[c] as i16).unsigned_abs() <= tolerance as u16);
+
+    let mut out = img.clone();
+    if auto_crop {
+        let shape: Vec<(u32, u32)> = img.enumerate_pixels().filter(|(_, _, p)| !is_background(p)).map(|(x, y, _)| (x, y)).collect();
+        if !shape.is_empty() {
+            // Chừa 1 điểm nền quanh hình để NCC còn thấy mép
+            let left = shape.iter().map(|p| p.0).min().unwrap().saturating_sub(1);
+            let top = shape.iter().map(|p| p.1).min().unwrap().saturating_sub(1);
+            let right = (shape.iter().map(|p| p.0).max().unwrap() + 1).min(w - 1);
+            let bottom = (shape.iter().map(|p| p.1).max().unwrap() + 1).min(h - 1);
+            out = image::imageops::crop_imm(img, left, top, right - left + 1, bottom - top + 1).to_image();
+        }
+    }
+    if transparent {
+        for p in out.pixels_mut() {
+            if is_background(p) { p[3] = 0; }
+        }
+    }
+    out
+}
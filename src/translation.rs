@@ -2,114 +2,1092 @@ use reqwest;
 use serde::{Deserialize, Serialize};
 use base64::{Engine as _, engine::general_purpose};
 use anyhow::Result;
+use futures::future::BoxFuture;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::SystemTime;
+use crate::config::Config;
+use crate::cache;
+use crate::http;
+use crate::context::{self, DialogueLine};
 
 // --- STRUCTURES ---
 #[derive(Serialize)]
-struct GeminiRequest { contents: Vec<Content> }
+struct GeminiRequest {
+    contents: Vec<Content>,
+    #[serde(rename = "generationConfig", skip_serializing_if = "Option::is_none")]
+    generation_config: Option<serde_json::Value>,
+}
 #[derive(Serialize)]
 struct Content { parts: Vec<Part> }
 #[derive(Serialize)]
 struct Part { text: Option<String>, inline_data: Option<InlineData> }
 #[derive(Serialize)]
 struct InlineData { mime_type: String, data: String }
+// Bị chặn (an toàn, MAX_TOKENS...) thì vẫn là 200 nhưng không có candidate / không có parts
 #[derive(Deserialize)]
-struct GeminiResponse { candidates: Vec<Candidate> }
+#[serde(rename_all = "camelCase")]
+struct GeminiResponse { #[serde(default)] candidates: Vec<Candidate>, prompt_feedback: Option<PromptFeedback> }
 #[derive(Deserialize)]
-struct Candidate { content: ContentResponse }
+#[serde(rename_all = "camelCase")]
+struct PromptFeedback { block_reason: Option<String> }
 #[derive(Deserialize)]
-struct ContentResponse { parts: Vec<PartResponse> }
+#[serde(rename_all = "camelCase")]
+struct Candidate { #[serde(default)] content: ContentResponse, finish_reason: Option<String> }
+#[derive(Deserialize, Default)]
+struct ContentResponse { #[serde(default)] parts: Vec<PartResponse> }
 #[derive(Deserialize)]
-struct PartResponse { text: String }
+struct PartResponse { #[serde(default)] text: String }
+
+impl GeminiResponse {
+    /// Text của phần đầu tiên, hoặc lỗi kèm lý do Gemini không trả kết quả
+    fn into_text(self) -> Result<String, TranslationError> {
+        let reason = self.candidates.first().and_then(|c| c.finish_reason.clone())
+            .or_else(|| self.prompt_feedback.as_ref().and_then(|f| f.block_reason.clone()));
+        match self.candidates.into_iter().next().and_then(|c| c.content.parts.into_iter().next()) {
+            Some(part) if !part.text.is_empty() => Ok(part.text),
+            _ => Err(gemini_empty(reason.as_deref())),
+        }
+    }
+}
+
+fn gemini_empty(reason: Option<&str>) -> TranslationError {
+    TranslationError::Other(anyhow::anyhow!("Gemini không trả kết quả (finishReason: {})", reason.unwrap_or("không rõ")))
+}
 
 pub struct TranslationResult {
-    pub text: String,
+    pub translated_text: String,
+    pub original_text: Option<String>,
+    pub speaker: Option<String>,
+    pub detected_language: Option<String>,
     pub remaining_requests: Option<i32>,
+    // Lấy từ cache, không tốn request nào
+    pub from_cache: bool,
+}
+
+/// Dạng JSON mà model được yêu cầu trả về khi bật `structured`
+#[derive(Deserialize)]
+struct StructuredOutput {
+    #[serde(default)]
+    original_text: String,
+    #[serde(default)]
+    translated_text: String,
+    #[serde(default)]
+    speaker: Option<String>,
+    #[serde(default)]
+    detected_language: Option<String>,
+}
+
+/// Kết quả khi gộp nhiều vùng vào 1 request: mỗi ảnh một phần tử, đúng thứ tự
+#[derive(Deserialize)]
+struct BatchOutput {
+    regions: Vec<StructuredOutput>,
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+impl TranslationResult {
+    pub fn plain(text: String, remaining_requests: Option<i32>) -> Self {
+        Self { translated_text: text, original_text: None, speaker: None, detected_language: None, remaining_requests, from_cache: false }
+    }
+
+    fn from_structured(out: StructuredOutput, remaining_requests: Option<i32>) -> Self {
+        Self {
+            translated_text: out.translated_text.trim().to_string(),
+            original_text: non_empty(Some(out.original_text)),
+            speaker: non_empty(out.speaker),
+            detected_language: non_empty(out.detected_language),
+            remaining_requests,
+            from_cache: false,
+        }
+    }
+
+    /// Đọc câu trả lời của model. Nếu model không trả JSON đúng dạng thì coi cả câu trả lời là bản dịch.
+    fn from_response(raw: &str, structured: bool, remaining_requests: Option<i32>) -> Self {
+        let raw = raw.trim();
+        if structured {
+            if let Ok(out) = serde_json::from_str::<StructuredOutput>(strip_code_fence(raw)) {
+                return Self::from_structured(out, remaining_requests);
+            }
+        }
+        Self::plain(raw.to_string(), remaining_requests)
+    }
+
+    /// Đọc câu trả lời gộp nhiều vùng. Sai dạng hoặc sai số lượng thì báo lỗi để bên gọi dịch lại từng vùng.
+    fn batch_from_response(raw: &str, count: usize, remaining_requests: Option<i32>) -> Result<Vec<Self>, TranslationError> {
+        let json = strip_code_fence(raw.trim());
+        // Chấp nhận cả {"regions": [...]} lẫn mảng trần
+        let regions = match serde_json::from_str::<BatchOutput>(json) {
+            Ok(out) => out.regions,
+            Err(_) => serde_json::from_str::<Vec<StructuredOutput>>(json).map_err(|e| TranslationError::Other(anyhow::anyhow!("Kết quả gộp vùng không đúng dạng: {}", e)))?,
+        };
+        if regions.len() != count {
+            return Err(TranslationError::Other(anyhow::anyhow!("Model trả {} kết quả cho {} vùng", regions.len(), count)));
+        }
+        Ok(regions.into_iter().map(|out| Self::from_structured(out, remaining_requests)).collect())
+    }
+
+    /// Text hiển thị trên overlay và đọc TTS (kèm tên người nói nếu có)
+    pub fn display_text(&self) -> String {
+        match &self.speaker {
+            Some(speaker) => format!("{}: {}", speaker, self.translated_text),
+            None => self.translated_text.clone(),
+        }
+    }
+}
+
+/// Lấy giá trị chuỗi của `key` trong JSON đang stream dở (chuỗi có thể chưa có dấu đóng)
+fn partial_json_string(raw: &str, key: &str) -> Option<String> {
+    let pattern = format!("\"{}\"", key);
+    let start = raw.find(&pattern)? + pattern.len();
+    let rest = raw[start..].trim_start().strip_prefix(':')?.trim_start().strip_prefix('"')?;
+    let mut out = String::new();
+    let mut chars = rest.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => break,
+            '\\' => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('r') => {},
+                Some('u') => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    if let Some(ch) = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) { out.push(ch); }
+                },
+                Some(other) => out.push(other),
+                None => break,
+            },
+            c => out.push(c),
+        }
+    }
+    Some(out)
+}
+
+/// Bản dịch tạm để hiện khi đang stream: với JSON thì chỉ lấy phần `translated_text` đã về
+fn partial_text(raw: &str, structured: bool) -> Option<String> {
+    let text = if structured { partial_json_string(raw, "translated_text")? } else { raw.to_string() };
+    let text = text.trim();
+    if text.is_empty() { None } else { Some(text.to_string()) }
+}
+
+// Một số model vẫn bọc JSON trong ```json ... ```
+fn strip_code_fence(raw: &str) -> &str {
+    raw.trim_start_matches("```json").trim_start_matches("```").trim_end_matches("```").trim()
+}
+
+const STRUCTURED_INSTRUCTION: &str = "Respond ONLY with a JSON object with these fields: \"original_text\" (the raw text extracted from the image, exactly as it appears), \"translated_text\" (the result of the instructions above), \"speaker\" (the character name if the text starts with a speaker name, otherwise empty) and \"detected_language\" (the language of the original text).";
+
+const BATCH_INSTRUCTION: &str = "Respond ONLY with a JSON object {\"regions\": [...]} where \"regions\" has exactly one item per image, in the same order as the images. Each item has these fields: \"original_text\" (the raw text extracted from that image, exactly as it appears), \"translated_text\" (the result of the instructions above for that image), \"speaker\" (the character name if the text starts with a speaker name, otherwise empty) and \"detected_language\" (the language of the original text).";
+
+/// JSON schema cho OpenAI `response_format` và Ollama `format`
+fn structured_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            "original_text": { "type": "string" },
+            "translated_text": { "type": "string" },
+            "speaker": { "type": "string" },
+            "detected_language": { "type": "string" }
+        },
+        "required": ["original_text", "translated_text"]
+    })
+}
+
+fn batch_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "object",
+        "properties": { "regions": { "type": "array", "items": structured_schema() } },
+        "required": ["regions"]
+    })
+}
+
+/// Gemini dùng tập con OpenAPI (kiểu viết hoa) cho `responseSchema`
+fn gemini_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "OBJECT",
+        "properties": {
+            "original_text": { "type": "STRING" },
+            "translated_text": { "type": "STRING" },
+            "speaker": { "type": "STRING", "nullable": true },
+            "detected_language": { "type": "STRING", "nullable": true }
+        },
+        "required": ["original_text", "translated_text"],
+        "propertyOrdering": ["original_text", "translated_text", "speaker", "detected_language"]
+    })
+}
+
+fn gemini_structured_config() -> serde_json::Value {
+    serde_json::json!({ "responseMimeType": "application/json", "responseSchema": gemini_schema() })
+}
+
+fn gemini_batch_config() -> serde_json::Value {
+    serde_json::json!({
+        "responseMimeType": "application/json",
+        "responseSchema": {
+            "type": "OBJECT",
+            "properties": { "regions": { "type": "ARRAY", "items": gemini_schema() } },
+            "required": ["regions"]
+        }
+    })
 }
 
 #[derive(Debug)]
 pub enum TranslationError {
     RateLimitExceeded,
+    // Không kết nối được tới server (thường là server local chưa chạy)
+    Unreachable(String),
+    // Server chạy nhưng chưa có model được chọn
+    ModelNotFound(String),
+    // Provider không hỗ trợ gửi nhiều ảnh trong 1 request
+    BatchUnsupported(String),
     Other(anyhow::Error),
 }
 
-// --- GEMINI ---
-pub async fn translate_with_gemini_image(api_key: &str, prompt: &str, image_bytes: &[u8]) -> Result<TranslationResult, TranslationError> {
-    let client = reqwest::Client::new();
+impl std::fmt::Display for TranslationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TranslationError::RateLimitExceeded => write!(f, "Hết lượt Request"),
+            TranslationError::Unreachable(url) => write!(f, "Không kết nối được tới {} (server đã chạy chưa?)", url),
+            TranslationError::ModelNotFound(model) => write!(f, "Chưa có model '{}' (hãy chạy: ollama pull {})", model, model),
+            TranslationError::BatchUnsupported(name) => write!(f, "{} không hỗ trợ dịch gộp nhiều vùng", name),
+            TranslationError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+// --- PROVIDER TRAIT ---
+
+/// Dữ liệu của một lần gọi dịch. Ảnh là tùy chọn để provider chỉ hỗ trợ text cũng dùng được.
+pub struct TranslationRequest<'a> {
+    pub api_key: &'a str,
+    pub prompt: &'a str,
+    pub image_bytes: Option<&'a [u8]>,
+    // Text đã được OCR sẵn trên máy (chế độ OCR -> dịch text)
+    pub source_text: Option<&'a str>,
+    // Yêu cầu model trả JSON có cấu trúc (xem TranslationResult)
+    pub structured: bool,
+    // Các câu thoại trước đó của cùng vùng (cũ trước mới sau)
+    pub history: &'a [DialogueLine],
+}
+
+impl TranslationRequest<'_> {
+    /// Prompt gửi cho LLM, kèm ngữ cảnh hội thoại và text đã OCR nếu có
+    pub fn full_prompt(&self) -> String {
+        self.build_prompt(true)
+    }
+
+    fn build_prompt(&self, with_history: bool) -> String {
+        let mut prompt = self.prompt.to_string();
+        if with_history && !self.history.is_empty() {
+            prompt = format!("{}\n\nPrevious dialogue lines of this scene, for context only (keep names, pronouns and tone consistent with them, do NOT translate them again):\n{}", prompt, context::format_lines(self.history));
+        }
+        if let Some(text) = self.source_text {
+            prompt = format!("{}\n\nThe text has already been extracted from the image by OCR (it may contain OCR mistakes):\n{}", prompt, text);
+        }
+        if self.structured {
+            prompt = format!("{}\n\n{}", prompt, STRUCTURED_INSTRUCTION);
+        }
+        prompt
+    }
+
+    /// Với API dạng chat: gửi ngữ cảnh thành các lượt user/assistant trước đó thay vì nhét vào prompt
+    fn history_messages(&self) -> Vec<serde_json::Value> {
+        let mut messages = Vec::new();
+        for line in self.history {
+            let answer = if self.structured {
+                serde_json::json!({ "original_text": line.original, "translated_text": line.translated }).to_string()
+            } else {
+                line.translated.clone()
+            };
+            messages.push(serde_json::json!({ "role": "user", "content": line.original }));
+            messages.push(serde_json::json!({ "role": "assistant", "content": answer }));
+        }
+        messages
+    }
+}
+
+/// Nhiều vùng gửi trong 1 request: ảnh đánh số theo thứ tự vùng
+pub struct BatchRequest<'a> {
+    pub api_key: &'a str,
+    pub prompt: &'a str,
+    pub images: &'a [&'a [u8]],
+    // Ngữ cảnh hội thoại riêng của từng vùng, cùng thứ tự với `images`
+    pub histories: &'a [Vec<DialogueLine>],
+}
+
+impl BatchRequest<'_> {
+    pub fn full_prompt(&self) -> String {
+        let mut prompt = self.prompt.to_string();
+        for (i, history) in self.histories.iter().enumerate().filter(|(_, h)| !h.is_empty()) {
+            prompt = format!("{}\n\nPrevious dialogue lines of image {}, for context only (do NOT translate them again):\n{}", prompt, i + 1, context::format_lines(history));
+        }
+        format!("{}\n\nYou are given {} images. Translate each image separately.\n{}", prompt, self.images.len(), BATCH_INSTRUCTION)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ProviderCapabilities {
+    pub vision: bool,
+    pub text: bool,
+    pub needs_api_key: bool,
+    // Có ô nhập key hay không (key tuỳ chọn vẫn tính), false = chạy trên máy
+    pub uses_api_key: bool,
+}
+
+/// Giới hạn của gói miễn phí (None = không rõ / không giới hạn).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RateLimitInfo {
+    pub requests_per_minute: Option<u32>,
+    pub requests_per_day: Option<u32>,
+    // Provider có trả về số lượt còn lại qua header hay không
+    pub reports_remaining: bool,
+}
+
+/// Nhận bản dịch tạm (toàn bộ phần đã về, không phải phần mới) khi đang stream
+pub type PartialFn<'a> = dyn Fn(&str) + Send + Sync + 'a;
+
+pub trait TranslationProvider: Send + Sync {
+    fn id(&self) -> &'static str;
+    fn name(&self) -> &'static str;
+    fn capabilities(&self) -> ProviderCapabilities;
+    fn rate_limit(&self) -> RateLimitInfo;
+    fn translate<'a>(&'a self, request: TranslationRequest<'a>) -> BoxFuture<'a, Result<TranslationResult, TranslationError>>;
+
+    /// Như `translate` nhưng gọi `on_partial` mỗi khi có thêm chữ. Mặc định là không stream (trả 1 lần).
+    fn translate_stream<'a>(&'a self, request: TranslationRequest<'a>, on_partial: &'a PartialFn<'a>) -> BoxFuture<'a, Result<TranslationResult, TranslationError>> {
+        let _ = on_partial;
+        self.translate(request)
+    }
+
+    /// Dịch nhiều ảnh trong 1 request, kết quả đúng thứ tự ảnh. Mặc định là không hỗ trợ.
+    fn translate_batch<'a>(&'a self, _request: BatchRequest<'a>) -> BoxFuture<'a, Result<Vec<TranslationResult>, TranslationError>> {
+        Box::pin(async move { Err(TranslationError::BatchUnsupported(self.name().to_string())) })
+    }
+}
+
+/// Ảnh có thể là PNG / JPEG / WebP tuỳ cài đặt xử lý ảnh
+fn image_mime(image_bytes: &[u8]) -> &'static str {
+    image::guess_format(image_bytes).map(|f| f.to_mime_type()).unwrap_or("image/png")
+}
+
+fn gemini_image_part(image_bytes: &[u8]) -> Part {
+    Part { text: None, inline_data: Some(InlineData { mime_type: image_mime(image_bytes).to_string(), data: general_purpose::STANDARD.encode(image_bytes) }) }
+}
+
+fn openai_image_part(image_bytes: &[u8]) -> serde_json::Value {
     let b64 = general_purpose::STANDARD.encode(image_bytes);
-    let request = GeminiRequest {
-        contents: vec![Content {
-            parts: vec![
-                Part { text: Some(prompt.to_string()), inline_data: None },
-                Part { text: None, inline_data: Some(InlineData { mime_type: "image/png".to_string(), data: b64 }) },
-            ],
-        }],
-    };
-    let url = format!("https://generativelanguage.googleapis.com/v1beta/models/gemini-2.5-flash-lite:generateContent?key={}", api_key);
-    
-    let response = client.post(&url).header("Content-Type", "application/json").json(&request).send().await.map_err(|e| TranslationError::Other(e.into()))?;
+    serde_json::json!({ "type": "image_url", "image_url": { "url": format!("data:{};base64,{}", image_mime(image_bytes), b64) } })
+}
+
+// --- GEMINI ---
+pub struct GeminiProvider {
+    pub model: String,
+    // Gốc của API, vd. "https://generativelanguage.googleapis.com/v1beta"
+    pub base_url: String,
+}
+
+impl Default for GeminiProvider {
+    fn default() -> Self {
+        Self { model: "gemini-2.5-flash-lite".to_string(), base_url: "https://generativelanguage.googleapis.com/v1beta".to_string() }
+    }
+}
+
+impl GeminiProvider {
+    /// Model mặc định, hoặc model do prompt đang chọn chỉ định
+    pub fn from_config(config: &Config) -> Self {
+        let mut provider = Self::default();
+        if !config.model_override.is_empty() { provider.model = config.model_override.clone(); }
+        provider
+    }
+
+    async fn generate_translation(&self, request: TranslationRequest<'_>, on_partial: Option<&PartialFn<'_>>) -> Result<TranslationResult, TranslationError> {
+        let mut parts = vec![Part { text: Some(request.full_prompt()), inline_data: None }];
+        if let Some(image_bytes) = request.image_bytes {
+            parts.push(gemini_image_part(image_bytes));
+        }
+        let generation_config = if request.structured { Some(gemini_structured_config()) } else { None };
+        let structured = request.structured;
+        let text = match on_partial {
+            Some(on_partial) => self.generate(request.api_key, parts, generation_config, Some(&move |raw: &str| {
+                if let Some(partial) = partial_text(raw, structured) { on_partial(&partial); }
+            })).await?,
+            None => self.generate(request.api_key, parts, generation_config, None).await?,
+        };
+        Ok(TranslationResult::from_response(&text, request.structured, None))
+    }
+
+    /// Gọi generateContent, trả về text của candidate đầu tiên.
+    /// Có `on_partial` thì dùng streamGenerateContent (SSE) và báo text đã nhận sau mỗi mảnh.
+    async fn generate(&self, api_key: &str, parts: Vec<Part>, generation_config: Option<serde_json::Value>, on_partial: Option<&PartialFn<'_>>) -> Result<String, TranslationError> {
+        let body = GeminiRequest { contents: vec![Content { parts }], generation_config };
+        let url = match on_partial {
+            Some(_) => format!("{}/models/{}:streamGenerateContent?alt=sse&key={}", self.base_url, self.model, api_key),
+            None => format!("{}/models/{}:generateContent?key={}", self.base_url, self.model, api_key),
+        };
+
+        let response = http::send(http::client().post(&url).header("Content-Type", "application/json").json(&body)).await.map_err(|e| TranslationError::Other(e.into()))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            if status.as_u16() == 429 { return Err(TranslationError::RateLimitExceeded); }
+            return Err(TranslationError::Other(anyhow::anyhow!("Gemini Error {}", status)));
+        }
+
+        if let Some(on_partial) = on_partial {
+            // Mỗi event là 1 GeminiResponse chứa phần text mới; mảnh cuối có thể chỉ có finishReason
+            let mut text = String::new();
+            let mut reason = None;
+            http::read_sse(response, |data| {
+                let Ok(chunk) = serde_json::from_str::<serde_json::Value>(data) else { return };
+                if let Some(r) = chunk["candidates"][0]["finishReason"].as_str().or(chunk["promptFeedback"]["blockReason"].as_str()) { reason = Some(r.to_string()); }
+                if let Some(part) = chunk["candidates"][0]["content"]["parts"][0]["text"].as_str() {
+                    text.push_str(part);
+                    on_partial(&text);
+                }
+            }).await.map_err(|e| TranslationError::Other(e.into()))?;
+            if text.is_empty() { return Err(gemini_empty(reason.as_deref())); }
+            return Ok(text);
+        }
+
+        let resp_json: GeminiResponse = response.json().await.map_err(|e| TranslationError::Other(e.into()))?;
+        resp_json.into_text()
+    }
+}
+
+impl TranslationProvider for GeminiProvider {
+    fn id(&self) -> &'static str { "gemini" }
+    fn name(&self) -> &'static str { "Gemini" }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities { vision: true, text: true, needs_api_key: true, uses_api_key: true }
+    }
+
+    fn rate_limit(&self) -> RateLimitInfo {
+        RateLimitInfo { requests_per_minute: Some(15), requests_per_day: Some(1000), reports_remaining: false }
+    }
+
+    fn translate<'a>(&'a self, request: TranslationRequest<'a>) -> BoxFuture<'a, Result<TranslationResult, TranslationError>> {
+        Box::pin(self.generate_translation(request, None))
+    }
+
+    fn translate_stream<'a>(&'a self, request: TranslationRequest<'a>, on_partial: &'a PartialFn<'a>) -> BoxFuture<'a, Result<TranslationResult, TranslationError>> {
+        Box::pin(self.generate_translation(request, Some(on_partial)))
+    }
+
+    fn translate_batch<'a>(&'a self, request: BatchRequest<'a>) -> BoxFuture<'a, Result<Vec<TranslationResult>, TranslationError>> {
+        Box::pin(async move {
+            let mut parts = vec![Part { text: Some(request.full_prompt()), inline_data: None }];
+            for (i, image_bytes) in request.images.iter().enumerate() {
+                parts.push(Part { text: Some(format!("Image {}:", i + 1)), inline_data: None });
+                parts.push(gemini_image_part(image_bytes));
+            }
+            let text = self.generate(request.api_key, parts, Some(gemini_batch_config()), None).await?;
+            TranslationResult::batch_from_response(&text, request.images.len(), None)
+        })
+    }
+}
+
+// --- OPENAI-COMPATIBLE (chat/completions) ---
+pub struct OpenAiCompatibleProvider {
+    pub base_url: String,
+    pub model: String,
+    pub extra_headers: Vec<(String, String)>,
+    pub temperature: f32,
+}
+
+impl OpenAiCompatibleProvider {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            base_url: config.openai_base_url.clone(),
+            model: config.openai_model.clone(),
+            extra_headers: config.openai_extra_headers.iter()
+                .filter(|h| !h.name.trim().is_empty())
+                .map(|h| (h.name.trim().to_string(), h.value.clone()))
+                .collect(),
+            temperature: config.openai_temperature,
+        }
+    }
+
+    fn endpoint(&self) -> String {
+        format!("{}/chat/completions", self.base_url.trim_end_matches('/'))
+    }
+
+    /// Gọi chat/completions. `label` dùng để ghi tên dịch vụ vào thông báo lỗi.
+    async fn chat(&self, label: &str, request: TranslationRequest<'_>, on_partial: Option<&PartialFn<'_>>) -> Result<TranslationResult, TranslationError> {
+        let mut content = vec![serde_json::json!({ "type": "text", "text": request.build_prompt(false) })];
+        if let Some(image_bytes) = request.image_bytes {
+            content.push(openai_image_part(image_bytes));
+        }
+        let mut messages = request.history_messages();
+        messages.push(serde_json::json!({ "role": "user", "content": content }));
+        let schema = if request.structured { Some(structured_schema()) } else { None };
+        let structured = request.structured;
+        let (text, remaining) = match on_partial {
+            Some(on_partial) => self.complete(label, request.api_key, messages, schema, Some(&move |raw: &str| {
+                if let Some(partial) = partial_text(raw, structured) { on_partial(&partial); }
+            })).await?,
+            None => self.complete(label, request.api_key, messages, schema, None).await?,
+        };
+        Ok(TranslationResult::from_response(&text, request.structured, remaining))
+    }
+
+    /// Nhiều ảnh trong 1 tin nhắn, mỗi ảnh có nhãn số thứ tự đứng trước
+    async fn chat_batch(&self, label: &str, request: BatchRequest<'_>) -> Result<Vec<TranslationResult>, TranslationError> {
+        let mut content = vec![serde_json::json!({ "type": "text", "text": request.full_prompt() })];
+        for (i, image_bytes) in request.images.iter().enumerate() {
+            content.push(serde_json::json!({ "type": "text", "text": format!("Image {}:", i + 1) }));
+            content.push(openai_image_part(image_bytes));
+        }
+        let messages = vec![serde_json::json!({ "role": "user", "content": content })];
+        let (text, remaining) = self.complete(label, request.api_key, messages, Some(batch_schema()), None).await?;
+        TranslationResult::batch_from_response(&text, request.images.len(), remaining)
+    }
+
+    /// Gửi request, trả về nội dung câu trả lời và số request còn lại (nếu server báo).
+    /// Có `on_partial` thì bật `stream` (SSE) và báo nội dung đã nhận sau mỗi mảnh.
+    async fn complete(&self, label: &str, api_key: &str, messages: Vec<serde_json::Value>, schema: Option<serde_json::Value>, on_partial: Option<&PartialFn<'_>>) -> Result<(String, Option<i32>), TranslationError> {
+        let mut body = serde_json::json!({
+            "model": self.model,
+            "messages": messages,
+            "temperature": self.temperature
+        });
+        if on_partial.is_some() { body["stream"] = serde_json::json!(true); }
+        if let Some(schema) = schema {
+            body["response_format"] = serde_json::json!({
+                "type": "json_schema",
+                "json_schema": { "name": "translation", "schema": schema }
+            });
+        }
+
+        let mut builder = http::client().post(self.endpoint()).header("Content-Type", "application/json");
+        // Server local (LM Studio, llama.cpp) thường không cần key
+        if !api_key.is_empty() {
+            builder = builder.header("Authorization", format!("Bearer {}", api_key));
+        }
+        for (name, value) in &self.extra_headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+
+        let response = http::send(builder.json(&body)).await.map_err(|e| TranslationError::Other(e.into()))?;
 
-    if !response.status().is_success() {
         let status = response.status();
-        if status.as_u16() == 429 { return Err(TranslationError::RateLimitExceeded); }
-        return Err(TranslationError::Other(anyhow::anyhow!("Gemini Error {}", status)));
+        let remaining = response.headers().get("x-ratelimit-remaining-requests").and_then(|h| h.to_str().ok()).and_then(|s| s.parse::<i32>().ok());
+
+        if !status.is_success() {
+            if status.as_u16() == 429 { return Err(TranslationError::RateLimitExceeded); }
+            let body = response.text().await.unwrap_or_default();
+            return Err(TranslationError::Other(anyhow::anyhow!("{} Error {}: {}", label, status, body)));
+        }
+
+        if let Some(on_partial) = on_partial {
+            let mut content = String::new();
+            http::read_sse(response, |data| {
+                let Ok(chunk) = serde_json::from_str::<serde_json::Value>(data) else { return };
+                if let Some(delta) = chunk["choices"][0]["delta"]["content"].as_str() {
+                    content.push_str(delta);
+                    on_partial(&content);
+                }
+            }).await.map_err(|e| TranslationError::Other(e.into()))?;
+            if content.is_empty() { return Err(TranslationError::Other(anyhow::anyhow!("Invalid {} response", label))); }
+            return Ok((content, remaining));
+        }
+
+        let resp_json: serde_json::Value = response.json().await.map_err(|e| TranslationError::Other(e.into()))?;
+
+        if let Some(content) = resp_json["choices"][0]["message"]["content"].as_str() {
+            Ok((content.to_string(), remaining))
+        } else {
+            Err(TranslationError::Other(anyhow::anyhow!("Invalid {} response", label)))
+        }
+    }
+}
+
+impl TranslationProvider for OpenAiCompatibleProvider {
+    fn id(&self) -> &'static str { "openai" }
+    fn name(&self) -> &'static str { "OpenAI-compatible" }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        // Không biết trước model có nhận ảnh hay không, để server tự báo lỗi
+        ProviderCapabilities { vision: true, text: true, needs_api_key: false, uses_api_key: true }
+    }
+
+    fn rate_limit(&self) -> RateLimitInfo {
+        RateLimitInfo { requests_per_minute: None, requests_per_day: None, reports_remaining: true }
+    }
+
+    fn translate<'a>(&'a self, request: TranslationRequest<'a>) -> BoxFuture<'a, Result<TranslationResult, TranslationError>> {
+        Box::pin(self.chat("OpenAI-compatible", request, None))
     }
 
-    let resp_json: GeminiResponse = response.json().await.map_err(|e| TranslationError::Other(e.into()))?;
-    if resp_json.candidates.is_empty() { return Err(TranslationError::Other(anyhow::anyhow!("No candidates"))); }
-    let text = resp_json.candidates[0].content.parts[0].text.trim().to_string();
-    
-    Ok(TranslationResult { text, remaining_requests: None })
+    fn translate_stream<'a>(&'a self, request: TranslationRequest<'a>, on_partial: &'a PartialFn<'a>) -> BoxFuture<'a, Result<TranslationResult, TranslationError>> {
+        Box::pin(self.chat("OpenAI-compatible", request, Some(on_partial)))
+    }
+
+    fn translate_batch<'a>(&'a self, request: BatchRequest<'a>) -> BoxFuture<'a, Result<Vec<TranslationResult>, TranslationError>> {
+        Box::pin(self.chat_batch("OpenAI-compatible", request))
+    }
 }
 
 // --- GROQ ---
-pub async fn translate_with_groq_image(api_key: &str, prompt: &str, image_bytes: &[u8]) -> Result<TranslationResult, TranslationError> {
-    let client = reqwest::Client::new();
-    let b64 = general_purpose::STANDARD.encode(image_bytes);
-    let request = serde_json::json!({
-        "model": "meta-llama/llama-4-scout-17b-16e-instruct",
-        "messages": [
-            {
-                "role": "user",
-                "content": [
-                    { "type": "text", "text": prompt },
-                    { "type": "image_url", "image_url": { "url": format!("data:image/png;base64,{}", b64) } }
-                ]
+pub const GROQ_BASE_URL: &str = "https://api.groq.com/openai/v1";
+
+/// Groq chỉ là một endpoint OpenAI-compatible với URL và model cố định.
+pub struct GroqProvider {
+    inner: OpenAiCompatibleProvider,
+}
+
+impl Default for GroqProvider {
+    fn default() -> Self {
+        Self {
+            inner: OpenAiCompatibleProvider {
+                base_url: GROQ_BASE_URL.to_string(),
+                model: "meta-llama/llama-4-scout-17b-16e-instruct".to_string(),
+                extra_headers: Vec::new(),
+                temperature: 0.1,
+            },
+        }
+    }
+}
+
+impl GroqProvider {
+    pub fn from_config(config: &Config) -> Self {
+        let mut provider = Self::default();
+        if !config.model_override.is_empty() { provider.inner.model = config.model_override.clone(); }
+        provider
+    }
+}
+
+impl TranslationProvider for GroqProvider {
+    fn id(&self) -> &'static str { "groq" }
+    fn name(&self) -> &'static str { "Groq" }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities { vision: true, text: true, needs_api_key: true, uses_api_key: true }
+    }
+
+    fn rate_limit(&self) -> RateLimitInfo {
+        RateLimitInfo { requests_per_minute: Some(30), requests_per_day: Some(1000), reports_remaining: true }
+    }
+
+    fn translate<'a>(&'a self, request: TranslationRequest<'a>) -> BoxFuture<'a, Result<TranslationResult, TranslationError>> {
+        Box::pin(self.inner.chat("Groq", request, None))
+    }
+
+    fn translate_stream<'a>(&'a self, request: TranslationRequest<'a>, on_partial: &'a PartialFn<'a>) -> BoxFuture<'a, Result<TranslationResult, TranslationError>> {
+        Box::pin(self.inner.chat("Groq", request, Some(on_partial)))
+    }
+
+    fn translate_batch<'a>(&'a self, request: BatchRequest<'a>) -> BoxFuture<'a, Result<Vec<TranslationResult>, TranslationError>> {
+        Box::pin(self.inner.chat_batch("Groq", request))
+    }
+}
+
+// --- OLLAMA (offline) ---
+#[derive(Deserialize)]
+struct OllamaTags { models: Vec<OllamaModel> }
+#[derive(Deserialize)]
+struct OllamaModel { name: String }
+
+pub struct OllamaProvider {
+    pub base_url: String,
+    pub model: String,
+}
+
+impl OllamaProvider {
+    pub fn from_config(config: &Config) -> Self {
+        Self { base_url: config.ollama_base_url.trim_end_matches('/').to_string(), model: config.ollama_model.clone() }
+    }
+
+    fn map_send_error(&self, e: reqwest::Error) -> TranslationError {
+        if e.is_connect() || e.is_timeout() { TranslationError::Unreachable(self.base_url.clone()) } else { TranslationError::Other(e.into()) }
+    }
+
+    /// Lấy danh sách model đã pull về máy (GET /api/tags)
+    pub async fn list_models(&self) -> Result<Vec<String>, TranslationError> {
+        let url = format!("{}/api/tags", self.base_url);
+        let response = http::send(http::client().get(&url)).await.map_err(|e| self.map_send_error(e))?;
+        if !response.status().is_success() {
+            return Err(TranslationError::Other(anyhow::anyhow!("Ollama Error {}", response.status())));
+        }
+        let tags: OllamaTags = response.json().await.map_err(|e| TranslationError::Other(e.into()))?;
+        Ok(tags.models.into_iter().map(|m| m.name).collect())
+    }
+
+    /// Kiểm tra server có chạy và model đã chọn đã được pull chưa
+    pub async fn test_connection(&self) -> Result<String, TranslationError> {
+        let models = self.list_models().await?;
+        let has_model = models.iter().any(|m| m == &self.model || m.split(':').next() == Some(self.model.as_str()));
+        if !has_model { return Err(TranslationError::ModelNotFound(self.model.clone())); }
+        Ok(format!("Kết nối OK ({} model)", models.len()))
+    }
+}
+
+impl TranslationProvider for OllamaProvider {
+    fn id(&self) -> &'static str { "ollama" }
+    fn name(&self) -> &'static str { "Ollama" }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities { vision: true, text: true, needs_api_key: false, uses_api_key: false }
+    }
+
+    fn rate_limit(&self) -> RateLimitInfo {
+        RateLimitInfo::default()
+    }
+
+    fn translate<'a>(&'a self, request: TranslationRequest<'a>) -> BoxFuture<'a, Result<TranslationResult, TranslationError>> {
+        Box::pin(async move {
+            let mut message = serde_json::json!({ "role": "user", "content": request.build_prompt(false) });
+            if let Some(image_bytes) = request.image_bytes {
+                message["images"] = serde_json::json!([general_purpose::STANDARD.encode(image_bytes)]);
+            }
+            let mut body = serde_json::json!({
+                "model": self.model,
+                "messages": request.history_messages().into_iter().chain(std::iter::once(message)).collect::<Vec<_>>(),
+                "stream": false,
+                "options": { "temperature": 0.1 }
+            });
+            if request.structured { body["format"] = structured_schema(); }
+            let url = format!("{}/api/chat", self.base_url);
+
+            let response = http::send(http::client().post(&url).json(&body)).await.map_err(|e| self.map_send_error(e))?;
+
+            let status = response.status();
+            if !status.is_success() {
+                let body = response.text().await.unwrap_or_default();
+                if status.as_u16() == 404 && body.contains("not found") { return Err(TranslationError::ModelNotFound(self.model.clone())); }
+                return Err(TranslationError::Other(anyhow::anyhow!("Ollama Error {}: {}", status, body)));
             }
-        ],
-        "temperature": 0.1
-    });
-    let url = "https://api.groq.com/openai/v1/chat/completions";
-    
-    let response = client.post(url)
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .json(&request)
-        .send()
-        .await
-        .map_err(|e| TranslationError::Other(e.into()))?;
-
-    let status = response.status();
-    let remaining = response.headers().get("x-ratelimit-remaining-requests").and_then(|h| h.to_str().ok()).and_then(|s| s.parse::<i32>().ok());
-
-    if !status.is_success() {
-        if status.as_u16() == 429 { return Err(TranslationError::RateLimitExceeded); }
-        let body = response.text().await.unwrap_or_default();
-        return Err(TranslationError::Other(anyhow::anyhow!("Groq Error {}: {}", status, body)));
-    }
-
-    let resp_json: serde_json::Value = response.json().await.map_err(|e| TranslationError::Other(e.into()))?;
-    
-    if let Some(content) = resp_json["choices"][0]["message"]["content"].as_str() {
-        Ok(TranslationResult { text: content.trim().to_string(), remaining_requests: remaining })
+
+            let resp_json: serde_json::Value = response.json().await.map_err(|e| TranslationError::Other(e.into()))?;
+            if let Some(content) = resp_json["message"]["content"].as_str() {
+                Ok(TranslationResult::from_response(content, request.structured, None))
+            } else {
+                Err(TranslationError::Other(anyhow::anyhow!("Invalid Ollama response")))
+            }
+        })
+    }
+}
+
+// --- DICTIONARY (text-only, offline) ---
+
+// (gốc, nghĩa), cụm dài trước
+type DictionaryEntries = Arc<Vec<(String, String)>>;
+
+// Từ điển đã đọc và thời điểm sửa file lúc đọc: UI tạo provider mỗi khung hình nên không đọc lại file mỗi lần
+static DICTIONARY: Mutex<Option<(Option<SystemTime>, DictionaryEntries)>> = Mutex::new(None);
+
+/// Tra từ điển `dictionary.tsv` (mỗi dòng: gốc<TAB>nghĩa). Chỉ dùng được sau bước OCR.
+pub struct DictionaryProvider {
+    entries: DictionaryEntries,
+}
+
+impl DictionaryProvider {
+    /// Dùng lại bản đã đọc, chỉ đọc lại khi `dictionary.tsv` bị sửa
+    pub fn load() -> Self {
+        let path = Config::get_dictionary_path();
+        let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+        let mut cached = DICTIONARY.lock().unwrap();
+        if let Some((at, entries)) = cached.as_ref() {
+            if *at == modified { return Self { entries: entries.clone() }; }
+        }
+        let entries = Arc::new(Self::parse(&std::fs::read_to_string(&path).unwrap_or_default()));
+        *cached = Some((modified, entries.clone()));
+        Self { entries }
+    }
+
+    fn parse(content: &str) -> Vec<(String, String)> {
+        let mut entries: Vec<(String, String)> = content.lines()
+            .filter_map(|line| line.split_once('\t'))
+            .map(|(src, dst)| (src.trim().to_string(), dst.trim().to_string()))
+            .filter(|(src, _)| !src.is_empty())
+            .collect();
+        // Ưu tiên cụm dài trước để không thay nhầm một phần của cụm khác
+        entries.sort_by(|a, b| b.0.chars().count().cmp(&a.0.chars().count()));
+        entries
+    }
+
+    fn lookup(&self, text: &str) -> String {
+        let trimmed = text.trim();
+        if let Some((_, dst)) = self.entries.iter().find(|(src, _)| src == trimmed) {
+            return dst.clone();
+        }
+        let mut result = trimmed.to_string();
+        for (src, dst) in self.entries.iter() {
+            if result.contains(src.as_str()) { result = result.replace(src.as_str(), dst); }
+        }
+        result
+    }
+}
+
+impl TranslationProvider for DictionaryProvider {
+    fn id(&self) -> &'static str { "dictionary" }
+    fn name(&self) -> &'static str { "Từ điển" }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities { vision: false, text: true, needs_api_key: false, uses_api_key: false }
+    }
+
+    fn rate_limit(&self) -> RateLimitInfo {
+        RateLimitInfo::default()
+    }
+
+    fn translate<'a>(&'a self, request: TranslationRequest<'a>) -> BoxFuture<'a, Result<TranslationResult, TranslationError>> {
+        Box::pin(async move {
+            let text = request.source_text.ok_or_else(|| TranslationError::Other(anyhow::anyhow!("Từ điển chỉ dùng được khi bật OCR")))?;
+            let mut result = TranslationResult::plain(self.lookup(text), None);
+            result.original_text = Some(text.to_string());
+            Ok(result)
+        })
+    }
+}
+
+// --- REGISTRY ---
+
+/// Tạo provider từ Config hiện tại (để provider đọc được URL, model... do người dùng cấu hình).
+pub type ProviderFactory = Arc<dyn Fn(&Config) -> Arc<dyn TranslationProvider> + Send + Sync>;
+
+pub struct ProviderEntry {
+    pub id: &'static str,
+    pub label: &'static str,
+    factory: ProviderFactory,
+}
+
+pub struct ProviderRegistry {
+    entries: Vec<ProviderEntry>,
+}
+
+impl ProviderRegistry {
+    pub fn empty() -> Self {
+        Self { entries: Vec::new() }
+    }
+
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::empty();
+        registry.register("gemini", "Gemini (Không nên dùng)", Arc::new(|config| Arc::new(GeminiProvider::from_config(config))));
+        registry.register("groq", "Groq (Meta Llama)", Arc::new(|config| Arc::new(GroqProvider::from_config(config))));
+        registry.register("openai", "OpenAI-compatible (Tự cấu hình)", Arc::new(|config| Arc::new(OpenAiCompatibleProvider::from_config(config))));
+        registry.register("ollama", "Ollama (Offline)", Arc::new(|config| Arc::new(OllamaProvider::from_config(config))));
+        registry.register("dictionary", "Từ điển (chỉ dùng với OCR)", Arc::new(|_| Arc::new(DictionaryProvider::load())));
+        registry
+    }
+
+    /// Đăng ký provider mới. Nếu trùng id thì ghi đè (dùng để thay provider giả khi test).
+    pub fn register(&mut self, id: &'static str, label: &'static str, factory: ProviderFactory) {
+        let entry = ProviderEntry { id, label, factory };
+        if let Some(existing) = self.entries.iter_mut().find(|e| e.id == id) {
+            *existing = entry;
+        } else {
+            self.entries.push(entry);
+        }
+    }
+
+    pub fn entries(&self) -> &[ProviderEntry] {
+        &self.entries
+    }
+
+    pub fn get(&self, id: &str, config: &Config) -> Option<Arc<dyn TranslationProvider>> {
+        self.entries.iter().find(|e| e.id == id).map(|e| (e.factory)(config))
+    }
+
+    pub fn resolve(&self, config: &Config) -> Option<Arc<dyn TranslationProvider>> {
+        self.get(&config.selected_api, config)
+    }
+}
+
+static REGISTRY: OnceLock<RwLock<ProviderRegistry>> = OnceLock::new();
+
+pub fn registry() -> &'static RwLock<ProviderRegistry> {
+    REGISTRY.get_or_init(|| RwLock::new(ProviderRegistry::with_defaults()))
+}
+
+pub fn register_provider(id: &'static str, label: &'static str, factory: ProviderFactory) {
+    registry().write().unwrap().register(id, label, factory);
+}
+
+pub fn resolve_provider(config: &Config) -> Option<Arc<dyn TranslationProvider>> {
+    registry().read().unwrap().resolve(config)
+}
+
+/// Danh sách (id, nhãn hiển thị) cho ComboBox chọn dịch vụ
+pub fn provider_labels() -> Vec<(&'static str, &'static str)> {
+    registry().read().unwrap().entries().iter().map(|e| (e.id, e.label)).collect()
+}
+
+pub fn get_provider(id: &str, config: &Config) -> Option<Arc<dyn TranslationProvider>> {
+    registry().read().unwrap().get(id, config)
+}
+
+/// `on_partial`: nhận bản dịch tạm khi stream (provider không hỗ trợ thì chỉ có kết quả cuối)
+pub async fn translate_from_image(config: &Config, key: &str, prompt: &str, image_bytes: &[u8], history: &[DialogueLine], on_partial: Option<&PartialFn<'_>>) -> Result<TranslationResult, TranslationError> {
+    http::configure(config);
+    let provider = resolve_provider(config).ok_or_else(|| TranslationError::Other(anyhow::anyhow!("Invalid API")))?;
+
+    // Khung hình giống hệt lần trước (auto mode, bấm phím lại) -> trả luôn, không tốn request
+    let cache_key = if config.use_translation_cache {
+        cache::image_hash(image_bytes).map(|hash| (hash, cache::scope_key(config, provider.id(), prompt, history)))
     } else {
-        Err(TranslationError::Other(anyhow::anyhow!("Invalid Groq response")))
+        None
+    };
+    if let Some((hash, scope)) = &cache_key {
+        if let Some(result) = cache::get(config, hash, *scope) {
+            return Ok(result);
+        }
+    }
+
+    let request = TranslationRequest { api_key: key, prompt, image_bytes: Some(image_bytes), source_text: None, structured: true, history };
+    let result = match on_partial {
+        Some(on_partial) => provider.translate_stream(request, on_partial).await?,
+        None => provider.translate(request).await?,
+    };
+    if let Some((hash, scope)) = cache_key {
+        if !result.translated_text.trim().is_empty() {
+            cache::put(config, hash, scope, &result);
+        }
     }
+    Ok(result)
 }
 
-pub async fn translate_from_image(api: &str, key: &str, prompt: &str, image_bytes: &[u8]) -> Result<TranslationResult, TranslationError> {
-    match api {
-        "gemini" => translate_with_gemini_image(key, prompt, image_bytes).await,
-        "groq" => translate_with_groq_image(key, prompt, image_bytes).await,
-        _ => Err(TranslationError::Other(anyhow::anyhow!("Invalid API"))),
+/// Dịch nhiều vùng trong 1 request (không dùng cache), lỗi thì bên gọi quay về dịch từng vùng
+pub async fn translate_batch(config: &Config, key: &str, prompt: &str, images: &[&[u8]], histories: &[Vec<DialogueLine>]) -> Result<Vec<TranslationResult>, TranslationError> {
+    http::configure(config);
+    let provider = resolve_provider(config).ok_or_else(|| TranslationError::Other(anyhow::anyhow!("Invalid API")))?;
+    provider.translate_batch(BatchRequest { api_key: key, prompt, images, histories }).await
+}
+
+/// Bước 2 của pipeline OCR: chỉ gửi text đã nhận dạng cho provider `api`
+pub async fn translate_text(config: &Config, api: &str, key: &str, prompt: &str, text: &str, history: &[DialogueLine], on_partial: Option<&PartialFn<'_>>) -> Result<TranslationResult, TranslationError> {
+    http::configure(config);
+    let provider = get_provider(api, config).ok_or_else(|| TranslationError::Other(anyhow::anyhow!("Invalid API")))?;
+    if !provider.capabilities().text {
+        return Err(TranslationError::Other(anyhow::anyhow!("{} không hỗ trợ dịch text", provider.name())));
+    }
+    let request = TranslationRequest { api_key: key, prompt, image_bytes: None, source_text: Some(text), structured: true, history };
+    let mut result = match on_partial {
+        Some(on_partial) => provider.translate_stream(request, on_partial).await?,
+        None => provider.translate(request).await?,
+    };
+    // Text gốc đã có từ OCR, không cần tin vào bản model chép lại
+    result.original_text = Some(text.to_string());
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::mock::{self, MockServer};
+
+    fn openai(base_url: &str) -> OpenAiCompatibleProvider {
+        OpenAiCompatibleProvider {
+            base_url: base_url.to_string(),
+            model: "local-model".to_string(),
+            extra_headers: vec![("X-Title".to_string(), "screen-translator".to_string())],
+            temperature: 0.3,
+        }
+    }
+
+    fn text_request(api_key: &str) -> TranslationRequest<'_> {
+        TranslationRequest { api_key, prompt: "Translate", image_bytes: None, source_text: Some("你好"), structured: false, history: &[] }
+    }
+
+    fn completion(content: &str) -> mock::Response {
+        let body = serde_json::json!({ "choices": [{ "message": { "content": content } }] }).to_string();
+        mock::response("200 OK", &[("Content-Type", "application/json"), ("x-ratelimit-remaining-requests", "42")], &body)
+    }
+
+    #[tokio::test]
+    async fn openai_request_uses_base_url_model_and_headers() {
+        let server = MockServer::start(vec![completion("Xin chào"), completion("Xin chào")]);
+        let provider = openai(&format!("{}/v1/", server.url));
+
+        let result = provider.translate(text_request("sk-test")).await.unwrap();
+        assert_eq!(result.translated_text, "Xin chào");
+        assert_eq!(result.remaining_requests, Some(42));
+        provider.translate(text_request("")).await.unwrap();
+
+        let requests = server.requests();
+        assert!(requests[0].head.starts_with("POST /v1/chat/completions "), "{}", requests[0].head);
+        let body: serde_json::Value = serde_json::from_str(&requests[0].body).unwrap();
+        assert_eq!(body["model"], "local-model");
+        assert!((body["temperature"].as_f64().unwrap() - 0.3).abs() < 1e-6);
+        assert!(body.get("stream").is_none());
+        assert_eq!(requests[0].header("x-title"), Some("screen-translator"));
+        assert_eq!(requests[0].header("authorization"), Some("Bearer sk-test"));
+        // Không có key (server local) thì không gửi Authorization
+        assert_eq!(requests[1].header("authorization"), None);
+        assert_eq!(requests[1].header("x-title"), Some("screen-translator"));
+    }
+
+    #[test]
+    fn partial_json_string_handles_escapes_and_unterminated_values() {
+        let raw = r#"{"original_text": "\"你好\"", "translated_text" : "Anh ấy nói \"chào\"\nrồi đi \u00e0"#;
+        assert_eq!(partial_json_string(raw, "translated_text").as_deref(), Some("Anh ấy nói \"chào\"\nrồi đi à"));
+        assert_eq!(partial_json_string(raw, "original_text").as_deref(), Some("\"你好\""));
+        // Dừng đúng ở dấu nháy đóng, không lấy sang field sau
+        assert_eq!(partial_json_string(r#"{"translated_text":"a\\","speaker":"b"}"#, "translated_text").as_deref(), Some("a\\"));
+        // Mới về tới tên field / đang giữa escape
+        assert_eq!(partial_json_string(r#"{"translated_text"#, "translated_text"), None);
+        assert_eq!(partial_json_string(r#"{"translated_text": "xin \"#, "translated_text").as_deref(), Some("xin "));
+        assert_eq!(partial_text(r#"{"translated_text": "  "#, true), None);
+    }
+
+    #[tokio::test]
+    async fn openai_stream_reports_partial_text() {
+        let delta = |text: &str| format!("data: {}\n\n", serde_json::json!({ "choices": [{ "delta": { "content": text } }] }));
+        let (first, second) = (delta(r#"{"translated_text": "Xin "#), delta(r#"chào \"bạn\""}"#));
+        let server = MockServer::start(vec![mock::sse(&[first.as_bytes(), &second.as_bytes()[..20], &second.as_bytes()[20..], b"data: [DONE]\n\n"])]);
+        let provider = openai(&server.url);
+
+        let partials = Mutex::new(Vec::new());
+        let on_partial = |text: &str| partials.lock().unwrap().push(text.to_string());
+        let request = TranslationRequest { structured: true, ..text_request("") };
+        let result = provider.translate_stream(request, &on_partial).await.unwrap();
+
+        assert_eq!(result.translated_text, "Xin chào \"bạn\"");
+        assert_eq!(*partials.lock().unwrap(), vec!["Xin", "Xin chào \"bạn\""]);
+        let body: serde_json::Value = serde_json::from_str(&server.requests()[0].body).unwrap();
+        assert_eq!(body["stream"], true);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn gemini_empty_candidates_become_err() {
+        let json = |body: &str| mock::response("200 OK", &[("Content-Type", "application/json")], body);
+        let server = MockServer::start(vec![
+            json(r#"{"promptFeedback":{"blockReason":"SAFETY"}}"#),
+            json(r#"{"candidates":[{"finishReason":"MAX_TOKENS","content":{"role":"model"}}]}"#),
+            json(r#"{"candidates":[]}"#),
+            json(r#"{"candidates":[{"content":{"parts":[{"text":"Xin chào"}]},"finishReason":"STOP"}]}"#),
+        ]);
+        let provider = GeminiProvider { base_url: server.url.clone(), ..GeminiProvider::default() };
+
+        for reason in ["SAFETY", "MAX_TOKENS", "không rõ"] {
+            match provider.translate(text_request("key")).await {
+                Err(TranslationError::Other(e)) => assert!(e.to_string().contains(reason), "{}", e),
+                Err(e) => panic!("unexpected error: {}", e),
+                Ok(_) => panic!("empty response must be an error"),
+            }
+        }
+        assert_eq!(provider.translate(text_request("key")).await.unwrap().translated_text, "Xin chào");
+        assert!(server.requests()[0].head.starts_with("POST /models/gemini-2.5-flash-lite:generateContent?key=key "));
+    }
+
+    #[tokio::test]
+    async fn openai_error_responses_become_err() {
+        let server = MockServer::start(vec![
+            mock::response("400 Bad Request", &[], r#"{"error":{"message":"model not loaded"}}"#),
+            mock::response("200 OK", &[("Content-Type", "application/json")], "not json"),
+            mock::response("200 OK", &[("Content-Type", "application/json")], r#"{"choices":[]}"#),
+            mock::response("429 Too Many Requests", &[], ""),
+        ]);
+        let provider = openai(&server.url);
+
+        match provider.translate(text_request("")).await {
+            Err(TranslationError::Other(e)) => assert!(e.to_string().contains("model not loaded"), "{}", e),
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("400 must be an error"),
+        }
+        assert!(matches!(provider.translate(text_request("")).await, Err(TranslationError::Other(_))));
+        assert!(matches!(provider.translate(text_request("")).await, Err(TranslationError::Other(_))));
+        assert!(matches!(provider.translate(text_request("")).await, Err(TranslationError::RateLimitExceeded)));
+    }
+}
@@ -0,0 +1,228 @@
+use crate::cache::{self, ImageHash};
+use crate::capture::{self, CaptureSource};
+use crate::config::{Config, MatchSettings, TriggerMode, TriggerSettings};
+use crate::matcher::TemplateMatcher;
+use crate::templates::TemplateLibrary;
+
+// Hash có ít bit 1 hơn mức này coi như vùng trống (hộp thoại đã tắt)
+const BLANK_BITS: u32 = 8;
+// Mũi tên mất quá số lần kiểm tra này liên tiếp thì coi như đã sang câu mới
+const MISS_COUNTER_THRESHOLD: i32 = 5;
+
+/// Kích hoạt auto mode không cần mũi tên: chữ trong vùng dịch phải khác lần dịch trước
+/// và đứng yên đủ `stable_frames` lần kiểm tra liên tiếp (chạy chữ xong) thì mới dịch, mỗi câu 1 lần.
+#[derive(Default)]
+pub struct TextStableTrigger {
+    last_hash: Option<ImageHash>,
+    stable_count: u32,
+    // Nội dung đã dịch gần nhất
+    fired_hash: Option<ImageHash>,
+}
+
+impl TextStableTrigger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Nạp hash của khung hình mới, trả về true nếu cần dịch ngay
+    pub fn update(&mut self, hash: ImageHash, settings: &TriggerSettings) -> bool {
+        if hash.iter().map(|w| w.count_ones()).sum::<u32>() < BLANK_BITS {
+            // Vùng trống: câu sau dù giống hệt câu trước vẫn được dịch lại
+            self.reset();
+            return false;
+        }
+        let same = |a: &ImageHash| cache::distance(a, &hash) <= settings.change_threshold;
+        self.stable_count = if self.last_hash.as_ref().map_or(false, same) { self.stable_count + 1 } else { 0 };
+        self.last_hash = Some(hash);
+
+        // stable_count đếm số lần lặp lại, khung đầu tiên cũng tính là 1 khung đứng yên
+        if self.stable_count + 1 < settings.stable_frames.max(1) { return false; }
+        if self.fired_hash.as_ref().map_or(false, same) { return false; }
+        self.fired_hash = Some(hash);
+        true
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Dựng mẫu mũi tên cho game đang chơi
+pub type ArrowLoader = Box<dyn Fn(&Config) -> Vec<TemplateMatcher> + Send>;
+
+/// 1 lần kiểm tra của auto mode: có câu thoại mới cần dịch hay không, theo cách kích hoạt của game đang chơi.
+/// Ảnh lấy từ `CaptureSource` bất kỳ nên chạy được cả với ảnh mẫu (không cần màn hình).
+pub struct AutoTrigger {
+    load_arrows: ArrowLoader,
+    // Mẫu mũi tên giải mã + co giãn sẵn 1 lần, dựng lại khi đổi thư viện / game / độ nhạy
+    arrow_matchers: Vec<TemplateMatcher>,
+    arrows_key: Option<(u64, String, MatchSettings)>,
+    last_found_state: bool,
+    miss_counter: i32,
+    text_trigger: TextStableTrigger,
+    // Lần kiểm tra gần nhất có thấy mũi tên không (None = không dò mũi tên)
+    arrow_found: Option<bool>,
+}
+
+impl AutoTrigger {
+    pub fn new(load_arrows: ArrowLoader) -> Self {
+        Self {
+            load_arrows,
+            arrow_matchers: Vec::new(),
+            arrows_key: None,
+            last_found_state: false,
+            miss_counter: 0,
+            text_trigger: TextStableTrigger::new(),
+            arrow_found: None,
+        }
+    }
+
+    /// Kiểm tra 1 khung hình, trả về true nếu cần dịch ngay
+    pub fn check(&mut self, source: &dyn CaptureSource, config: &Config) -> bool {
+        self.arrow_found = None;
+        let trigger_settings = config.trigger_settings();
+        match trigger_settings.mode {
+            TriggerMode::Arrow => {
+                let key = (TemplateLibrary::revision(), config.active_game.clone(), config.arrow_match.clone());
+                if self.arrows_key.as_ref() != Some(&key) {
+                    self.arrow_matchers = (self.load_arrows)(config);
+                    self.arrows_key = Some(key);
+                }
+                let Some(arrow_region) = &config.arrow_region else { return false };
+                if self.arrow_matchers.is_empty() { return false; }
+
+                let found = capture::find_template(source, arrow_region, &mut self.arrow_matchers).is_some();
+                self.arrow_found = Some(found);
+                let mut fire = false;
+                if found {
+                    self.miss_counter = 0;
+                    fire = !self.last_found_state;
+                    self.last_found_state = true;
+                } else if self.last_found_state {
+                    self.miss_counter += 1;
+                    if self.miss_counter > MISS_COUNTER_THRESHOLD { self.last_found_state = false; self.miss_counter = 0; }
+                }
+                fire
+            }
+            TriggerMode::TextStable => match config.fixed_regions.first().map(|r| source.capture(r)) {
+                Some(Ok(img)) => self.text_trigger.update(cache::hash_image(&img), trigger_settings),
+                _ => false,
+            },
+        }
+    }
+
+    /// Kết quả dò mũi tên của lần kiểm tra gần nhất (để hiện trạng thái debug)
+    pub fn arrow_found(&self) -> Option<bool> {
+        self.arrow_found
+    }
+
+    /// Tắt auto: câu đang hiện sẽ được dịch lại khi bật lại
+    pub fn reset(&mut self) {
+        self.last_found_state = false;
+        self.miss_counter = 0;
+        self.arrow_found = None;
+        self.text_trigger.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::FixtureSource;
+    use crate::config::Region;
+    use std::path::Path;
+    use std::time::Duration;
+
+    // Ảnh mẫu 320x180: hộp thoại 2 dòng chữ ở (30, 112), mũi tên ở (150, 160)
+    fn frame(name: &str) -> FixtureSource {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join("auto").join(format!("{}.png", name));
+        FixtureSource::new(&path, Duration::ZERO).unwrap()
+    }
+
+    fn region(x: i32, y: i32, width: u32, height: u32) -> Region {
+        Region { x, y, width, height, anchor: None }
+    }
+
+    fn config(mode: TriggerMode) -> Config {
+        let mut config = Config::default();
+        config.auto_trigger = TriggerSettings { mode, ..TriggerSettings::default() };
+        config.fixed_regions = vec![region(30, 112, 260, 44)];
+        config.arrow_region = Some(region(140, 150, 40, 30));
+        config
+    }
+
+    fn auto_trigger() -> AutoTrigger {
+        AutoTrigger::new(Box::new(|config: &Config| {
+            TemplateMatcher::from_bytes(include_bytes!("arrow.png"), &config.arrow_match).into_iter().collect()
+        }))
+    }
+
+    /// Mỗi tên là 1 lần kiểm tra, trả về lần nào kích hoạt dịch
+    fn replay(trigger: &mut AutoTrigger, config: &Config, names: &[&str]) -> Vec<bool> {
+        names.iter().map(|name| trigger.check(&frame(name), config)).collect()
+    }
+
+    #[test]
+    fn arrow_fires_once_per_line() {
+        let config = config(TriggerMode::Arrow);
+        let mut trigger = auto_trigger();
+        let fired = replay(&mut trigger, &config, &["blank", "line1", "line1_arrow", "line1_arrow", "line1", "line1_arrow"]);
+        // Mũi tên nhấp nháy (mất ít lần) vẫn là câu cũ
+        assert_eq!(fired, [false, false, true, false, false, false]);
+        assert_eq!(trigger.arrow_found(), Some(true));
+
+        // Mất hẳn quá MISS_COUNTER_THRESHOLD lần: mũi tên sau là câu mới
+        let gap = vec!["blank"; MISS_COUNTER_THRESHOLD as usize + 1];
+        assert!(replay(&mut trigger, &config, &gap).iter().all(|f| !f));
+        assert_eq!(trigger.arrow_found(), Some(false));
+        assert_eq!(replay(&mut trigger, &config, &["line2_arrow", "line2_arrow"]), [true, false]);
+    }
+
+    #[test]
+    fn arrow_refires_after_reset() {
+        let config = config(TriggerMode::Arrow);
+        let mut trigger = auto_trigger();
+        assert_eq!(replay(&mut trigger, &config, &["line1_arrow", "line1_arrow"]), [true, false]);
+        // Tắt rồi bật lại auto khi mũi tên vẫn hiện: dịch lại câu đó
+        trigger.reset();
+        assert_eq!(trigger.arrow_found(), None);
+        assert_eq!(replay(&mut trigger, &config, &["line1_arrow", "line1_arrow"]), [true, false]);
+
+        // Reset giữa lúc mũi tên nhấp nháy cũng không giữ lại số lần mất
+        assert_eq!(replay(&mut trigger, &config, &["line1"]), [false]);
+        trigger.reset();
+        assert_eq!(replay(&mut trigger, &config, &["line1", "line1_arrow"]), [false, true]);
+    }
+
+    #[test]
+    fn arrow_needs_arrow_region() {
+        let mut config = config(TriggerMode::Arrow);
+        config.arrow_region = None;
+        let mut trigger = auto_trigger();
+        assert_eq!(replay(&mut trigger, &config, &["line1_arrow", "line2_arrow"]), [false, false]);
+        assert_eq!(trigger.arrow_found(), None);
+    }
+
+    #[test]
+    fn text_stable_fires_when_text_settles() {
+        let config = config(TriggerMode::TextStable);
+        let mut trigger = auto_trigger();
+        // Mũi tên nằm ngoài vùng dịch nên không tính là chữ đổi
+        let fired = replay(&mut trigger, &config, &["blank", "line1", "line1", "line1", "line1", "line1_arrow"]);
+        assert_eq!(fired, [false, false, false, true, false, false]);
+        assert_eq!(trigger.arrow_found(), None);
+
+        assert_eq!(replay(&mut trigger, &config, &["line2_arrow", "line2_arrow", "line2_arrow"]), [false, false, true]);
+        // Hộp thoại tắt rồi hiện lại đúng câu đó: dịch lại
+        assert_eq!(replay(&mut trigger, &config, &["blank", "line2_arrow", "line2_arrow", "line2_arrow"]), [false, false, false, true]);
+    }
+
+    #[test]
+    fn text_stable_restarts_after_reset() {
+        let config = config(TriggerMode::TextStable);
+        let mut trigger = auto_trigger();
+        assert_eq!(replay(&mut trigger, &config, &["line1", "line1", "line1"]), [false, false, true]);
+        trigger.reset();
+        assert_eq!(replay(&mut trigger, &config, &["line1", "line1", "line1"]), [false, false, true]);
+    }
+}
@@ -13,17 +13,36 @@ use winapi::shared::windef::HWND;
 
 // Map lưu trạng thái dừng của từng ID (Request ID -> Token dừng)
 static STOP_TOKENS: OnceLock<Mutex<HashMap<u64, Arc<AtomicBool>>>> = OnceLock::new();
-// Map lưu HWND của cửa sổ Overlay tương ứng với ID (Request ID -> HWND)
-static WINDOW_HANDLES: OnceLock<Mutex<HashMap<u64, usize>>> = OnceLock::new();
+// Map lưu HWND của các cửa sổ Overlay tương ứng với ID (Request ID -> HWND, nhiều vùng dùng chung 1 ID)
+static WINDOW_HANDLES: OnceLock<Mutex<HashMap<u64, Vec<usize>>>> = OnceLock::new();
+// Mã ngôn ngữ Google TTS (tl=...) theo ngôn ngữ đích trong config chung (cửa sổ Reader)
+static LANGUAGE: Mutex<String> = Mutex::new(String::new());
+
+pub fn set_language(code: &str) {
+    *LANGUAGE.lock().unwrap() = code.to_string();
+}
+
+fn language() -> String {
+    let code = LANGUAGE.lock().unwrap().clone();
+    if code.is_empty() { "vi".to_string() } else { code }
+}
+
+fn tts_url(text: &str, lang: &str) -> String {
+    format!("https://translate.google.com/translate_tts?ie=UTF-8&q={}&tl={}&client=tw-ob", urlencoding::encode(text.trim()), urlencoding::encode(lang))
+}
 
 pub fn register_window(id: u64, hwnd: usize) {
     let map = WINDOW_HANDLES.get_or_init(|| Mutex::new(HashMap::new()));
-    map.lock().unwrap().insert(id, hwnd);
+    map.lock().unwrap().entry(id).or_default().push(hwnd);
 }
 
-pub fn unregister_window(id: u64) {
+pub fn unregister_window(id: u64, hwnd: usize) {
     if let Some(map) = WINDOW_HANDLES.get() {
-        map.lock().unwrap().remove(&id);
+        let mut map = map.lock().unwrap();
+        if let Some(handles) = map.get_mut(&id) {
+            handles.retain(|&h| h != hwnd);
+            if handles.is_empty() { map.remove(&id); }
+        }
     }
 }
 
@@ -48,7 +67,19 @@ pub fn stop() {
     stop_all();
 }
 
-pub async fn speak(text: &str, split: bool, speed: f32, use_tts: bool, req_id: u64) -> Result<(), anyhow::Error> {
+/// 1 đoạn cần đọc, gửi qua kênh cho thread TTS. Mang theo giọng đọc của vùng đã dịch ra nó
+/// (vùng có thể đặt giọng riêng), không dùng giọng chung lúc thread đọc tới.
+pub struct Speech {
+    pub text: String,
+    pub lang: String,
+    pub split: bool,
+    pub speed: f32,
+    pub use_tts: bool,
+    pub req_id: u64,
+}
+
+/// Đọc với ngôn ngữ cố định (giọng riêng của vùng, thông báo giao diện luôn là tiếng Việt)
+pub async fn speak_in(text: &str, lang: &str, split: bool, speed: f32, use_tts: bool, req_id: u64) -> Result<(), anyhow::Error> {
     if use_tts {
         // Tạo token dừng riêng cho request này
         let stop_token = Arc::new(AtomicBool::new(false));
@@ -57,7 +88,7 @@ pub async fn speak(text: &str, split: bool, speed: f32, use_tts: bool, req_id: u
             map.lock().unwrap().insert(req_id, stop_token.clone());
         }
 
-        let res = speak_gtts(text, split, speed, stop_token.clone()).await;
+        let res = speak_gtts(text, lang, split, speed, stop_token.clone()).await;
 
         // Xóa token sau khi chạy xong
         if let Some(map) = STOP_TOKENS.get() {
@@ -67,10 +98,12 @@ pub async fn speak(text: &str, split: bool, speed: f32, use_tts: bool, req_id: u
         // Nếu chạy xong mà không bị dừng đột ngột (do người dùng click), gửi lệnh đóng cửa sổ
         if !stop_token.load(Ordering::Relaxed) {
              if let Some(map) = WINDOW_HANDLES.get() {
-                 if let Some(&hwnd_ptr) = map.lock().unwrap().get(&req_id) {
-                     unsafe {
-                         // Gửi lệnh đóng cửa sổ
-                         PostMessageW(hwnd_ptr as HWND, WM_CLOSE, 0, 0);
+                 if let Some(handles) = map.lock().unwrap().get(&req_id) {
+                     for &hwnd_ptr in handles {
+                         unsafe {
+                             // Gửi lệnh đóng cửa sổ
+                             PostMessageW(hwnd_ptr as HWND, WM_CLOSE, 0, 0);
+                         }
                      }
                  }
              }
@@ -81,7 +114,7 @@ pub async fn speak(text: &str, split: bool, speed: f32, use_tts: bool, req_id: u
     }
 }
 
-async fn speak_gtts(text: &str, split: bool, speed: f32, stop_token: Arc<AtomicBool>) -> Result<(), anyhow::Error> {
+async fn speak_gtts(text: &str, lang: &str, split: bool, speed: f32, stop_token: Arc<AtomicBool>) -> Result<(), anyhow::Error> {
     let parts: Vec<String> = if split {
         let re = Regex::new(r"[,.]")?;
         re.split(text).map(|s| s.to_string()).collect()
@@ -91,11 +124,8 @@ async fn speak_gtts(text: &str, split: bool, speed: f32, stop_token: Arc<AtomicB
 
     let (_stream, stream_handle) = OutputStream::try_default()?;
     let handles: Vec<_> = parts.into_iter().filter(|p| !p.trim().is_empty()).map(|part| {
+        let url = tts_url(&part, lang);
         tokio::spawn(async move {
-            let url = format!(
-                "https://translate.google.com/translate_tts?ie=UTF-8&q={}&tl=vi&client=tw-ob",
-                urlencoding::encode(&part.trim())
-            );
             let response = reqwest::get(&url).await?;
             response.bytes().await
         })
@@ -129,7 +159,7 @@ async fn speak_gtts(text: &str, split: bool, speed: f32, stop_token: Arc<AtomicB
 // Giữ lại các hàm download_audio/play_audio_data cho ReaderWindow (không đổi)
 pub async fn download_audio(text: String) -> Result<Vec<u8>, anyhow::Error> {
     if text.trim().is_empty() { return Ok(Vec::new()); }
-    let url = format!("https://translate.google.com/translate_tts?ie=UTF-8&q={}&tl=vi&client=tw-ob", urlencoding::encode(&text.trim()));
+    let url = tts_url(&text, &language());
     let response = reqwest::get(&url).await?;
     let bytes = response.bytes().await?;
     Ok(bytes.to_vec())
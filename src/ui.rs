@@ -1,9 +1,22 @@
 // ... (Giữ nguyên các phần import ở đầu file)
 use crate::config;
 use crate::overlay;
+use crate::tts;
+use crate::translation;
+use crate::cache;
+use crate::context;
+use crate::glossary;
+use crate::prompt;
+use crate::capture;
+use crate::preprocess;
+use crate::templates;
+use crate::matcher;
+use crate::window;
+use crate::profiles;
 use eframe::egui;
 use std::sync::atomic::Ordering;
 use crate::AUTO_TRANSLATE_ENABLED;
+use crate::OVERLAY_ACTIVE;
 use crate::HOTKEYS_NEED_UPDATE;
 use crate::BindingTarget;
 use crate::IS_BINDING_MODE;
@@ -19,51 +32,105 @@ use crate::ARROW_CHECK_INTERVAL_MIN;
 use crate::ARROW_CHECK_INTERVAL_MAX;
 use crate::DEFAULT_ARROW_CHECK_INTERVAL;
 use crate::SUCCESS_DISPLAY_DURATION_SECS;
-use crate::WWM_TEXT_REGION_X_RATIO;
-use crate::WWM_TEXT_REGION_Y_RATIO;
-use crate::WWM_TEXT_REGION_W_RATIO;
-use crate::WWM_TEXT_REGION_H_RATIO;
-use crate::WWM_NAME_REGION_X_RATIO;
-use crate::WWM_NAME_REGION_Y_RATIO;
-use crate::WWM_NAME_REGION_W_RATIO;
-use crate::WWM_NAME_REGION_H_RATIO;
-use crate::WWM_ARROW_REGION_X_RATIO;
-use crate::WWM_ARROW_REGION_Y_RATIO;
-use crate::WWM_ARROW_REGION_W_RATIO;
-use crate::WWM_ARROW_REGION_H_RATIO;
-use crate::WWM_REGION_PADDING;
-use crate::WWM_REGION_EXTRA_WIDTH;
-use crate::WWM_REGION_EXTRA_HEIGHT;
 use crate::APP_NAME;
 use winapi::um::winuser::{
-    GetSystemMetrics, SM_CXSCREEN, SM_CYSCREEN, FindWindowW, GetClientRect, ClientToScreen,
-    MessageBoxW, MB_OK, MB_ICONWARNING, MB_TOPMOST, IsWindowVisible
+    GetSystemMetrics, SM_CXSCREEN, SM_CYSCREEN,
+    MessageBoxW, MB_OK, MB_ICONWARNING, MB_TOPMOST
 };
-use winapi::shared::windef::{RECT, POINT};
+use winapi::shared::windef::RECT;
 use std::ffi::OsStr;
 use std::os::windows::ffi::OsStrExt;
 use webbrowser;
-use std::fs;
+use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
 use rfd;
 
-// ... (Giữ nguyên các hàm helper to_wide, get_game_bounds, show_alert, ReaderState, UiState...)
+// ... (Giữ nguyên các hàm helper to_wide, show_alert, ReaderState, UiState...)
 fn to_wide(s: &str) -> Vec<u16> {
     OsStr::new(s).encode_wide().chain(std::iter::once(0)).collect()
 }
 
-fn get_game_bounds(window_title: &str) -> Option<(i32, i32, u32, u32)> {
-    unsafe {
-        let wide_title = to_wide(window_title);
-        let hwnd = FindWindowW(std::ptr::null(), wide_title.as_ptr());
-        if hwnd.is_null() || IsWindowVisible(hwnd) == 0 { return None; }
-        let mut rect: RECT = std::mem::zeroed();
-        if GetClientRect(hwnd, &mut rect) == 0 { return None; }
-        let mut point = POINT { x: 0, y: 0 };
-        if ClientToScreen(hwnd, &mut point) == 0 { return None; }
-        let width = (rect.right - rect.left) as u32;
-        let height = (rect.bottom - rect.top) as u32;
-        Some((point.x, point.y, width, height))
+/// Checkbox bật/tắt ghi đè một cài đặt: bật thì lấy giá trị chung làm giá trị ban đầu
+fn override_toggle<T>(ui: &mut egui::Ui, label: &str, value: &mut Option<T>, default: impl FnOnce() -> T) -> bool {
+    let mut custom = value.is_some();
+    if ui.checkbox(&mut custom, label).changed() {
+        *value = if custom { Some(default()) } else { None };
+        return true;
     }
+    false
+}
+
+/// Bảng cài đặt riêng của một vùng dịch (prompt, dịch vụ, TTS, overlay, clipboard)
+fn region_settings_editor(ui: &mut egui::Ui, id_salt: &str, settings: &mut config::RegionSettings, defaults: &config::Config) -> bool {
+    let mut changed = false;
+    let title = if settings.is_default() { "⚙ Cài đặt riêng (đang theo cài đặt chung)" } else { "⚙ Cài đặt riêng" };
+    egui::CollapsingHeader::new(title).id_source(format!("{}_settings", id_salt)).show(ui, |ui| {
+        egui::Grid::new(format!("{}_grid", id_salt)).num_columns(2).spacing([10.0, 4.0]).show(ui, |ui| {
+            changed |= override_toggle(ui, "Prompt", &mut settings.prompt, || defaults.current_prompt.clone());
+            match &mut settings.prompt {
+                Some(prompt) => { changed |= ui.add(egui::TextEdit::multiline(prompt).desired_rows(2).desired_width(250.0)).changed(); }
+                None => { ui.label(""); }
+            }
+            ui.end_row();
+
+            changed |= override_toggle(ui, "Dịch vụ", &mut settings.provider, || defaults.selected_api.clone());
+            match &mut settings.provider {
+                Some(provider) => {
+                    let providers = translation::provider_labels();
+                    let label = providers.iter().find(|(id, _)| *id == provider.as_str()).map_or("?", |(_, label)| *label);
+                    egui::ComboBox::from_id_source(format!("{}_provider", id_salt)).selected_text(label).width(180.0).show_ui(ui, |ui| {
+                        for (id, label) in &providers {
+                            changed |= ui.selectable_value(provider, id.to_string(), *label).clicked();
+                        }
+                    });
+                }
+                None => { ui.label(""); }
+            }
+            ui.end_row();
+
+            changed |= override_toggle(ui, "Đọc TTS", &mut settings.use_tts, || defaults.use_tts);
+            match &mut settings.use_tts {
+                Some(use_tts) => { changed |= ui.checkbox(use_tts, "Bật đọc").changed(); }
+                None => { ui.label(""); }
+            }
+            ui.end_row();
+
+            changed |= override_toggle(ui, "Giọng đọc", &mut settings.tts_voice, || defaults.tts_language_code().to_string());
+            match &mut settings.tts_voice {
+                Some(voice) => {
+                    let label = config::LANGUAGES.iter().find(|(_, code)| *code == voice.as_str()).map_or(voice.clone(), |(name, _)| name.to_string());
+                    egui::ComboBox::from_id_source(format!("{}_voice", id_salt)).selected_text(label).width(180.0).show_ui(ui, |ui| {
+                        for (name, code) in config::LANGUAGES {
+                            changed |= ui.selectable_value(voice, code.to_string(), *name).clicked();
+                        }
+                    });
+                }
+                None => { ui.label(""); }
+            }
+            ui.end_row();
+
+            changed |= override_toggle(ui, "Overlay", &mut settings.show_overlay, || defaults.show_overlay);
+            match &mut settings.show_overlay {
+                Some(show) => { changed |= ui.checkbox(show, "Hiện văn bản").changed(); }
+                None => { ui.label(""); }
+            }
+            ui.end_row();
+
+            changed |= override_toggle(ui, "Copy", &mut settings.clipboard, || config::ClipboardMode::Translation);
+            match &mut settings.clipboard {
+                Some(mode) => {
+                    ui.horizontal(|ui| {
+                        changed |= ui.radio_value(mode, config::ClipboardMode::Off, "Không copy").changed();
+                        changed |= ui.radio_value(mode, config::ClipboardMode::Translation, "Bản dịch").changed();
+                        changed |= ui.radio_value(mode, config::ClipboardMode::Original, "Bản gốc").changed();
+                    });
+                }
+                None => { ui.label(""); }
+            }
+            ui.end_row();
+        });
+    });
+    changed
 }
 
 fn show_alert(message: &str) {
@@ -110,6 +177,25 @@ impl ReaderState {
     }
 }
 
+/// Mẫu mũi tên đang tạo từ vùng khoanh trên màn hình
+#[derive(Clone)]
+pub struct TemplateDraft {
+    // Ảnh chụp gốc và ảnh sau khi cắt / xoá nền
+    pub image: Option<image::RgbaImage>,
+    pub cleaned: Option<image::RgbaImage>,
+    pub preview: Option<egui::TextureHandle>,
+    pub auto_crop: bool,
+    pub transparent: bool,
+    pub tolerance: u8,
+    pub name: String,
+}
+
+impl TemplateDraft {
+    pub fn new() -> Self {
+        Self { image: None, cleaned: None, preview: None, auto_crop: true, transparent: true, tolerance: 40, name: String::new() }
+    }
+}
+
 #[derive(Clone)]
 pub struct UiState {
     pub show_popup: bool,
@@ -119,14 +205,32 @@ pub struct UiState {
     pub show_arrow_help: bool,
     pub show_password: bool,
     pub reader: ReaderState,
+    // Kết quả kiểm tra kết nối / danh sách model Ollama (cập nhật từ thread nền)
+    pub ollama_status: Arc<Mutex<String>>,
+    pub ollama_models: Arc<Mutex<Vec<String>>>,
+    // Cửa sổ quản lý thuật ngữ
+    pub show_glossary: bool,
+    pub glossary: glossary::Glossary,
+    pub glossary_status: String,
+    // Ảnh xem thử sau bước xử lý ảnh và dung lượng trước/sau
+    pub preprocess_preview: Option<egui::TextureHandle>,
+    pub preprocess_info: String,
+    // Thư viện ảnh mẫu mũi tên và ảnh xem trước (key = tên file)
+    pub arrow_library: templates::TemplateLibrary,
+    pub arrow_textures: HashMap<String, egui::TextureHandle>,
+    pub arrow_status: String,
+    // Ảnh vừa khoanh (thread chụp gửi về) và mẫu đang tạo
+    pub template_capture: Arc<Mutex<Option<image::RgbaImage>>>,
+    pub template_draft: TemplateDraft,
+    // Profile game (có sẵn + thư mục `profiles`)
+    pub profiles: Vec<profiles::GameProfile>,
+    pub profile_status: String,
 }
 
 #[derive(Clone)]
 pub struct ConfigState {
     pub config: config::Config,
-    pub gemini_api_key: String,
     pub current_prompt: String,
-    pub editing_prompt_index: Option<usize>,
     pub selected_api: String,
     pub use_tts: bool,
 }
@@ -138,37 +242,37 @@ pub struct HotkeyState {
     pub hotkey_instant: String,
     pub hotkey_auto: String,
     pub hotkey_toggle_auto: String,
+    pub hotkey_clear_context: String,
 }
 
 #[derive(Clone)]
-pub struct WwmState {
-    pub wwm_success_timer: Option<std::time::Instant>,
-    pub wwm_name_success_timer: Option<std::time::Instant>,
-    pub arrow_wwm_success_timer: Option<std::time::Instant>,
+pub struct GameState {
+    // Vùng đặt sẵn vừa chọn (thứ tự trong profile) và lúc chọn, để hiện "Đã chọn"
+    pub preset_success: Option<(usize, std::time::Instant)>,
     pub auto_translate_active: bool,
 }
 
 impl UiState {
     pub fn new() -> Self {
-        Self { show_popup: false, popup_text: String::new(), show_reset_confirm: false, show_arrow_window: false, show_arrow_help: false, show_password: false, reader: ReaderState::new(), }
+        Self { show_popup: false, popup_text: String::new(), show_reset_confirm: false, show_arrow_window: false, show_arrow_help: false, show_password: false, reader: ReaderState::new(), ollama_status: Arc::new(Mutex::new(String::new())), ollama_models: Arc::new(Mutex::new(Vec::new())), show_glossary: false, glossary: glossary::Glossary::default(), glossary_status: String::new(), preprocess_preview: None, preprocess_info: String::new(), arrow_library: templates::TemplateLibrary::default(), arrow_textures: HashMap::new(), arrow_status: String::new(), template_capture: Arc::new(Mutex::new(None)), template_draft: TemplateDraft::new(), profiles: profiles::GameProfile::load_all().0, profile_status: String::new(), }
     }
 }
 
 impl ConfigState {
     pub fn new(config: config::Config) -> Self {
-        Self { gemini_api_key: config.gemini_api_key.clone(), current_prompt: config.current_prompt.clone(), editing_prompt_index: None, selected_api: config.selected_api.clone(), use_tts: config.use_tts, config, }
+        Self { current_prompt: config.current_prompt.clone(), selected_api: config.selected_api.clone(), use_tts: config.use_tts, config, }
     }
 }
 
 impl HotkeyState {
     pub fn new(config: &config::Config) -> Self {
-        Self { hotkey_translate: config.hotkey_translate.clone(), hotkey_select: config.hotkey_select.clone(), hotkey_instant: config.hotkey_instant.clone(), hotkey_auto: config.hotkey_auto.clone(), hotkey_toggle_auto: config.hotkey_toggle_auto.clone(), }
+        Self { hotkey_translate: config.hotkey_translate.clone(), hotkey_select: config.hotkey_select.clone(), hotkey_instant: config.hotkey_instant.clone(), hotkey_auto: config.hotkey_auto.clone(), hotkey_toggle_auto: config.hotkey_toggle_auto.clone(), hotkey_clear_context: config.hotkey_clear_context.clone(), }
     }
 }
 
-impl WwmState {
+impl GameState {
     pub fn new() -> Self {
-        Self { wwm_success_timer: None, wwm_name_success_timer: None, arrow_wwm_success_timer: None, auto_translate_active: false, }
+        Self { preset_success: None, auto_translate_active: false, }
     }
 }
 
@@ -179,11 +283,13 @@ pub trait UiRenderer {
     fn render_hotkeys_section(&mut self, ui: &mut egui::Ui);
     fn render_aux_regions_section(&mut self, ui: &mut egui::Ui);
     fn render_settings_section(&mut self, ui: &mut egui::Ui);
-    fn render_wwm_section(&mut self, ctx: &egui::Context, ui: &mut egui::Ui);
+    fn render_preprocess_section(&mut self, ui: &mut egui::Ui);
+    fn render_game_section(&mut self, ctx: &egui::Context, ui: &mut egui::Ui);
     fn render_reader_window(&mut self, ctx: &egui::Context);
+    fn render_glossary_window(&mut self, ctx: &egui::Context);
+    fn render_arrow_templates_window(&mut self, ctx: &egui::Context);
     fn sync_config_from_file(&mut self);
     fn check_key_binding(&mut self);
-    fn load_texture(&mut self, ctx: &egui::Context, bytes: &[u8], is_arrow: bool);
 }
 
 impl UiRenderer for super::MainApp {
@@ -198,7 +304,7 @@ impl UiRenderer for super::MainApp {
                 self.is_paused = !listening;
                 crate::LISTENING_PAUSED.store(self.is_paused, Ordering::Relaxed);
                 if self.is_paused {
-                    self.wwm_state.auto_translate_active = false;
+                    self.game_state.auto_translate_active = false;
                     AUTO_TRANSLATE_ENABLED.store(false, Ordering::Relaxed);
                 }
             }
@@ -229,22 +335,22 @@ impl UiRenderer for super::MainApp {
             egui::Grid::new("api_grid").num_columns(2).spacing([20.0, 15.0]).striped(true).show(ui, |ui| {
                 ui.label("Dịch vụ:");
                 ui.horizontal(|ui| {
+                    let providers = translation::provider_labels();
+                    let selected_label = providers.iter().find(|(id, _)| *id == self.config_state.selected_api).map_or("?", |(_, label)| *label);
                     egui::ComboBox::from_id_source("api_selector")
-                        .selected_text(if self.config_state.selected_api == "gemini" { "Gemini (Không nên dùng)" } else { "Groq (Nhanh)" })
+                        .selected_text(selected_label)
                         .width(200.0)
                         .show_ui(ui, |ui| {
-                            if ui.selectable_value(&mut self.config_state.selected_api, "gemini".to_string(), "Gemini (Không nên dùng)").clicked() {
-                                self.config_state.config.selected_api = self.config_state.selected_api.clone();
-                                self.config_state.config.save().unwrap();
-                            }
-                            if ui.selectable_value(&mut self.config_state.selected_api, "groq".to_string(), "Groq (Meta Llama)").clicked() {
-                                self.config_state.config.selected_api = self.config_state.selected_api.clone();
-                                self.config_state.config.save().unwrap();
+                            for (id, label) in &providers {
+                                if ui.selectable_value(&mut self.config_state.selected_api, id.to_string(), *label).clicked() {
+                                    self.config_state.config.selected_api = self.config_state.selected_api.clone();
+                                    self.config_state.config.save().unwrap();
+                                }
                             }
                         });
 
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                        let help_text = if self.config_state.selected_api == "gemini" { "❓ Hướng dẫn (Gemini)" } else { "❓ Hướng dẫn (Groq)" };
+                        let help_text = match self.config_state.selected_api.as_str() { "gemini" => "❓ Hướng dẫn (Gemini)", "openai" => "❓ Hướng dẫn (OpenAI)", "ollama" => "❓ Hướng dẫn (Ollama)", _ => "❓ Hướng dẫn (Groq)" };
                         if ui.add(egui::Button::new(egui::RichText::new(help_text).small())).clicked() {
                             self.ui_state.show_popup = true;
                             self.ui_state.popup_text = self.config_state.selected_api.clone();
@@ -256,89 +362,372 @@ impl UiRenderer for super::MainApp {
                 ui.label("API Key:");
                 ui.vertical(|ui| {
                       let show_pass = self.ui_state.show_password;
-                      if self.config_state.selected_api == "gemini" {
-                          if ui.add(egui::TextEdit::singleline(&mut self.config_state.gemini_api_key).password(!show_pass).desired_width(250.0)).changed() {
-                              self.config_state.config.gemini_api_key = self.config_state.gemini_api_key.clone();
-                              self.config_state.config.save().unwrap();
-                          }
+                      let api = self.config_state.selected_api.clone();
+                      let provider = translation::get_provider(&api, &self.config_state.config);
+                      let capabilities = provider.as_ref().map(|p| p.capabilities());
+                      let needs_key = capabilities.map_or(true, |c| c.needs_api_key);
+                      let daily_limit = provider.as_ref().and_then(|p| p.rate_limit().requests_per_day);
+                      if !capabilities.map_or(true, |c| c.uses_api_key) {
+                          ui.label(egui::RichText::new("Không cần Key (chạy trên máy)").italics());
                       } else {
-                          if self.config_state.config.groq_api_keys.is_empty() {
-                              self.config_state.config.groq_api_keys.push(String::new());
+                          let pool = self.config_state.config.key_pool_mut(&api);
+                          if pool.keys.is_empty() { pool.keys.push(config::ApiKey::default()); }
+                          let active = pool.active;
+                          let mut changed = false;
+                          let mut remove_index = None;
+                          let key_total = pool.keys.len();
+                          for (i, key) in pool.keys.iter_mut().enumerate() {
+                              ui.horizontal(|ui| {
+                                  let hint = if needs_key { "API Key" } else { "Để trống nếu server local" };
+                                  if ui.add(egui::TextEdit::singleline(&mut key.key).password(!show_pass).hint_text(hint).desired_width(200.0)).changed() {
+                                      changed = true;
+                                  }
+                                  if key_total > 1 && ui.small_button("❌").clicked() { remove_index = Some(i); }
+                              });
+                              if key.key.trim().is_empty() { continue; }
+                              // Ưu tiên số lượt server báo về, không có thì tự tính theo giới hạn/ngày của provider
+                              let remaining = key.remaining.or_else(|| daily_limit.map(|limit| limit as i32 - key.used_today() as i32));
+                              let mut status = format!("Hôm nay: {} request", key.used_today());
+                              if let Some(rem) = remaining { status.push_str(&format!(" | Còn lại: {}", rem.max(0))); }
+                              if key.is_cooling_down() { status.push_str(" | ⏳ Đang nghỉ (429)"); }
+                              if i == active && key_total > 1 { status.push_str(" | ▶ Đang dùng"); }
+                              ui.label(egui::RichText::new(status).small().weak());
                           }
-                          if let Some(key) = self.config_state.config.groq_api_keys.get_mut(0) {
-                              if ui.add(egui::TextEdit::singleline(key).password(!show_pass).desired_width(250.0)).changed() {
-                                  self.config_state.config.save().unwrap();
-                              }
+                          if let Some(i) = remove_index {
+                              pool.keys.remove(i);
+                              if pool.active >= pool.keys.len() { pool.active = 0; }
+                              changed = true;
                           }
+                          ui.horizontal(|ui| {
+                              if ui.small_button("➕ Thêm key").clicked() {
+                                  pool.keys.push(config::ApiKey::default());
+                                  changed = true;
+                              }
+                              if pool.keys.len() > 1 {
+                                  changed |= ui.radio_value(&mut pool.strategy, config::KeyStrategy::RoundRobin, "Lần lượt").changed();
+                                  changed |= ui.radio_value(&mut pool.strategy, config::KeyStrategy::LeastUsed, "Ít dùng nhất").changed();
+                              }
+                          });
+                          if changed { self.config_state.config.save().unwrap(); }
                       }
                       if ui.button(if self.ui_state.show_password { "🙈 Ẩn Key" } else { "👁 Hiện Key" }).clicked() {
                           self.ui_state.show_password = !self.ui_state.show_password;
                       }
                  });
                  ui.end_row();
+
+                 if self.config_state.selected_api == "ollama" {
+                     ui.label("Ollama URL:");
+                     if ui.add(egui::TextEdit::singleline(&mut self.config_state.config.ollama_base_url).hint_text("http://localhost:11434").desired_width(250.0)).changed() {
+                         self.config_state.config.save().unwrap();
+                     }
+                     ui.end_row();
+
+                     ui.label("Model:");
+                     ui.horizontal(|ui| {
+                         let models = self.ui_state.ollama_models.lock().unwrap().clone();
+                         egui::ComboBox::from_id_source("ollama_model_selector")
+                             .selected_text(self.config_state.config.ollama_model.clone())
+                             .width(180.0)
+                             .show_ui(ui, |ui| {
+                                 for model in &models {
+                                     if ui.selectable_value(&mut self.config_state.config.ollama_model, model.clone(), model.as_str()).clicked() {
+                                         self.config_state.config.save().unwrap();
+                                     }
+                                 }
+                             });
+                         if ui.button("🔄").on_hover_text("Tải danh sách model").clicked() {
+                             let provider = translation::OllamaProvider::from_config(&self.config_state.config);
+                             let models_out = self.ui_state.ollama_models.clone();
+                             let status_out = self.ui_state.ollama_status.clone();
+                             std::thread::spawn(move || {
+                                 let rt = tokio::runtime::Runtime::new().unwrap();
+                                 match rt.block_on(provider.list_models()) {
+                                     Ok(list) => { *status_out.lock().unwrap() = format!("Tìm thấy {} model", list.len()); *models_out.lock().unwrap() = list; }
+                                     Err(e) => { *status_out.lock().unwrap() = format!("❌ {}", e); }
+                                 }
+                             });
+                         }
+                     });
+                     ui.end_row();
+
+                     ui.label("Kết nối:");
+                     ui.horizontal(|ui| {
+                         if ui.button("🔌 Kiểm tra").clicked() {
+                             let provider = translation::OllamaProvider::from_config(&self.config_state.config);
+                             let status_out = self.ui_state.ollama_status.clone();
+                             *status_out.lock().unwrap() = "Đang kiểm tra...".to_string();
+                             std::thread::spawn(move || {
+                                 let rt = tokio::runtime::Runtime::new().unwrap();
+                                 let msg = match rt.block_on(provider.test_connection()) {
+                                     Ok(msg) => format!("✅ {}", msg),
+                                     Err(e) => format!("❌ {}", e),
+                                 };
+                                 *status_out.lock().unwrap() = msg;
+                             });
+                         }
+                         ui.label(self.ui_state.ollama_status.lock().unwrap().clone());
+                     });
+                     ui.end_row();
+                 }
+
+                 if self.config_state.selected_api == "openai" {
+                     ui.label("Base URL:");
+                     if ui.add(egui::TextEdit::singleline(&mut self.config_state.config.openai_base_url).hint_text("http://localhost:1234/v1").desired_width(250.0)).changed() {
+                         self.config_state.config.save().unwrap();
+                     }
+                     ui.end_row();
+
+                     ui.label("Model:");
+                     if ui.add(egui::TextEdit::singleline(&mut self.config_state.config.openai_model).hint_text("vd: qwen2-vl-7b-instruct").desired_width(250.0)).changed() {
+                         self.config_state.config.save().unwrap();
+                     }
+                     ui.end_row();
+
+                     ui.label("Temperature:");
+                     if ui.add(egui::Slider::new(&mut self.config_state.config.openai_temperature, 0.0..=2.0)).changed() {
+                         self.config_state.config.save().unwrap();
+                     }
+                     ui.end_row();
+
+                     ui.label("Header thêm:");
+                     ui.vertical(|ui| {
+                         let mut remove_idx = None;
+                         let mut changed = false;
+                         for (i, header) in self.config_state.config.openai_extra_headers.iter_mut().enumerate() {
+                             ui.horizontal(|ui| {
+                                 changed |= ui.add(egui::TextEdit::singleline(&mut header.name).hint_text("Tên").desired_width(100.0)).changed();
+                                 changed |= ui.add(egui::TextEdit::singleline(&mut header.value).hint_text("Giá trị").desired_width(120.0)).changed();
+                                 if ui.button("🗑").clicked() { remove_idx = Some(i); }
+                             });
+                         }
+                         if let Some(i) = remove_idx {
+                             self.config_state.config.openai_extra_headers.remove(i);
+                             changed = true;
+                         }
+                         if ui.button("➕ Thêm header").clicked() {
+                             self.config_state.config.openai_extra_headers.push(config::HttpHeader { name: String::new(), value: String::new() });
+                             changed = true;
+                         }
+                         if changed { self.config_state.config.save().unwrap(); }
+                     });
+                     ui.end_row();
+                 }
+
+                 ui.label("OCR trên máy:");
+                 ui.vertical(|ui| {
+                     if ui.add(egui::Checkbox::new(&mut self.config_state.config.use_ocr_pipeline, "Tesseract OCR trước, chỉ gửi text đi dịch")).changed() {
+                         self.config_state.config.save().unwrap();
+                     }
+                     if self.config_state.config.use_ocr_pipeline {
+                         ui.horizontal(|ui| {
+                             ui.label("tesseract.exe:");
+                             if ui.add(egui::TextEdit::singleline(&mut self.config_state.config.tesseract_path).desired_width(150.0)).changed() {
+                                 self.config_state.config.save().unwrap();
+                             }
+                             if ui.button("📂").clicked() {
+                                 if let Some(path) = rfd::FileDialog::new().add_filter("Tesseract", &["exe"]).pick_file() {
+                                     self.config_state.config.tesseract_path = path.to_string_lossy().to_string();
+                                     self.config_state.config.save().unwrap();
+                                 }
+                             }
+                         });
+                         ui.horizontal(|ui| {
+                             ui.label("Ngôn ngữ:");
+                             if ui.add(egui::TextEdit::singleline(&mut self.config_state.config.ocr_language).hint_text("chi_sim+eng").desired_width(120.0)).changed() {
+                                 self.config_state.config.save().unwrap();
+                             }
+                         });
+                         ui.horizontal(|ui| {
+                             ui.label("Dịch text bằng:");
+                             let providers = translation::provider_labels();
+                             let selected_label = if self.config_state.config.ocr_translator.is_empty() { "(Giống dịch vụ đang chọn)" } else {
+                                 providers.iter().find(|(id, _)| *id == self.config_state.config.ocr_translator).map_or("?", |(_, label)| *label)
+                             };
+                             egui::ComboBox::from_id_source("ocr_translator_selector")
+                                 .selected_text(selected_label)
+                                 .width(180.0)
+                                 .show_ui(ui, |ui| {
+                                     if ui.selectable_value(&mut self.config_state.config.ocr_translator, String::new(), "(Giống dịch vụ đang chọn)").clicked() {
+                                         self.config_state.config.save().unwrap();
+                                     }
+                                     for (id, label) in &providers {
+                                         if ui.selectable_value(&mut self.config_state.config.ocr_translator, id.to_string(), *label).clicked() {
+                                             self.config_state.config.save().unwrap();
+                                         }
+                                     }
+                                 });
+                         });
+                     }
+                 });
+                 ui.end_row();
+
+                 ui.label("Mạng:");
+                 ui.horizontal(|ui| {
+                     ui.label("Timeout:");
+                     if ui.add(egui::DragValue::new(&mut self.config_state.config.http_timeout_secs).clamp_range(5..=300).suffix("s")).changed() {
+                         self.config_state.config.save().unwrap();
+                     }
+                     ui.label("Thử lại:");
+                     if ui.add(egui::DragValue::new(&mut self.config_state.config.http_max_retries).clamp_range(0..=5)).changed() {
+                         self.config_state.config.save().unwrap();
+                     }
+                     ui.label("Dịch song song:").on_hover_text("Số vùng cố định được dịch cùng lúc");
+                     if ui.add(egui::DragValue::new(&mut self.config_state.config.max_parallel_regions).clamp_range(1..=8)).changed() {
+                         self.config_state.config.save().unwrap();
+                     }
+                     if ui.checkbox(&mut self.config_state.config.batch_regions, "Gộp vùng").on_hover_text("Gửi mọi vùng cố định trong 1 request nhiều ảnh để đỡ tốn lượt. Provider không nhận thì tự dịch từng vùng.").changed() {
+                         self.config_state.config.save().unwrap();
+                     }
+                 });
+                 ui.end_row();
             });
         });
     }
 
     fn render_prompt_section(&mut self, ui: &mut egui::Ui) {
         egui::CollapsingHeader::new(egui::RichText::new("📝 Cấu hình Dịch (Prompt)").strong()).default_open(true).show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Dịch sang:");
+                let mut lang_changed = false;
+                egui::ComboBox::from_id_source("target_language")
+                    .selected_text(self.config_state.config.target_language.clone())
+                    .width(150.0)
+                    .show_ui(ui, |ui| {
+                        for (name, _) in config::LANGUAGES {
+                            lang_changed |= ui.selectable_value(&mut self.config_state.config.target_language, name.to_string(), *name).clicked();
+                        }
+                    });
+                ui.label("Từ:");
+                let source_label = if self.config_state.config.source_language.is_empty() { "(Tự nhận diện)".to_string() } else { self.config_state.config.source_language.clone() };
+                egui::ComboBox::from_id_source("source_language")
+                    .selected_text(source_label)
+                    .width(150.0)
+                    .show_ui(ui, |ui| {
+                        lang_changed |= ui.selectable_value(&mut self.config_state.config.source_language, String::new(), "(Tự nhận diện)").clicked();
+                        for (name, _) in config::LANGUAGES {
+                            lang_changed |= ui.selectable_value(&mut self.config_state.config.source_language, name.to_string(), *name).clicked();
+                        }
+                    });
+                if lang_changed {
+                    tts::set_language(self.config_state.config.tts_language_code());
+                    self.config_state.config.save().unwrap();
+                }
+            });
+            let variables_hint = prompt::VARIABLES.iter().map(|(v, _)| *v).collect::<Vec<_>>().join(" ");
+            ui.label(egui::RichText::new(format!("Biến dùng được trong prompt: {}", variables_hint)).small().weak())
+                .on_hover_text(prompt::VARIABLES.iter().map(|(v, d)| format!("{} = {}", v, d)).collect::<Vec<_>>().join("\n"));
             ui.horizontal_wrapped(|ui| {
                 if ui.button("🗡️ Kiếm hiệp").clicked() {
                     self.config_state.current_prompt = config::Config::get_wuxia_prompt();
                     self.config_state.config.current_prompt = self.config_state.current_prompt.clone();
-                    self.config_state.editing_prompt_index = None;
+                    self.config_state.config.active_prompt = None;
                     self.config_state.config.save().unwrap();
                 }
                 if ui.button("🌍 Thông thường").clicked() {
                     self.config_state.current_prompt = config::Config::get_normal_prompt();
                     self.config_state.config.current_prompt = self.config_state.current_prompt.clone();
-                    self.config_state.editing_prompt_index = None;
+                    self.config_state.config.active_prompt = None;
                     self.config_state.config.save().unwrap();
                 }
                 if ui.button("🔍 Phân tích hình ảnh").clicked() {
                     self.config_state.current_prompt = config::Config::get_analyze_prompt();
                     self.config_state.config.current_prompt = self.config_state.current_prompt.clone();
-                    self.config_state.editing_prompt_index = None;
+                    self.config_state.config.active_prompt = None;
                     self.config_state.config.save().unwrap();
                 }
                 if ui.button("abc Xuất chữ").clicked() {
                     self.config_state.current_prompt = config::Config::get_extract_text_prompt();
                     self.config_state.config.current_prompt = self.config_state.current_prompt.clone();
-                    self.config_state.editing_prompt_index = None;
+                    self.config_state.config.active_prompt = None;
                     self.config_state.config.save().unwrap();
                 }
                 let mut to_select = None;
-                for (i, _) in self.config_state.config.saved_prompts.iter().enumerate() {
-                    let btn_label = format!("Mẫu {}", i + 1);
-                    let is_selected = self.config_state.editing_prompt_index == Some(i);
-                    if ui.add(egui::Button::new(btn_label).selected(is_selected)).clicked() {
+                for (i, saved) in self.config_state.config.saved_prompts.iter().enumerate() {
+                    let btn_label = if saved.name.trim().is_empty() { format!("Mẫu {}", i + 1) } else { saved.name.clone() };
+                    let is_selected = self.config_state.config.active_prompt == Some(i);
+                    let mut btn = ui.add(egui::Button::new(btn_label).selected(is_selected));
+                    if !saved.description.is_empty() || !saved.tags.is_empty() {
+                        btn = btn.on_hover_text(format!("{}\n{}", saved.description, saved.tags.iter().map(|t| format!("#{}", t)).collect::<Vec<_>>().join(" ")));
+                    }
+                    if btn.clicked() {
                         to_select = Some(i);
                     }
                 }
                 if let Some(i) = to_select {
-                    self.config_state.editing_prompt_index = Some(i);
+                    self.config_state.config.active_prompt = Some(i);
                     self.config_state.current_prompt = self.config_state.config.saved_prompts[i].content.clone();
                     self.config_state.config.current_prompt = self.config_state.current_prompt.clone();
                     self.config_state.config.save().unwrap();
                 }
                 if ui.button("➕").clicked() {
-                    self.config_state.config.saved_prompts.push(config::CustomPrompt {
-                        content: String::new(),
-                    });
-                    self.config_state.editing_prompt_index = Some(self.config_state.config.saved_prompts.len() - 1);
+                    let name = format!("Mẫu {}", self.config_state.config.saved_prompts.len() + 1);
+                    self.config_state.config.saved_prompts.push(config::CustomPrompt { name, ..Default::default() });
+                    self.config_state.config.active_prompt = Some(self.config_state.config.saved_prompts.len() - 1);
                     self.config_state.current_prompt = String::new();
                     self.config_state.config.save().unwrap();
                 }
+                if ui.button("📥").on_hover_text("Nhập bộ prompt (JSON)").clicked() {
+                    if let Some(path) = rfd::FileDialog::new().add_filter("JSON", &["json"]).pick_file() {
+                        match prompt::import_library(&mut self.config_state.config.saved_prompts, &path) {
+                            Ok(count) => { self.config_state.config.save().unwrap(); show_alert(&format!("Đã nhập {} prompt", count)); },
+                            Err(e) => show_alert(&format!("Không đọc được file prompt: {}", e)),
+                        }
+                    }
+                }
+                if ui.button("📤").on_hover_text("Xuất bộ prompt (JSON)").clicked() {
+                    if let Some(path) = rfd::FileDialog::new().add_filter("JSON", &["json"]).set_file_name("prompts.json").save_file() {
+                        if let Err(e) = prompt::export_library(&self.config_state.config.saved_prompts, &path) {
+                            show_alert(&format!("Không ghi được file: {}", e));
+                        }
+                    }
+                }
             });
             ui.add_space(5.0);
-            if let Some(idx) = self.config_state.editing_prompt_index {
+            if let Some(idx) = self.config_state.config.active_prompt {
                 if idx < self.config_state.config.saved_prompts.len() {
+                    let mut changed = false;
+                    let providers = translation::provider_labels();
+                    let saved = &mut self.config_state.config.saved_prompts[idx];
+                    egui::Grid::new("prompt_meta_grid").num_columns(2).spacing([10.0, 4.0]).show(ui, |ui| {
+                        ui.label("Tên:");
+                        changed |= ui.add(egui::TextEdit::singleline(&mut saved.name).desired_width(250.0)).changed();
+                        ui.end_row();
+                        ui.label("Mô tả:");
+                        changed |= ui.add(egui::TextEdit::singleline(&mut saved.description).desired_width(250.0)).changed();
+                        ui.end_row();
+                        ui.label("Tags:");
+                        let mut tags = saved.tags.join(", ");
+                        if ui.add(egui::TextEdit::singleline(&mut tags).hint_text("wuxia, wwm").desired_width(250.0)).changed() {
+                            saved.tags = tags.split(',').map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect();
+                            changed = true;
+                        }
+                        ui.end_row();
+                        ui.label("Dịch vụ:");
+                        ui.horizontal(|ui| {
+                            let selected_label = if saved.provider.is_empty() { "(Theo cài đặt chung)" } else {
+                                providers.iter().find(|(id, _)| *id == saved.provider).map_or("?", |(_, label)| *label)
+                            };
+                            egui::ComboBox::from_id_source("prompt_provider")
+                                .selected_text(selected_label)
+                                .width(160.0)
+                                .show_ui(ui, |ui| {
+                                    changed |= ui.selectable_value(&mut saved.provider, String::new(), "(Theo cài đặt chung)").clicked();
+                                    for (id, label) in &providers {
+                                        changed |= ui.selectable_value(&mut saved.provider, id.to_string(), *label).clicked();
+                                    }
+                                });
+                            changed |= ui.add(egui::TextEdit::singleline(&mut saved.model).hint_text("Model (tùy chọn)").desired_width(150.0)).changed();
+                        });
+                        ui.end_row();
+                    });
+                    if changed { self.config_state.config.save().unwrap(); }
                     ui.horizontal(|ui| {
-                        ui.label(egui::RichText::new(format!("Đang sửa: Mẫu {}", idx + 1)).italics());
+                        ui.label(egui::RichText::new(format!("Đang sửa: {}", self.config_state.config.saved_prompts[idx].name)).italics());
                         ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                             if ui.add(egui::Button::new(egui::RichText::new("🗑").color(egui::Color32::RED))).clicked() {
                                 self.config_state.config.saved_prompts.remove(idx);
-                                self.config_state.editing_prompt_index = None;
+                                self.config_state.config.active_prompt = None;
                                 self.config_state.current_prompt = config::Config::get_normal_prompt();
                                 self.config_state.config.current_prompt = self.config_state.current_prompt.clone();
                                 self.config_state.config.save().unwrap();
@@ -350,13 +739,32 @@ impl UiRenderer for super::MainApp {
             if ui.add(egui::TextEdit::multiline(&mut self.config_state.current_prompt).desired_rows(4).desired_width(f32::INFINITY)).changed() {
                 self.config_state.config.current_prompt = self.config_state.current_prompt.clone();
                 self.config_state.config.save().unwrap();
-                if let Some(idx) = self.config_state.editing_prompt_index {
+                if let Some(idx) = self.config_state.config.active_prompt {
                     if idx < self.config_state.config.saved_prompts.len() {
                         self.config_state.config.saved_prompts[idx].content = self.config_state.current_prompt.clone();
                         self.config_state.config.save().unwrap();
                     }
                 }
             }
+            ui.horizontal(|ui| {
+                ui.label("Ngữ cảnh hội thoại:");
+                if ui.add(egui::Slider::new(&mut self.config_state.config.context_window, 0..=10).text("câu trước")).changed() {
+                    self.config_state.config.save().unwrap();
+                }
+                if ui.small_button("🧹 Xóa").on_hover_text("Xóa ngữ cảnh khi đổi cảnh").clicked() {
+                    context::clear();
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.add(egui::Checkbox::new(&mut self.config_state.config.use_glossary, "Dùng thuật ngữ")).changed() {
+                    self.config_state.config.save().unwrap();
+                }
+                if ui.button("📚 Bảng thuật ngữ").clicked() {
+                    self.ui_state.glossary = glossary::Glossary::load();
+                    self.ui_state.glossary_status.clear();
+                    self.ui_state.show_glossary = true;
+                }
+            });
         });
     }
 
@@ -386,6 +794,7 @@ impl UiRenderer for super::MainApp {
                  draw_bind_btn("Chọn vùng dịch:", BindingTarget::Select, &self.hotkey_state.hotkey_select);
                  draw_bind_btn("Chụp & Dịch ngay:", BindingTarget::Instant, &self.hotkey_state.hotkey_instant);
                  draw_bind_btn("Bật/Tắt Tự động dịch:", BindingTarget::ToggleAuto, &self.hotkey_state.hotkey_toggle_auto);
+                 draw_bind_btn("Xóa ngữ cảnh hội thoại:", BindingTarget::ClearContext, &self.hotkey_state.hotkey_clear_context);
             });
         });
     }
@@ -400,12 +809,23 @@ impl UiRenderer for super::MainApp {
                     region: None,
                     hotkey_select: "NONE".to_string(),
                     hotkey_translate: "NONE".to_string(),
+                    settings: config::RegionSettings::default(),
                 });
                 self.config_state.config.save().unwrap();
                 HOTKEYS_NEED_UPDATE.store(true, Ordering::Relaxed);
             }
             ui.add_space(5.0);
+            ui.group(|ui| {
+                ui.label(egui::RichText::new("Vùng dịch chính").strong());
+                let defaults = self.config_state.config.clone();
+                if region_settings_editor(ui, "main_region", &mut self.config_state.config.main_region_settings, &defaults) {
+                    self.config_state.config.save().unwrap();
+                }
+            });
+            ui.add_space(2.0);
             let mut remove_idx = None;
+            let mut settings_changed = false;
+            let defaults = self.config_state.config.clone();
             for (i, aux) in self.config_state.config.aux_regions.iter_mut().enumerate() {
                 ui.group(|ui| {
                     ui.horizontal(|ui| {
@@ -427,11 +847,17 @@ impl UiRenderer for super::MainApp {
                             self.binding_target = Some(BindingTarget::AuxTranslate(i));
                             IS_BINDING_MODE.store(true, Ordering::Relaxed);
                         }
-                        if aux.region.is_some() { ui.label("✅ Đã có vùng"); } else { ui.label("⚠️ Chưa chọn vùng"); }
+                        match aux.region.as_ref().map(|r| r.anchor.as_ref()) {
+                            Some(Some(anchor)) => { ui.label(format!("✅ Đã có vùng 🔗 {}", anchor.target())); }
+                            Some(None) => { ui.label("✅ Đã có vùng"); }
+                            None => { ui.label("⚠️ Chưa chọn vùng"); }
+                        }
                     });
+                    settings_changed |= region_settings_editor(ui, &format!("aux_{}", i), &mut aux.settings, &defaults);
                 });
                 ui.add_space(2.0);
             }
+            if settings_changed { self.config_state.config.save().unwrap(); }
             if let Some(i) = remove_idx {
                 self.config_state.config.aux_regions.remove(i);
                 self.config_state.config.save().unwrap();
@@ -448,6 +874,9 @@ impl UiRenderer for super::MainApp {
                     if ui.add(egui::Checkbox::new(&mut self.config_state.config.show_overlay, "Hiện văn bản")).changed() {
                         self.config_state.config.save().unwrap();
                     }
+                    if ui.add_enabled(self.config_state.config.show_overlay, egui::Checkbox::new(&mut self.config_state.config.stream_translation, "Hiện dần khi đang dịch (stream)")).changed() {
+                        self.config_state.config.save().unwrap();
+                    }
                     ui.horizontal(|ui| {
                         ui.label("Cỡ chữ:");
                         if ui.add_enabled(self.config_state.config.show_overlay, egui::Slider::new(&mut self.config_state.config.overlay_font_size, FONT_SIZE_MIN as i32..=FONT_SIZE_MAX as i32).text("px")).changed() {
@@ -469,6 +898,9 @@ impl UiRenderer for super::MainApp {
                             self.config_state.config.save().unwrap();
                         }
                     });
+                    if ui.add_enabled(self.config_state.use_tts && self.config_state.config.stream_translation, egui::Checkbox::new(&mut self.config_state.config.tts_first_sentence, "Đọc ngay câu đầu tiên")).on_hover_text("Khi stream, đọc câu đầu tiên luôn thay vì chờ dịch xong cả đoạn").changed() {
+                        self.config_state.config.save().unwrap();
+                    }
                 });
                 ui.end_row();
                 ui.label("Tùy chọn khác:");
@@ -476,6 +908,10 @@ impl UiRenderer for super::MainApp {
                     if ui.add(egui::Checkbox::new(&mut self.config_state.config.freeze_screen, "Đóng băng khi chọn vùng")).changed() {
                         self.config_state.config.save().unwrap();
                     }
+                    if ui.add(egui::Checkbox::new(&mut self.config_state.config.bind_regions_to_window, "Gắn vùng mới vào cửa sổ game"))
+                        .on_hover_text("Vùng khoanh trên cửa sổ game sẽ đi theo cửa sổ khi di chuyển hoặc đổi kích thước").changed() {
+                        self.config_state.config.save().unwrap();
+                    }
                 });
                 ui.end_row();
                 ui.label("Copy Text:");
@@ -494,13 +930,127 @@ impl UiRenderer for super::MainApp {
                     }
                 });
                 ui.end_row();
+                ui.label("Cache dịch:");
+                ui.vertical(|ui| {
+                    if ui.add(egui::Checkbox::new(&mut self.config_state.config.use_translation_cache, "Dùng lại bản dịch khi khung hình không đổi")).changed() {
+                        self.config_state.config.save().unwrap();
+                    }
+                    if self.config_state.config.use_translation_cache {
+                        if ui.add(egui::Checkbox::new(&mut self.config_state.config.translation_cache_on_disk, "Lưu cache ra ổ đĩa")).changed() {
+                            self.config_state.config.save().unwrap();
+                        }
+                        ui.horizontal(|ui| {
+                            ui.label("Số mục tối đa:");
+                            if ui.add(egui::DragValue::new(&mut self.config_state.config.translation_cache_size).clamp_range(10..=5000)).changed() {
+                                self.config_state.config.save().unwrap();
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            let (hits, misses) = cache::stats();
+                            ui.label(egui::RichText::new(format!("Hit: {} | Miss: {}", hits, misses)).weak());
+                            if ui.button("🗑 Xóa cache").clicked() {
+                                cache::clear();
+                            }
+                        });
+                    }
+                });
+                ui.end_row();
+            });
+        });
+    }
+
+    fn render_preprocess_section(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new(egui::RichText::new("🖼 Xử lý ảnh trước khi gửi").strong()).default_open(false).show(ui, |ui| {
+            let mut changed = false;
+            let settings = &mut self.config_state.config.preprocess;
+            changed |= ui.checkbox(&mut settings.enabled, "Bật xử lý ảnh").on_hover_text("Thu nhỏ / lọc màu / nén ảnh để gửi nhanh hơn và OCR dễ đọc chữ hơn").changed();
+            ui.add_enabled_ui(settings.enabled, |ui| {
+                egui::Grid::new("preprocess_grid").num_columns(2).spacing([20.0, 6.0]).show(ui, |ui| {
+                    ui.label("Cạnh dài tối đa:");
+                    changed |= ui.add(egui::DragValue::new(&mut settings.max_dimension).clamp_range(0..=4096).suffix("px")).on_hover_text("0 = giữ nguyên kích thước").changed();
+                    ui.end_row();
+
+                    ui.label("Màu:");
+                    changed |= ui.checkbox(&mut settings.grayscale, "Ảnh xám").changed();
+                    ui.end_row();
+
+                    ui.label("Tương phản:");
+                    changed |= ui.add(egui::Slider::new(&mut settings.contrast, -50.0..=100.0).suffix("%")).changed();
+                    ui.end_row();
+
+                    ui.label("Ngưỡng đen/trắng:");
+                    changed |= ui.add(egui::Slider::new(&mut settings.threshold, 0..=255)).on_hover_text("0 = tắt. Hợp với chữ game mờ trên nền nhiễu").changed();
+                    ui.end_row();
+
+                    ui.label("Xoá màu nền:");
+                    ui.horizontal(|ui| {
+                        let mut keyed = settings.color_key.is_some();
+                        if ui.checkbox(&mut keyed, "").changed() {
+                            settings.color_key = if keyed { Some([0, 0, 0]) } else { None };
+                            changed = true;
+                        }
+                        if let Some(key) = settings.color_key.as_mut() {
+                            changed |= ui.color_edit_button_srgb(key).changed();
+                            ui.label("Lệch:");
+                            changed |= ui.add(egui::DragValue::new(&mut settings.color_key_tolerance).clamp_range(0..=255)).changed();
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Định dạng:");
+                    ui.horizontal(|ui| {
+                        changed |= ui.radio_value(&mut settings.encoding, config::ImageEncoding::Png, "PNG").changed();
+                        changed |= ui.radio_value(&mut settings.encoding, config::ImageEncoding::Jpeg, "JPEG").changed();
+                        changed |= ui.radio_value(&mut settings.encoding, config::ImageEncoding::WebP, "WebP").on_hover_text("WebP nén mất dữ liệu, thường nhỏ hơn JPEG").changed();
+                    });
+                    ui.end_row();
+
+                    if settings.encoding != config::ImageEncoding::Png {
+                        ui.label("Chất lượng nén:");
+                        changed |= ui.add(egui::Slider::new(&mut settings.quality, 10..=100)).changed();
+                        ui.end_row();
+                    }
+                });
+            });
+            if changed { self.config_state.config.save().unwrap(); }
+
+            ui.horizontal(|ui| {
+                let region = self.config_state.config.fixed_regions.first().cloned();
+                if ui.add_enabled(region.is_some(), egui::Button::new("👁 Xem thử")).on_disabled_hover_text("Chưa chọn vùng dịch").clicked() {
+                    if let Some(region) = region {
+                        let settings = &self.config_state.config.preprocess;
+                        let result = capture::capture_raw(&region).and_then(|img| {
+                            let before = preprocess::encode(&img, config::ImageEncoding::Png, settings.quality)?.len();
+                            let processed = if settings.enabled { preprocess::apply(img, settings) } else { img };
+                            let encoding = if settings.enabled { settings.encoding } else { config::ImageEncoding::Png };
+                            let after = preprocess::encode(&processed, encoding, settings.quality)?.len();
+                            Ok((processed, before, after))
+                        });
+                        match result {
+                            Ok((processed, before, after)) => {
+                                let rgba = processed.to_rgba8();
+                                let size = [rgba.width() as usize, rgba.height() as usize];
+                                let image_data = egui::ColorImage::from_rgba_unmultiplied(size, rgba.as_raw());
+                                self.ui_state.preprocess_preview = Some(ui.ctx().load_texture("preprocess_preview", image_data, egui::TextureOptions::default()));
+                                self.ui_state.preprocess_info = format!("{}x{} · {:.1} KB -> {:.1} KB", size[0], size[1], before as f32 / 1024.0, after as f32 / 1024.0);
+                            }
+                            Err(e) => self.ui_state.preprocess_info = format!("Lỗi: {}", e),
+                        }
+                    }
+                }
+                if !self.ui_state.preprocess_info.is_empty() { ui.label(&self.ui_state.preprocess_info); }
             });
+            if let Some(texture) = &self.ui_state.preprocess_preview {
+                let size = texture.size_vec2();
+                let scale = (ui.available_width() / size.x).min(1.0);
+                ui.image((texture.id(), size * scale));
+            }
         });
     }
 
-    fn render_wwm_section(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
+    fn render_game_section(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
         ui.vertical_centered(|ui| {
-            egui::CollapsingHeader::new(egui::RichText::new("🎮 Dịch Where Winds Meet").strong()).default_open(true).show(ui, |ui| {
+            egui::CollapsingHeader::new(egui::RichText::new("🎮 Dịch theo game").strong()).default_open(true).show(ui, |ui| {
                 ui.add_space(5.0);
                 ui.horizontal(|ui| {
                     ui.label("Phím tắt chọn vùng Mũi tên:");
@@ -512,115 +1062,163 @@ impl UiRenderer for super::MainApp {
                     ui.add_space(10.0);
                     if ui.button("❓").clicked() { self.ui_state.show_arrow_help = true; }
                 });
+                // Chọn game: profile có sẵn và các file .json trong thư mục `profiles`
                 ui.add_space(5.0);
                 ui.horizontal(|ui| {
-                    ui.with_layout(egui::Layout::left_to_right(egui::Align::Center).with_main_align(egui::Align::Center), |ui| {
-                        let mut wwm_text = "🎯 Tự động chọn vùng dịch WWM";
-                        if let Some(time) = self.wwm_state.wwm_success_timer {
-                            if time.elapsed().as_secs_f32() < SUCCESS_DISPLAY_DURATION_SECS { wwm_text = "✅ Đã chọn"; ctx.request_repaint(); }
-                            else { self.wwm_state.wwm_success_timer = None; }
-                        }
-                        if ui.add(egui::Button::new(wwm_text)).clicked() {
-                            if let Some((win_x, win_y, win_w, win_h)) = get_game_bounds("Where Winds Meet") {
-                                let f_w = win_w as f32;
-                                let f_h = win_h as f32;
-                                let region = config::Region {
-                                    x: win_x + (f_w * WWM_TEXT_REGION_X_RATIO) as i32 - WWM_REGION_PADDING,
-                                    y: win_y + (f_h * WWM_TEXT_REGION_Y_RATIO) as i32 - WWM_REGION_PADDING,
-                                    width: (f_w * WWM_TEXT_REGION_W_RATIO) as u32 + WWM_REGION_EXTRA_WIDTH,
-                                    height: (f_h * WWM_TEXT_REGION_H_RATIO) as u32 + WWM_REGION_EXTRA_HEIGHT
-                                };
-                                self.config_state.config.fixed_regions.clear();
-                                self.config_state.config.fixed_regions.push(region.clone());
-                                self.config_state.current_prompt = config::Config::get_wuxia_prompt();
-                                self.config_state.config.current_prompt = self.config_state.current_prompt.clone();
-                                self.config_state.editing_prompt_index = None;
-                                self.config_state.config.save().unwrap();
-                                self.sync_config_from_file();
-                                self.wwm_state.wwm_success_timer = Some(std::time::Instant::now());
-                                overlay::show_highlight(RECT{left: region.x, top: region.y, right: region.x + region.width as i32, bottom: region.y + region.height as i32});
-                            } else {
-                                show_alert("Không tìm thấy cửa sổ 'Where Winds Meet'. Vui lòng mở game trước.");
-                            }
-                        }
-                        ui.label(egui::RichText::new("(16:9)").italics().color(egui::Color32::GRAY));
-                    });
-                });
-                ui.horizontal(|ui| {
-                    ui.with_layout(egui::Layout::left_to_right(egui::Align::Center).with_main_align(egui::Align::Center), |ui| {
-                        let mut wwm_name_text = "🎯 Tự chọn vùng dịch WWM (có tên người thoại)";
-                        if let Some(time) = self.wwm_state.wwm_name_success_timer {
-                            if time.elapsed().as_secs_f32() < SUCCESS_DISPLAY_DURATION_SECS { wwm_name_text = "✅ Đã chọn"; ctx.request_repaint(); }
-                            else { self.wwm_state.wwm_name_success_timer = None; }
+                    ui.label("Game:");
+                    let current = self.config_state.config.active_game.trim().to_string();
+                    let selected_text = if current.is_empty() { "Chọn game".to_string() } else { current.clone() };
+                    let mut chosen = None;
+                    egui::ComboBox::from_id_source("game_profile").selected_text(selected_text).width(200.0).show_ui(ui, |ui| {
+                        for (i, profile) in self.ui_state.profiles.iter().enumerate() {
+                            let label = if profile.builtin { format!("{} (có sẵn)", profile.name) } else { profile.name.clone() };
+                            if ui.selectable_label(profile.name.trim().eq_ignore_ascii_case(&current), label).clicked() { chosen = Some(i); }
                         }
-                        if ui.add(egui::Button::new(wwm_name_text)).clicked() {
-                            if let Some((win_x, win_y, win_w, win_h)) = get_game_bounds("Where Winds Meet") {
-                                let f_w = win_w as f32;
-                                let f_h = win_h as f32;
-                                let region = config::Region {
-                                    x: win_x + (f_w * WWM_NAME_REGION_X_RATIO) as i32,
-                                    y: win_y + (f_h * WWM_NAME_REGION_Y_RATIO) as i32,
-                                    width: (f_w * WWM_NAME_REGION_W_RATIO) as u32,
-                                    height: (f_h * WWM_NAME_REGION_H_RATIO) as u32
-                                };
-                                self.config_state.config.fixed_regions.clear();
-                                self.config_state.config.fixed_regions.push(region.clone());
-                                self.config_state.current_prompt = config::Config::get_wuxia_speaker_prompt();
-                                self.config_state.config.current_prompt = self.config_state.current_prompt.clone();
-                                self.config_state.editing_prompt_index = None;
-                                self.config_state.config.save().unwrap();
-                                self.sync_config_from_file();
-                                self.wwm_state.wwm_name_success_timer = Some(std::time::Instant::now());
-                                overlay::show_highlight(RECT{left: region.x, top: region.y, right: region.x + region.width as i32, bottom: region.y + region.height as i32});
-                            } else {
-                                show_alert("Không tìm thấy cửa sổ 'Where Winds Meet'.");
-                            }
-                        }
-                        ui.label(egui::RichText::new("(16:9)").italics().color(egui::Color32::GRAY));
                     });
-                });
-                ui.horizontal(|ui| {
-                    ui.with_layout(egui::Layout::left_to_right(egui::Align::Center).with_main_align(egui::Align::Center), |ui| {
-                        let mut arrow_text = "🏹 Tự động chọn vùng Mũi tên WWM";
-                        if let Some(time) = self.wwm_state.arrow_wwm_success_timer {
-                            if time.elapsed().as_secs_f32() < SUCCESS_DISPLAY_DURATION_SECS { arrow_text = "✅ Đã chọn"; ctx.request_repaint(); }
-                            else { self.wwm_state.arrow_wwm_success_timer = None; }
-                        }
-                        if ui.add(egui::Button::new(arrow_text)).clicked() {
-                            if let Some((win_x, win_y, win_w, win_h)) = get_game_bounds("Where Winds Meet") {
-                                let f_w = win_w as f32;
-                                let f_h = win_h as f32;
-                                let region = config::Region {
-                                    x: win_x + (f_w * WWM_ARROW_REGION_X_RATIO) as i32,
-                                    y: win_y + (f_h * WWM_ARROW_REGION_Y_RATIO) as i32,
-                                    width: (f_w * WWM_ARROW_REGION_W_RATIO) as u32,
-                                    height: (f_h * WWM_ARROW_REGION_H_RATIO) as u32
-                                };
-                                self.config_state.config.arrow_region = Some(region.clone());
-                                self.config_state.config.save().unwrap();
-                                self.sync_config_from_file();
-                                self.wwm_state.arrow_wwm_success_timer = Some(std::time::Instant::now());
-                                overlay::show_highlight(RECT{left: region.x, top: region.y, right: region.x + region.width as i32, bottom: region.y + region.height as i32});
-                            } else {
-                                show_alert("Không tìm thấy cửa sổ 'Where Winds Meet'.");
-                            }
+                    if let Some(i) = chosen {
+                        let profile = self.ui_state.profiles[i].clone();
+                        let errors = profile.activate(&mut self.config_state.config, &config::Config::get_config_dir());
+                        self.config_state.config.save().unwrap();
+                        // Thuật ngữ / mẫu mũi tên vừa thêm: nạp lại để cửa sổ đang mở không ghi đè
+                        self.ui_state.glossary = glossary::Glossary::load();
+                        self.ui_state.arrow_library = templates::TemplateLibrary::load();
+                        self.game_state.preset_success = None;
+                        self.ui_state.profile_status = if errors.is_empty() { format!("Đã chọn {}", profile.name) } else { format!("Đã chọn {}, lỗi: {}", profile.name, errors.join("; ")) };
+                    }
+                    if ui.button("🔄").on_hover_text("Đọc lại thư mục profiles").clicked() {
+                        let (loaded, errors) = profiles::GameProfile::load_all();
+                        self.ui_state.profile_status = if errors.is_empty() { format!("{} profile", loaded.len()) } else { format!("Lỗi: {}", errors.join("; ")) };
+                        self.ui_state.profiles = loaded;
+                    }
+                    if ui.button("📂").on_hover_text("Mở thư mục profiles (mỗi game 1 file .json hoặc .toml)").clicked() {
+                        let dir = profiles::GameProfile::get_dir();
+                        let _ = std::fs::create_dir_all(&dir);
+                        let _ = std::process::Command::new("explorer").arg(dir).spawn();
+                    }
+                    let export = ui.button("📤").on_hover_text("Xuất profile đang chọn thành file .json để sửa / làm mẫu cho game khác (chuột phải: .toml)");
+                    if export.clicked() || export.secondary_clicked() {
+                        if let Some(profile) = self.ui_state.profiles.iter().find(|p| p.name.trim().eq_ignore_ascii_case(&current)) {
+                            self.ui_state.profile_status = match profile.export(export.secondary_clicked()) {
+                                Ok(path) => format!("Đã lưu {}", path.display()),
+                                Err(e) => format!("Lỗi: {}", e),
+                            };
                         }
-                        ui.label(egui::RichText::new("(16:9)").italics().color(egui::Color32::GRAY));
-                        if ui.button("🖼️").clicked() { self.ui_state.show_arrow_window = true; }
-                    });
+                    }
                 });
+                if !self.ui_state.profile_status.is_empty() {
+                    ui.label(egui::RichText::new(&self.ui_state.profile_status).small().weak());
+                }
+
+                let active_game = self.config_state.config.active_game.trim().to_string();
+                let profile = self.ui_state.profiles.iter().find(|p| p.name.trim().eq_ignore_ascii_case(&active_game)).cloned();
+                match &profile {
+                    Some(profile) => for (i, preset) in profile.regions.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.with_layout(egui::Layout::left_to_right(egui::Align::Center).with_main_align(egui::Align::Center), |ui| {
+                                let icon = match preset.role { profiles::RegionRole::Text => "🎯", profiles::RegionRole::Arrow => "🏹" };
+                                let mut preset_text = format!("{} Tự động chọn {}", icon, preset.name);
+                                if let Some((_, time)) = self.game_state.preset_success.filter(|(index, _)| *index == i) {
+                                    if time.elapsed().as_secs_f32() < SUCCESS_DISPLAY_DURATION_SECS { preset_text = "✅ Đã chọn".to_string(); ctx.request_repaint(); }
+                                    else { self.game_state.preset_success = None; }
+                                }
+                                if ui.add(egui::Button::new(preset_text)).clicked() {
+                                    match profile.find_window() {
+                                        Some(bounds) => match profile.region(preset, bounds) {
+                                            Some(region) => {
+                                                match preset.role {
+                                                    profiles::RegionRole::Text => {
+                                                        self.config_state.config.fixed_regions.clear();
+                                                        self.config_state.config.fixed_regions.push(region.clone());
+                                                        let prompt = profile.prompt_for(preset);
+                                                        if !prompt.trim().is_empty() {
+                                                            self.config_state.current_prompt = prompt.to_string();
+                                                            self.config_state.config.current_prompt = self.config_state.current_prompt.clone();
+                                                            self.config_state.config.active_prompt = None;
+                                                        }
+                                                    }
+                                                    profiles::RegionRole::Arrow => self.config_state.config.arrow_region = Some(region.clone()),
+                                                }
+                                                self.config_state.config.save().unwrap();
+                                                self.sync_config_from_file();
+                                                self.game_state.preset_success = Some((i, std::time::Instant::now()));
+                                                overlay::show_highlight(RECT{left: region.x, top: region.y, right: region.x + region.width as i32, bottom: region.y + region.height as i32});
+                                            }
+                                            None => show_alert("Profile chưa có toạ độ cho vùng này."),
+                                        },
+                                        None => show_alert(&format!("Không tìm thấy cửa sổ '{}'. Vui lòng mở game trước.", profile.window_label())),
+                                    }
+                                }
+                                ui.label(egui::RichText::new(format!("({})", preset.aspects())).italics().color(egui::Color32::GRAY));
+                                if preset.role == profiles::RegionRole::Arrow && ui.button("🖼️").clicked() {
+                                    self.ui_state.arrow_library = templates::TemplateLibrary::load();
+                                    self.ui_state.arrow_status.clear();
+                                    self.ui_state.show_arrow_window = true;
+                                }
+                            });
+                        });
+                    },
+                    None => { ui.label(egui::RichText::new("Chọn game để dùng các vùng đặt sẵn của game đó.").weak()); }
+                }
 
                 // --- ĐÃ ẨN SLIDER TỐC ĐỘ MŨI TÊN TẠI ĐÂY ---
 
+                // Cách kích hoạt auto: mũi tên hoặc chữ đổi rồi đứng yên (game không có mũi tên)
+                ui.add_space(5.0);
+                let game = self.config_state.config.active_game.trim().to_string();
+                let mut changed = false;
+                let mut per_game = !game.is_empty() && self.config_state.config.game_triggers.contains_key(&game);
+                if !game.is_empty() && ui.checkbox(&mut per_game, format!("Riêng cho game: {}", game)).changed() {
+                    if per_game {
+                        let current = self.config_state.config.auto_trigger.clone();
+                        self.config_state.config.game_triggers.insert(game.clone(), current);
+                    } else {
+                        self.config_state.config.game_triggers.remove(&game);
+                    }
+                    changed = true;
+                }
+                let cfg = &mut self.config_state.config;
+                let settings = match cfg.game_triggers.get_mut(&game) {
+                    Some(settings) if per_game => settings,
+                    _ => &mut cfg.auto_trigger,
+                };
+                ui.horizontal(|ui| {
+                    ui.label("Kích hoạt auto:");
+                    changed |= ui.radio_value(&mut settings.mode, config::TriggerMode::Arrow, "🏹 Mũi tên").changed();
+                    changed |= ui.radio_value(&mut settings.mode, config::TriggerMode::TextStable, "📝 Chữ đổi rồi đứng yên").changed();
+                });
+                if settings.mode == config::TriggerMode::TextStable {
+                    ui.horizontal(|ui| {
+                        ui.label("Đứng yên:");
+                        changed |= ui.add(egui::Slider::new(&mut settings.stable_frames, 1..=20).text("lần kiểm tra")).changed();
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Ngưỡng đổi:");
+                        changed |= ui.add(egui::Slider::new(&mut settings.change_threshold, 0..=64).text("bit")).changed();
+                    }).response.on_hover_text("Số bit hash khác nhau tối đa vẫn coi là cùng một câu");
+                } else {
+                    let matching = &mut cfg.arrow_match;
+                    ui.horizontal(|ui| {
+                        ui.label("Độ khớp mũi tên:");
+                        changed |= ui.add(egui::Slider::new(&mut matching.threshold, 0.5..=0.99)).changed();
+                    }).response.on_hover_text("Độ tin cậy tối thiểu (tương quan chuẩn hoá) để coi là thấy mũi tên");
+                    ui.horizontal(|ui| {
+                        ui.label("Bước quét:");
+                        changed |= ui.add(egui::Slider::new(&mut matching.step, 1..=4).text("px")).changed();
+                        ui.label("Alpha nền:");
+                        changed |= ui.add(egui::DragValue::new(&mut matching.alpha_cutoff).clamp_range(0..=255)).changed();
+                    });
+                }
+                if changed { self.config_state.config.save().unwrap(); }
+
                 ui.add_space(5.0);
                 ui.horizontal(|ui| {
                     ui.with_layout(egui::Layout::left_to_right(egui::Align::Center).with_main_align(egui::Align::Center), |ui| {
-                        let btn_text = if self.wwm_state.auto_translate_active { "🔄 ĐANG BẬT TỰ ĐỘNG DỊCH" } else { "🔄 Bật Tự Động Dịch" };
-                        let btn_color = if self.wwm_state.auto_translate_active { egui::Color32::DARK_GREEN } else { egui::Color32::from_rgb(60, 60, 60) };
+                        let btn_text = if self.game_state.auto_translate_active { "🔄 ĐANG BẬT TỰ ĐỘNG DỊCH" } else { "🔄 Bật Tự Động Dịch" };
+                        let btn_color = if self.game_state.auto_translate_active { egui::Color32::DARK_GREEN } else { egui::Color32::from_rgb(60, 60, 60) };
                         if ui.add(egui::Button::new(egui::RichText::new(btn_text).strong().color(egui::Color32::WHITE)).fill(btn_color).min_size(egui::vec2(200.0, 30.0))).clicked() {
-                            self.wwm_state.auto_translate_active = !self.wwm_state.auto_translate_active;
-                            AUTO_TRANSLATE_ENABLED.store(self.wwm_state.auto_translate_active, Ordering::Relaxed);
-                            crate::show_toggle_notification(self.wwm_state.auto_translate_active);
+                            self.game_state.auto_translate_active = !self.game_state.auto_translate_active;
+                            AUTO_TRANSLATE_ENABLED.store(self.game_state.auto_translate_active, Ordering::Relaxed);
+                            crate::show_toggle_notification(self.game_state.auto_translate_active);
                         }
                     });
                 });
@@ -630,22 +1228,10 @@ impl UiRenderer for super::MainApp {
                         if ui.button("🐞 Debug Overlay").clicked() {
                             overlay::toggle_debug_overlay();
                         }
-                        if ui.button("📂 Đổi ảnh mũi tên").clicked() {
-                            if let Some(path) = rfd::FileDialog::new().add_filter("Image", &["png"]).pick_file() {
-                                if let Ok(bytes) = fs::read(&path) {
-                                    let dest = config::Config::get_custom_arrow_path();
-                                    if let Ok(_) = fs::write(&dest, &bytes) {
-                                        self.load_texture(ctx, &bytes, true);
-                                    }
-                                }
-                            }
-                        }
-                        let custom_path = config::Config::get_custom_arrow_path();
-                        if custom_path.exists() {
-                            if ui.button("❌ Reset Mặc định").clicked() {
-                                let _ = fs::remove_file(custom_path);
-                                self.load_texture(ctx, crate::DEFAULT_ARROW, true);
-                            }
+                        if ui.button("🏹 Mẫu mũi tên").clicked() {
+                            self.ui_state.arrow_library = templates::TemplateLibrary::load();
+                            self.ui_state.arrow_status.clear();
+                            self.ui_state.show_arrow_window = true;
                         }
                     });
                 });
@@ -659,6 +1245,8 @@ impl UiRenderer for super::MainApp {
         self.config_state.config.arrow_region = new_config.arrow_region;
         self.config_state.config.instant_region = new_config.instant_region;
         self.config_state.config.aux_regions = new_config.aux_regions;
+        // Bộ đếm request / cooldown do thread dịch ghi vào file
+        self.config_state.config.merge_key_usage(&new_config);
     }
 
     fn check_key_binding(&mut self) {
@@ -674,6 +1262,7 @@ impl UiRenderer for super::MainApp {
                             BindingTarget::Instant => { self.hotkey_state.hotkey_instant = key_name.clone(); self.config_state.config.hotkey_instant = key_name; }
                             BindingTarget::Auto => { self.hotkey_state.hotkey_auto = key_name.clone(); self.config_state.config.hotkey_auto = key_name; }
                             BindingTarget::ToggleAuto => { self.hotkey_state.hotkey_toggle_auto = key_name.clone(); self.config_state.config.hotkey_toggle_auto = key_name; }
+                            BindingTarget::ClearContext => { self.hotkey_state.hotkey_clear_context = key_name.clone(); self.config_state.config.hotkey_clear_context = key_name; }
                             BindingTarget::AuxSelect(idx) => { if idx < self.config_state.config.aux_regions.len() { self.config_state.config.aux_regions[idx].hotkey_select = key_name; } }
                             BindingTarget::AuxTranslate(idx) => { if idx < self.config_state.config.aux_regions.len() { self.config_state.config.aux_regions[idx].hotkey_translate = key_name; } }
                         }
@@ -744,15 +1333,236 @@ impl UiRenderer for super::MainApp {
         self.ui_state.reader.is_open = open;
     }
 
-    fn load_texture(&mut self, ctx: &egui::Context, bytes: &[u8], is_arrow: bool) {
-        if let Ok(image) = image::load_from_memory(bytes) {
-            let size = [image.width() as usize, image.height() as usize];
-            let image_buffer = image.to_rgba8();
-            let pixels = image_buffer.into_raw();
-            let image_data = egui::ColorImage::from_rgba_unmultiplied(size, &pixels);
-            let name = if is_arrow { "arrow_img" } else { "area_img" };
-            let texture = ctx.load_texture(name, image_data, egui::TextureOptions::default());
-            if is_arrow { self.arrow_texture = Some(texture); }
-        }
+    fn render_glossary_window(&mut self, ctx: &egui::Context) {
+        if !self.ui_state.show_glossary { return; }
+        let mut open = true;
+        egui::Window::new("📚 Thuật ngữ (Glossary)").open(&mut open).default_size(egui::vec2(620.0, 500.0)).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Game đang chơi:");
+                if ui.add(egui::TextEdit::singleline(&mut self.config_state.config.active_game).hint_text("vd: Where Winds Meet").desired_width(180.0)).changed() {
+                    self.config_state.config.save().unwrap();
+                }
+                if ui.add(egui::Checkbox::new(&mut self.config_state.config.glossary_auto_fix, "Tự sửa khi model để nguyên từ gốc")).changed() {
+                    self.config_state.config.save().unwrap();
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button("📥 Nhập CSV/TSV").clicked() {
+                    if let Some(path) = rfd::FileDialog::new().add_filter("Glossary", &["csv", "tsv", "txt"]).pick_file() {
+                        self.ui_state.glossary_status = match self.ui_state.glossary.import(&path) {
+                            Ok(count) => { let _ = self.ui_state.glossary.save(); format!("Đã nhập {} mục", count) },
+                            Err(e) => format!("Lỗi: {}", e),
+                        };
+                    }
+                }
+                if ui.button("📤 Xuất").clicked() {
+                    if let Some(path) = rfd::FileDialog::new().add_filter("CSV", &["csv"]).add_filter("TSV", &["tsv"]).set_file_name("glossary.csv").save_file() {
+                        self.ui_state.glossary_status = match self.ui_state.glossary.export(&path) {
+                            Ok(()) => "Đã xuất file".to_string(),
+                            Err(e) => format!("Lỗi: {}", e),
+                        };
+                    }
+                }
+                if ui.button("➕ Thêm").clicked() {
+                    self.ui_state.glossary.entries.push(glossary::GlossaryEntry { game: self.config_state.config.active_game.clone(), ..Default::default() });
+                }
+                ui.label(egui::RichText::new(&self.ui_state.glossary_status).weak());
+            });
+            ui.separator();
+
+            let mut changed = false;
+            let mut remove_index = None;
+            egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                egui::Grid::new("glossary_grid").num_columns(5).striped(true).show(ui, |ui| {
+                    ui.label(egui::RichText::new("Từ gốc").strong());
+                    ui.label(egui::RichText::new("Bản dịch").strong());
+                    ui.label(egui::RichText::new("Ghi chú").strong());
+                    ui.label(egui::RichText::new("Game").strong());
+                    ui.label("");
+                    ui.end_row();
+                    for (i, entry) in self.ui_state.glossary.entries.iter_mut().enumerate() {
+                        changed |= ui.add(egui::TextEdit::singleline(&mut entry.source).desired_width(120.0)).changed();
+                        changed |= ui.add(egui::TextEdit::singleline(&mut entry.target).desired_width(120.0)).changed();
+                        changed |= ui.add(egui::TextEdit::singleline(&mut entry.notes).desired_width(140.0)).changed();
+                        changed |= ui.add(egui::TextEdit::singleline(&mut entry.game).hint_text("(mọi game)").desired_width(100.0)).changed();
+                        if ui.small_button("❌").clicked() { remove_index = Some(i); }
+                        ui.end_row();
+                    }
+                });
+            });
+            if let Some(i) = remove_index {
+                self.ui_state.glossary.entries.remove(i);
+                changed = true;
+            }
+            if changed { let _ = self.ui_state.glossary.save(); }
+
+            let violations = glossary::LAST_VIOLATIONS.lock().unwrap().clone();
+            if !violations.is_empty() {
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new("⚠ Lần dịch gần nhất chưa dùng đúng thuật ngữ:").color(egui::Color32::from_rgb(230, 160, 0)));
+                    if ui.small_button("Bỏ qua").clicked() { glossary::LAST_VIOLATIONS.lock().unwrap().clear(); }
+                });
+                for v in &violations { ui.label(v); }
+            }
+        });
+        self.ui_state.show_glossary = open;
+    }
+
+    fn render_arrow_templates_window(&mut self, ctx: &egui::Context) {
+        if !self.ui_state.show_arrow_window { return; }
+        let mut open = true;
+        egui::Window::new("🏹 Mẫu mũi tên").open(&mut open).default_size(egui::vec2(520.0, 420.0)).show(ctx, |ui| {
+            ui.label("Auto dịch khi thấy bất kỳ mẫu nào của game đang chơi. Chưa có mẫu nào thì dùng mũi tên mặc định.");
+            ui.horizontal(|ui| {
+                ui.label("Game đang chơi:");
+                if ui.add(egui::TextEdit::singleline(&mut self.config_state.config.active_game).hint_text("vd: Where Winds Meet").desired_width(180.0)).changed() {
+                    self.config_state.config.save().unwrap();
+                }
+                if ui.button("➕ Thêm ảnh").clicked() {
+                    if let Some(paths) = rfd::FileDialog::new().add_filter("Image", &["png"]).pick_files() {
+                        let game = self.config_state.config.active_game.clone();
+                        let errors: Vec<String> = paths.iter().filter_map(|p| self.ui_state.arrow_library.add(p, &game).err().map(|e| e.to_string())).collect();
+                        let _ = self.ui_state.arrow_library.save();
+                        self.ui_state.arrow_status = if errors.is_empty() { format!("Đã thêm {} ảnh", paths.len()) } else { format!("Lỗi: {}", errors.join("; ")) };
+                    }
+                }
+                if ui.button("✂️ Khoanh trên màn hình").clicked() && !OVERLAY_ACTIVE.load(Ordering::Relaxed) {
+                    OVERLAY_ACTIVE.store(true, Ordering::Relaxed);
+                    let slot = self.ui_state.template_capture.clone();
+                    let ctx = ctx.clone();
+                    std::thread::spawn(move || {
+                        overlay::set_selection_mode(3);
+                        overlay::show_selection_overlay();
+                        OVERLAY_ACTIVE.store(false, Ordering::Relaxed);
+                        if let Some(region) = overlay::take_template_selection() {
+                            // Đợi overlay biến mất hẳn rồi mới chụp
+                            std::thread::sleep(std::time::Duration::from_millis(150));
+                            if let Ok(img) = capture::capture_raw(&region) {
+                                *slot.lock().unwrap() = Some(img.into_rgba8());
+                                ctx.request_repaint();
+                            }
+                        }
+                    });
+                }
+            });
+            ui.label(egui::RichText::new(&self.ui_state.arrow_status).weak());
+
+            if let Some(img) = self.ui_state.template_capture.lock().unwrap().take() {
+                let draft = &mut self.ui_state.template_draft;
+                draft.image = Some(img);
+                draft.cleaned = None;
+            }
+            if self.ui_state.template_draft.image.is_some() {
+                ui.separator();
+                ui.label(egui::RichText::new("Mẫu mới từ màn hình").strong());
+                let draft = &mut self.ui_state.template_draft;
+                let mut dirty = draft.cleaned.is_none();
+                ui.horizontal(|ui| {
+                    dirty |= ui.checkbox(&mut draft.auto_crop, "Tự cắt sát").changed();
+                    dirty |= ui.checkbox(&mut draft.transparent, "Nền trong suốt").changed();
+                    ui.label("Lệch màu nền:");
+                    dirty |= ui.add(egui::Slider::new(&mut draft.tolerance, 0..=120)).changed();
+                });
+                if dirty {
+                    if let Some(image) = &draft.image {
+                        let cleaned = templates::clean_capture(image, draft.auto_crop, draft.transparent, draft.tolerance);
+                        let image_data = egui::ColorImage::from_rgba_unmultiplied([cleaned.width() as usize, cleaned.height() as usize], cleaned.as_raw());
+                        draft.preview = Some(ctx.load_texture("template_draft", image_data, egui::TextureOptions::default()));
+                        draft.cleaned = Some(cleaned);
+                    }
+                }
+                if let Some(texture) = &draft.preview {
+                    let size = texture.size_vec2();
+                    ui.image((texture.id(), size * (64.0 / size.y.max(1.0)).min(4.0)));
+                }
+                let (mut save, mut discard) = (false, false);
+                ui.horizontal(|ui| {
+                    ui.label("Tên:");
+                    ui.add(egui::TextEdit::singleline(&mut draft.name).hint_text("mũi tên").desired_width(140.0));
+                    save = ui.button("💾 Lưu vào thư viện").clicked();
+                    discard = ui.button("Huỷ").clicked();
+                });
+
+                if save {
+                    if let Some(cleaned) = self.ui_state.template_draft.cleaned.clone() {
+                        let game = self.config_state.config.active_game.clone();
+                        let name = self.ui_state.template_draft.name.clone();
+                        self.ui_state.arrow_status = match self.ui_state.arrow_library.add_image(&cleaned, &name, &game) {
+                            Ok(template) => {
+                                // Thử ngay mẫu mới trên vùng mũi tên đang chọn
+                                let settings = template.match_settings(&self.config_state.config.arrow_match);
+                                let found = self.config_state.config.arrow_region.as_ref()
+                                    .and_then(|region| capture::capture_raw(region).ok())
+                                    .and_then(|shot| matcher::TemplateMatcher::new(&cleaned, &settings).best(&shot.into_rgba8()));
+                                match (&self.config_state.config.arrow_region, found) {
+                                    (None, _) => "Đã lưu. Chưa chọn vùng mũi tên để thử.".to_string(),
+                                    (Some(_), None) => "Đã lưu. Không thử được: mẫu lớn hơn vùng mũi tên?".to_string(),
+                                    (Some(_), Some(m)) => format!("Đã lưu. Độ khớp với vùng mũi tên hiện tại: {:.2} ({} ngưỡng {:.2})", m.confidence, if m.confidence >= settings.threshold { "đạt" } else { "chưa đạt" }, settings.threshold),
+                                }
+                            }
+                            Err(e) => format!("Lỗi: {}", e),
+                        };
+                        let _ = self.ui_state.arrow_library.save();
+                        discard = true;
+                    }
+                }
+                if discard {
+                    // Giữ lại tuỳ chọn cắt / xoá nền cho lần sau
+                    let draft = &mut self.ui_state.template_draft;
+                    draft.image = None;
+                    draft.cleaned = None;
+                    draft.preview = None;
+                    draft.name.clear();
+                }
+            }
+            ui.separator();
+
+            let mut changed = false;
+            let mut remove_index = None;
+            let global_threshold = self.config_state.config.arrow_match.threshold;
+            egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                egui::Grid::new("arrow_templates_grid").num_columns(5).striped(true).show(ui, |ui| {
+                    ui.label("");
+                    ui.label(egui::RichText::new("Tên").strong());
+                    ui.label(egui::RichText::new("Game").strong());
+                    ui.label(egui::RichText::new("Ngưỡng riêng").strong());
+                    ui.label("");
+                    ui.end_row();
+                    for (i, template) in self.ui_state.arrow_library.templates.iter_mut().enumerate() {
+                        let texture = self.ui_state.arrow_textures.entry(template.file.clone()).or_insert_with(|| {
+                            let image = template.read().and_then(|b| image::load_from_memory(&b).ok()).unwrap_or_default().to_rgba8();
+                            let image_data = egui::ColorImage::from_rgba_unmultiplied([image.width() as usize, image.height() as usize], image.as_raw());
+                            ctx.load_texture(format!("arrow_{}", template.file), image_data, egui::TextureOptions::default())
+                        });
+                        let size = texture.size_vec2();
+                        ui.image((texture.id(), size * (32.0 / size.y.max(1.0)).min(2.0)));
+                        changed |= ui.add(egui::TextEdit::singleline(&mut template.name).desired_width(120.0)).changed();
+                        changed |= ui.add(egui::TextEdit::singleline(&mut template.game).hint_text("(mọi game)").desired_width(110.0)).changed();
+                        ui.horizontal(|ui| {
+                            let mut custom = template.threshold.is_some();
+                            if ui.checkbox(&mut custom, "").changed() {
+                                template.threshold = if custom { Some(global_threshold) } else { None };
+                                changed = true;
+                            }
+                            if let Some(threshold) = template.threshold.as_mut() {
+                                changed |= ui.add(egui::Slider::new(threshold, 0.5..=0.99)).changed();
+                            } else {
+                                ui.label(egui::RichText::new(format!("{:.2} (chung)", global_threshold)).weak());
+                            }
+                        });
+                        if ui.small_button("❌").clicked() { remove_index = Some(i); }
+                        ui.end_row();
+                    }
+                });
+            });
+            if let Some(i) = remove_index {
+                self.ui_state.arrow_textures.remove(&self.ui_state.arrow_library.templates[i].file);
+                self.ui_state.arrow_library.remove(i);
+                changed = true;
+            }
+            if changed { let _ = self.ui_state.arrow_library.save(); }
+        });
+        self.ui_state.show_arrow_window = open;
     }
 }
\ No newline at end of file
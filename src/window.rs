@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::os::windows::ffi::OsStrExt;
+use std::sync::{Mutex, OnceLock};
+use winapi::shared::minwindef::{BOOL, DWORD, FALSE, LPARAM, TRUE};
+use winapi::shared::windef::{HWND, POINT, RECT};
+use winapi::um::handleapi::CloseHandle;
+use winapi::um::processthreadsapi::{GetCurrentProcessId, OpenProcess};
+use winapi::um::winbase::QueryFullProcessImageNameW;
+use winapi::um::winnt::PROCESS_QUERY_LIMITED_INFORMATION;
+use winapi::um::winuser::{
+    ClientToScreen, EnumWindows, FindWindowW, GetClientRect, GetWindowTextLengthW, GetWindowTextW,
+    GetWindowThreadProcessId, IsIconic, IsWindowVisible,
+};
+use crate::config::{Region, WindowAnchor};
+
+/// Vùng client của cửa sổ theo toạ độ màn hình: (x, y, rộng, cao)
+pub type Bounds = (i32, i32, u32, u32);
+
+// Cửa sổ tìm theo tên tiến trình lần trước (tên viết thường -> HWND), để không phải duyệt mọi cửa sổ mỗi lần chụp
+static PROCESS_WINDOWS: OnceLock<Mutex<HashMap<String, usize>>> = OnceLock::new();
+
+fn to_wide(s: &str) -> Vec<u16> {
+    OsStr::new(s).encode_wide().chain(std::iter::once(0)).collect()
+}
+
+fn client_bounds(hwnd: HWND) -> Option<Bounds> {
+    unsafe {
+        // Cửa sổ thu nhỏ có vùng client 0x0
+        if hwnd.is_null() || IsWindowVisible(hwnd) == 0 || IsIconic(hwnd) != 0 { return None; }
+        let mut rect: RECT = std::mem::zeroed();
+        if GetClientRect(hwnd, &mut rect) == 0 { return None; }
+        let mut point = POINT { x: 0, y: 0 };
+        if ClientToScreen(hwnd, &mut point) == 0 { return None; }
+        let width = (rect.right - rect.left) as u32;
+        let height = (rect.bottom - rect.top) as u32;
+        if width == 0 || height == 0 { return None; }
+        Some((point.x, point.y, width, height))
+    }
+}
+
+/// Vùng client của cửa sổ có tiêu đề đúng bằng `window_title`
+fn game_bounds(window_title: &str) -> Option<Bounds> {
+    let wide_title = to_wide(window_title);
+    client_bounds(unsafe { FindWindowW(std::ptr::null(), wide_title.as_ptr()) })
+}
+
+fn process_id(hwnd: HWND) -> DWORD {
+    let mut pid: DWORD = 0;
+    unsafe { GetWindowThreadProcessId(hwnd, &mut pid); }
+    pid
+}
+
+/// Tên file của tiến trình (vd. "wwm.exe")
+fn process_name(pid: DWORD) -> Option<String> {
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, pid);
+        if handle.is_null() { return None; }
+        let mut buffer = [0u16; 1024];
+        let mut len = buffer.len() as DWORD;
+        let ok = QueryFullProcessImageNameW(handle, 0, buffer.as_mut_ptr(), &mut len);
+        CloseHandle(handle);
+        if ok == 0 { return None; }
+        let path = String::from_utf16_lossy(&buffer[..len as usize]);
+        path.rsplit(['\\', '/']).next().map(|s| s.to_string())
+    }
+}
+
+fn window_title(hwnd: HWND) -> String {
+    unsafe {
+        let len = GetWindowTextLengthW(hwnd);
+        if len <= 0 { return String::new(); }
+        let mut buffer = vec![0u16; len as usize + 1];
+        let copied = GetWindowTextW(hwnd, buffer.as_mut_ptr(), buffer.len() as i32);
+        String::from_utf16_lossy(&buffer[..copied.max(0) as usize])
+    }
+}
+
+/// Các cửa sổ chính đang hiện, theo thứ tự chồng lớp (trên cùng trước)
+fn visible_windows() -> Vec<HWND> {
+    unsafe extern "system" fn collect(hwnd: HWND, lparam: LPARAM) -> BOOL {
+        let windows = &mut *(lparam as *mut Vec<HWND>);
+        if IsWindowVisible(hwnd) != 0 { windows.push(hwnd); }
+        TRUE
+    }
+    let mut windows: Vec<HWND> = Vec::new();
+    unsafe { EnumWindows(Some(collect), &mut windows as *mut Vec<HWND> as LPARAM); }
+    windows
+}
+
+/// Cửa sổ lớn nhất của tiến trình `name` (cửa sổ game, không phải launcher / tooltip)
+fn find_by_process(name: &str) -> Option<HWND> {
+    let key = name.to_lowercase();
+    let cache = PROCESS_WINDOWS.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Some(&hwnd) = cache.lock().unwrap().get(&key) {
+        if client_bounds(hwnd as HWND).is_some() { return Some(hwnd as HWND); }
+    }
+
+    let mut names: HashMap<DWORD, Option<String>> = HashMap::new();
+    let hwnd = visible_windows().into_iter()
+        .filter_map(|hwnd| client_bounds(hwnd).map(|(_, _, w, h)| (hwnd, w as u64 * h as u64)))
+        .filter(|&(hwnd, _)| {
+            let pid = process_id(hwnd);
+            names.entry(pid).or_insert_with(|| process_name(pid)).as_deref().map_or(false, |n| n.eq_ignore_ascii_case(name))
+        })
+        .max_by_key(|&(_, area)| area)
+        .map(|(hwnd, _)| hwnd)?;
+    cache.lock().unwrap().insert(key, hwnd as usize);
+    Some(hwnd)
+}
+
+/// Vùng client của cửa sổ game: tìm theo tiêu đề trước, rồi theo tiến trình
+pub fn find_bounds(window_title: &str, process_name: &str) -> Option<Bounds> {
+    if !window_title.is_empty() {
+        if let Some(bounds) = game_bounds(window_title) { return Some(bounds); }
+    }
+    if !process_name.is_empty() {
+        return find_by_process(process_name).and_then(client_bounds);
+    }
+    None
+}
+
+/// Vị trí hiện tại của cửa sổ được gắn
+pub fn anchor_bounds(anchor: &WindowAnchor) -> Option<Bounds> {
+    find_bounds(&anchor.window_title, &anchor.process_name)
+}
+
+/// Vùng theo toạ độ màn hình lúc này: vùng gắn cửa sổ được tính lại theo vị trí / kích thước cửa sổ,
+/// không thấy cửa sổ thì dùng vị trí lúc lưu
+pub fn resolve(region: &Region) -> Region {
+    match &region.anchor {
+        Some(anchor) => match anchor_bounds(anchor) {
+            Some(bounds) => anchor.place(bounds),
+            None => Region { anchor: None, ..region.clone() },
+        },
+        None => region.clone(),
+    }
+}
+
+/// Gắn vùng vừa khoanh vào cửa sổ trên cùng (không phải của app này) chứa tâm vùng
+pub fn bind_to_window_under(region: &Region) -> Option<Region> {
+    let (cx, cy) = (region.x + region.width as i32 / 2, region.y + region.height as i32 / 2);
+    let own_pid = unsafe { GetCurrentProcessId() };
+    visible_windows().into_iter().find_map(|hwnd| {
+        let pid = process_id(hwnd);
+        if pid == own_pid { return None; }
+        let bounds = client_bounds(hwnd)?;
+        let (bx, by, bw, bh) = bounds;
+        if cx < bx || cy < by || cx >= bx + bw as i32 || cy >= by + bh as i32 { return None; }
+        let title = window_title(hwnd);
+        let process = process_name(pid).unwrap_or_default();
+        if title.is_empty() && process.is_empty() { return None; }
+        Some(Region { anchor: Some(WindowAnchor::from_region(region, &title, &process, bounds)), ..region.clone() })
+    })
+}